@@ -1,8 +1,10 @@
 use std::error::Error;
 
-use glam::{IVec2, UVec2, Vec2, Vec3};
+use glam::{IVec2, UVec2, Vec2};
+use virae::scaling::ScalingMode;
 use virae::types::{
-    ComponentTransform, PixelRect, TextureSheetClusterDefinition, TextureSheetDefinition,
+    BlendMode, ComponentTransform, PixelRect, SamplerOptions, TextureSheetClusterDefinition,
+    TextureSheetDefinition,
 };
 use virae::window::Context;
 use virae::{Event, Vec4, WindowEvent};
@@ -21,10 +23,16 @@ async fn run() -> Result<(), Box<dyn Error>> {
     let (event_loop, window, mut context) =
         Context::new("testing", width, height, ControlFlow::Poll).await?;
 
+    // pixel art renders at a fixed size and scales up in whole steps.
+    context.set_virtual_resolution(UVec2::new(320, 240), ScalingMode::IntegerLetterbox);
+
+    // resolve asset paths from the crate root, wherever the example is run from.
+    context.geos.assets.mount_dir(env!("CARGO_MANIFEST_DIR"));
+
     // initialize assets
     let (group_index, player_index) = {
         let shader_path = "examples/testing/shader.wgsl";
-        context.file_watcher.add_path(shader_path);
+        let extent = context.logical_size();
         let config = context.config.lock().unwrap();
         let group_index = context.geos.new_unit_square(
             64,
@@ -40,16 +48,18 @@ async fn run() -> Result<(), Box<dyn Error>> {
                     sub_size: UVec2::new(16, 16),
                     spacing: UVec2::new(0, 0),
                 }],
+                sampler: SamplerOptions::default(),
             },
             shader_path,
+            BlendMode::Premultiplied,
         )?;
 
         let player_index = context.geos.instance_groups[0].add_new(
             context.queue.clone(),
             ComponentTransform::unit_square_transform_from_pixel_rect(PixelRect {
                 xy: IVec2::new(0, 0),
-                wh: UVec2::new(64, 64),
-                extent: UVec2::new(config.width, config.height),
+                wh: UVec2::new(16, 16),
+                extent,
             }),
             0,
             0,
@@ -57,6 +67,7 @@ async fn run() -> Result<(), Box<dyn Error>> {
         );
         (group_index, player_index)
     };
+    context.watch_assets();
 
     struct DigitalInputState {
         up: bool,
@@ -70,7 +81,8 @@ async fn run() -> Result<(), Box<dyn Error>> {
         left: false,
         right: false,
     };
-    let move_speed = 0.006;
+    // in world pixels per second.
+    let move_speed = 90.0;
 
     event_loop.run(move |event, target| {
         if let Event::WindowEvent {
@@ -116,6 +128,9 @@ async fn run() -> Result<(), Box<dyn Error>> {
                     }
                     _ => (),
                 },
+                WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                    context.set_scale_factor(scale_factor);
+                }
                 WindowEvent::Resized(size) => {
                     context.resize(size);
                     window.request_redraw();
@@ -142,7 +157,7 @@ async fn run() -> Result<(), Box<dyn Error>> {
                         context.geos.instance_groups[group_index]
                             .instance_buffer_manager
                             .data[player_index]
-                            .translate(move_speed * Vec3::new(move_impulse.x, move_impulse.y, 0.0));
+                            .translate_px(move_speed * context.delta_time * move_impulse);
                     }
                     context.update().expect("event loop context update error");
                     context.render().expect("event loop context render error");
@@ -1,14 +1,14 @@
-use glam::{IVec2, UVec2};
+use glam::{IVec2, UVec2, Vec2};
 use rand::Rng;
 use std::error::Error;
-use std::sync::{Arc, Mutex};
-use virae::geo::GeoInstances;
+use virae::autotile::{AutotileRule, Neighbours, CORNER_NE, CORNER_NW, CORNER_SE, CORNER_SW};
+use virae::ldtk::FieldValue;
+use virae::tilemap::{TileDef, TileId, Tilemap};
 use virae::types::{
-    ComponentTransform, PixelRect, TextureSheetClusterDefinition, TextureSheetDefinition,
+    BlendMode, SamplerOptions, SpriteRef, TextureSheetClusterDefinition, TextureSheetDefinition,
 };
 use virae::window::Context;
-use virae::{Event, Vec4, WindowEvent};
-use wgpu::Queue;
+use virae::{Event, WindowEvent};
 use winit::event_loop::ControlFlow;
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -16,74 +16,72 @@ fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
-#[derive(Copy, Clone)]
-struct TerrainBlock {
-    cluster: usize,
-    sub_variants: UVec2,
-}
-
-const DIRT_INTERIOR: TerrainBlock = TerrainBlock {
-    cluster: 1,
-    sub_variants: UVec2::new(0, 4),
-};
+// each exterior cluster is one blob of terrain with its inner corners cut out: the
+// Wang corner layout, one mask per sub in order.
+const CORNERS: [u8; 16] = [2, 6, 6, 4, 3, 13, 11, 12, 3, 14, 7, 12, 1, 9, 9, 8];
 
-struct Terrain<'a> {
-    screen_size: UVec2,
-    queue: Arc<Mutex<Queue>>,
-    geo: &'a mut GeoInstances,
-    xy: UVec2,
-    wh: UVec2,
-    block_size: UVec2,
-    blocks: Vec<TerrainBlock>,
+// a terrain's edges from its exterior cluster and weighted fill from its interior one,
+// returning a tile to place it with.
+fn add_terrain(terrain: &mut Tilemap, exterior: usize, interior: usize, solid: bool) -> TileId {
+    let def = |cluster: usize, sub: usize| match solid {
+        true => TileDef::new(SpriteRef::new(cluster, sub)).solid(),
+        false => TileDef::new(SpriteRef::new(cluster, sub)),
+    };
+    let first = terrain.add_tile(def(exterior, 0));
+    for sub in 1..CORNERS.len() {
+        terrain.add_tile(def(exterior, sub));
+    }
+    let mut rule = AutotileRule::new(Neighbours::Corners).layout(first, &CORNERS);
+    rule.seed = exterior as u32;
+    let fill = terrain.add_tile(def(interior, 0));
+    rule = rule
+        .variant(CORNER_NE | CORNER_SE | CORNER_SW | CORNER_NW, fill, 4.0)
+        .fallback(fill, 1.0);
+    for (sub, weight) in [(1, 4.0), (2, 1.0), (3, 1.0)] {
+        rule = rule.variant(
+            CORNER_NE | CORNER_SE | CORNER_SW | CORNER_NW,
+            terrain.add_tile(def(interior, sub)),
+            weight,
+        );
+    }
+    terrain.add_autotile(rule);
+    fill
 }
 
-impl Terrain<'_> {
-    fn fill_with_block(&mut self, block: &TerrainBlock, zoom: u32) {
-        for x in 0..self.wh.x {
-            for y in 0..self.wh.y {
-                self.geo.add_new(
-                    self.queue.clone(),
-                    ComponentTransform::unit_square_transform_from_pixel_rect(PixelRect {
-                        xy: IVec2::new(
-                            (self.xy.x + self.block_size.x * x) as i32,
-                            (self.xy.y + self.block_size.y * y) as i32,
-                        ) * zoom as i32,
-                        wh: UVec2::new(self.block_size.x, self.block_size.y) * zoom,
-                        extent: UVec2::new(self.screen_size.x, self.screen_size.y),
-                    }),
-                    block.cluster,
-                    rand::thread_rng()
-                        .gen_range(block.sub_variants.x as usize..block.sub_variants.y as usize),
-                    Vec4::new(1.0, 1.0, 1.0, 1.0),
-                );
-                self.blocks.push(*block);
-            }
-        }
+// random fill smoothed into caves: true where there's ground.
+fn caves(size: UVec2, rng: &mut impl Rng) -> Vec<bool> {
+    let mut solid: Vec<bool> = (0..size.x * size.y).map(|_| rng.gen_bool(0.55)).collect();
+    for _ in 0..4 {
+        solid = (0..size.x * size.y)
+            .map(|i| {
+                let xy = IVec2::new((i % size.x) as i32, (i / size.x) as i32);
+                let mut count = 0;
+                for y in -1..=1 {
+                    for x in -1..=1 {
+                        let near = xy + IVec2::new(x, y);
+                        let outside =
+                            near.cmplt(IVec2::ZERO).any() || near.cmpge(size.as_ivec2()).any();
+                        if outside || solid[(near.y * size.x as i32 + near.x) as usize] {
+                            count += 1;
+                        }
+                    }
+                }
+                count >= 5
+            })
+            .collect();
     }
+    solid
 }
 
-// fn terrain_test(queue: Arc<Mutex<Queue>>, geo: &mut GeoInstances, screen_size: (u32, u32)) {
-//     geo.add_new(
-//         queue,
-//         ComponentTransform::unit_square_transform_from_pixel_rect(PixelRect {
-//             xy: UVec2::new(100, 100),
-//             wh: UVec2::new(16, 16),
-//             extent: UVec2::new(screen_size.0, screen_size.1),
-//         }),
-//         1,
-//         1,
-//         Vec4::new(1.0, 1.0, 1.0, 1.0),
-//     );
-// }
-
 async fn run() -> Result<(), Box<dyn Error>> {
     let (width, height) = (800, 600);
     let (event_loop, window, mut context) =
         Context::new("testing", width, height, ControlFlow::Wait).await?;
+    // resolve asset paths from the crate root, wherever the example is run from.
+    context.geos.assets.mount_dir(env!("CARGO_MANIFEST_DIR"));
 
     {
         let shader_path = "examples/testing/shader.wgsl";
-        context.file_watcher.add_path(shader_path);
         let config = context.config.lock().unwrap();
         context.geos.new_unit_square(
             64,
@@ -109,7 +107,7 @@ async fn run() -> Result<(), Box<dyn Error>> {
                     },
                     TextureSheetClusterDefinition {
                         label: "stone-exterior".to_string(),
-                        offset: UVec2::new(33, 0),
+                        offset: UVec2::new(32, 0),
                         cluster_size: UVec2::new(32, 32),
                         sub_size: UVec2::new(8, 8),
                         spacing: UVec2::new(0, 0),
@@ -136,28 +134,112 @@ async fn run() -> Result<(), Box<dyn Error>> {
                         spacing: UVec2::new(0, 0),
                     },
                 ],
+                sampler: SamplerOptions::default(),
             },
             shader_path,
+            BlendMode::Premultiplied,
         )?;
 
-        let mut terrain = Terrain {
-            screen_size: UVec2::new(width, height),
-            queue: context.queue.clone(),
-            geo: &mut context.geos.instance_groups[0],
-            xy: UVec2::new(32, 32),
-            wh: UVec2::new(8, 8),
-            block_size: UVec2::new(8, 8),
-            blocks: vec![],
-        };
+        // caves of dirt with stone veins, 8px tiles drawn four times larger. placed tiles
+        // are autotiled into edges and corners from the exterior clusters.
+        let mut terrain = Tilemap::new(0, UVec2::new(48, 40), Vec2::splat(8.0));
+        let dirt = add_terrain(&mut terrain, 0, 1, false);
+        let stone = add_terrain(&mut terrain, 2, 3, true);
+        let roots = terrain.add_tile(
+            TileDef::new(SpriteRef::new(5, 0))
+                .animated((0..4).map(|sub| (SpriteRef::new(5, sub), 0.25)).collect()),
+        );
+        let ground = terrain.add_layer("ground");
+        let veins = terrain.add_layer("veins");
+        let mut rng = rand::thread_rng();
+        let solid = caves(terrain.size, &mut rng);
+        let stony = caves(terrain.size, &mut rng);
+        for y in 0..terrain.size.y {
+            for x in 0..terrain.size.x {
+                let xy = UVec2::new(x, y);
+                let i = (y * terrain.size.x + x) as usize;
+                if solid[i] {
+                    terrain.set_tile(ground, xy, dirt);
+                }
+                if solid[i] && stony[i] {
+                    terrain.set_tile(veins, xy, stone);
+                }
+            }
+        }
+        // an empty room in the middle, walled with dirt and flooded with roots.
+        let middle = terrain.size / 2;
+        for y in middle.y - 5..=middle.y + 5 {
+            for x in middle.x - 7..=middle.x + 7 {
+                let xy = UVec2::new(x, y);
+                let wall = y.abs_diff(middle.y) >= 4 || x.abs_diff(middle.x) >= 6;
+                terrain.set_tile(ground, xy, if wall { dirt } else { 0 });
+                terrain.set_tile(veins, xy, 0);
+            }
+        }
+        terrain.flood_fill(ground, middle, roots);
+        context.tilemaps.push(terrain);
 
-        terrain.fill_with_block(&DIRT_INTERIOR, 4)
+        // a room made in Tiled, stamped over the generated terrain.
+        let mut cave = context.geos.assets.tiled("examples/terrain-2d/cave.tmx")?;
+        for warning in cave.warnings.iter() {
+            eprintln!("{}", warning);
+        }
+        cave.tilemap.group = context.geos.new_unit_square_array(
+            64,
+            config.format,
+            config.width,
+            config.height,
+            cave.sheets.clone(),
+            "examples/testing/shader_array.wgsl",
+            BlendMode::Premultiplied,
+        )?;
+        cave.tilemap.position = Vec2::new(32.0, 16.0);
+        if let Some(exit) = cave.objects_named("exit").next() {
+            println!("the cave's exit leads to {:?}", exit.properties["leads_to"]);
+        }
+        context.tilemaps.push(cave.tilemap);
 
-        // terrain_test(
-        //     context.queue.clone(),
-        //     &mut context.geos.instance_groups[0],
-        //     (width, height),
-        // );
+        // two rooms made in LDtk, beside the cave. their walls are an IntGrid whose
+        // auto-layer draws the stone.
+        let mut rooms = context.geos.assets.ldtk("examples/terrain-2d/rooms.ldtk")?;
+        for warning in rooms.warnings.iter() {
+            eprintln!("{}", warning);
+        }
+        let rooms_group = context.geos.new_unit_square_array(
+            64,
+            config.format,
+            config.width,
+            config.height,
+            rooms.sheets.clone(),
+            "examples/testing/shader_array.wgsl",
+            BlendMode::Premultiplied,
+        )?;
+        rooms.set_group(rooms_group);
+        let beside_cave = Vec2::new(224.0, 24.0);
+        for mut level in rooms.levels.drain(..) {
+            level.add_solid_layer("Walls", &[1])?;
+            level.tilemap.position += beside_cave;
+            for entity in level.entities.iter_mut() {
+                entity.position += beside_cave;
+                entity.spawn(
+                    &mut context.geos.instance_groups[rooms_group],
+                    context.queue.clone(),
+                );
+                if let Some(FieldValue::Int(health)) = entity.fields.get("health") {
+                    println!(
+                        "{} starts in {} with {} health",
+                        entity.identifier, level.identifier, health
+                    );
+                }
+            }
+            context.tilemaps.push(level.tilemap);
+        }
+        context.geos.camera.zoom = 4.0;
+        for warning in context.geos.assets.warnings.drain(..) {
+            eprintln!("{}", warning);
+        }
     }
+    context.watch_assets();
 
     // if event_loop's ControlFlow is not Poll, it's
     // necessary to request an initial frame on Wayland.
@@ -165,12 +247,26 @@ async fn run() -> Result<(), Box<dyn Error>> {
     window.request_redraw();
 
     event_loop.run(move |event, target| {
+        if let Event::AboutToWait = event {
+            // pan slowly across the map; only the chunks in view are drawn.
+            let terrain = &context.tilemaps[0];
+            let map_width = terrain.size.x as f32 * terrain.tile_size.x;
+            let view_width = context.logical_size().x as f32 / context.geos.camera.zoom;
+            let camera = &mut context.geos.camera;
+            camera.position.x = (camera.position.x + 8.0 * context.delta_time)
+                .min(map_width - view_width)
+                .max(0.0);
+            window.request_redraw();
+        }
         if let Event::WindowEvent {
             window_id: _,
             event,
         } = event
         {
             match event {
+                WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                    context.set_scale_factor(scale_factor);
+                }
                 WindowEvent::Resized(size) => {
                     context.resize(size);
                     window.request_redraw();
@@ -0,0 +1,17 @@
+<?xml version="1.0" encoding="UTF-8"?>
+<tileset version="1.10" tiledversion="1.10.2" name="terrain" tilewidth="8" tileheight="8" tilecount="1024" columns="32">
+ <image source="terrain-2d.png" width="256" height="256"/>
+ <tile id="130">
+  <properties>
+   <property name="solid" type="bool" value="true"/>
+  </properties>
+ </tile>
+ <tile id="132">
+  <animation>
+   <frame tileid="132" duration="250"/>
+   <frame tileid="133" duration="250"/>
+   <frame tileid="164" duration="250"/>
+   <frame tileid="165" duration="250"/>
+  </animation>
+ </tile>
+</tileset>
@@ -1,11 +1,18 @@
 use std::error::Error;
 
 use glam::{IVec2, UVec2};
+use virae::hierarchy::{LocalTransform, NodeRef};
+use virae::mesh;
+use virae::shapes::ShapeStyle;
+use virae::text::{TextEffect, TextReveal};
+use virae::tween::{Animation, Easing, Tween};
 use virae::types::{
-    ComponentTransform, PixelRect, TextureSheetClusterDefinition, TextureSheetDefinition,
+    BlendMode, ComponentTransform, MeshLayout, PixelRect, SamplerOptions, SpriteRef,
+    TextureSheetClusterDefinition, TextureSheetDefinition,
 };
 use virae::window::Context;
-use virae::{Event, HalRect, Vec4, WindowEvent};
+use virae::{Event, HalRect, Vec2, Vec4, WindowEvent};
+use winit::event::{ElementState, MouseButton};
 use winit::event_loop::ControlFlow;
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -17,10 +24,12 @@ async fn run() -> Result<(), Box<dyn Error>> {
     let (width, height) = (800, 600);
     let (event_loop, window, mut context) =
         Context::new("testing", width, height, ControlFlow::Wait).await?;
+    // resolve asset paths from the crate root, wherever the example is run from.
+    context.geos.assets.mount_dir(env!("CARGO_MANIFEST_DIR"));
 
-    {
+    let (notes_input, array_group) = {
         let shader_path = "examples/testing/shader.wgsl";
-        context.file_watcher.add_path(shader_path);
+        let extent = context.logical_size();
         let config = context.config.lock().unwrap();
         context.geos.new_unit_square(
             64,
@@ -38,8 +47,10 @@ async fn run() -> Result<(), Box<dyn Error>> {
                     sub_size: UVec2::new(32, 32),
                     spacing: UVec2::new(1, 1),
                 }],
+                sampler: SamplerOptions::default(),
             },
             shader_path,
+            BlendMode::Premultiplied,
         )?;
 
         // test labels
@@ -60,7 +71,7 @@ async fn run() -> Result<(), Box<dyn Error>> {
                 ComponentTransform::unit_square_transform_from_pixel_rect(PixelRect {
                     xy: IVec2::new(x as i32, y as i32),
                     wh: UVec2::new(w, h),
-                    extent: UVec2::new(config.width, config.height),
+                    extent,
                 }),
                 0,
                 i as usize,
@@ -74,11 +85,253 @@ async fn run() -> Result<(), Box<dyn Error>> {
                     h: h as f64,
                 },
                 format!("tx{}", i).as_str(),
-                context.scale_factor,
                 1.0,
             );
         }
-    }
+
+        // one group drawing from two sheets through a texture array.
+        let array_group = context.geos.new_unit_square_array(
+            16,
+            config.format,
+            config.width,
+            config.height,
+            vec![
+                TextureSheetDefinition {
+                    path: "examples/shmuppin/shmuppin.png".to_string(),
+                    clusters: vec![TextureSheetClusterDefinition {
+                        label: "tinyshmup".to_string(),
+                        offset: UVec2::new(0, 0),
+                        cluster_size: UVec2::new(128, 48),
+                        sub_size: UVec2::new(16, 16),
+                        spacing: UVec2::new(0, 0),
+                    }],
+                    sampler: SamplerOptions::default(),
+                },
+                TextureSheetDefinition {
+                    path: "examples/terrain-2d/terrain-2d.png".to_string(),
+                    clusters: vec![TextureSheetClusterDefinition {
+                        label: "dirt-exterior".to_string(),
+                        offset: UVec2::new(0, 0),
+                        cluster_size: UVec2::new(32, 32),
+                        sub_size: UVec2::new(8, 8),
+                        spacing: UVec2::new(0, 0),
+                    }],
+                    sampler: SamplerOptions::default(),
+                },
+            ],
+            "examples/testing/shader_array.wgsl",
+            BlendMode::Premultiplied,
+        )?;
+        for i in 0..8 {
+            context.geos.instance_groups[array_group].add_new_from_layer(
+                context.queue.clone(),
+                ComponentTransform::unit_square_transform_from_pixel_rect(PixelRect {
+                    xy: IVec2::new(450 + 40 * i, 240),
+                    wh: UVec2::new(32, 32),
+                    extent,
+                }),
+                (i % 2) as usize,
+                0,
+                i as usize,
+                Vec4::new(1.0, 1.0, 1.0, 1.0),
+            );
+        }
+        // turn one sprite about its center and mirror another.
+        let sprites = &mut context.geos.instance_groups[array_group]
+            .instance_buffer_manager
+            .data;
+        sprites[0].set_pivot(Vec2::splat(0.5));
+        sprites[0].set_rotation(45.0);
+        sprites[2].set_flip(true, false);
+
+        // untextured circles, instanced from a custom mesh.
+        let (vertices, indices) = mesh::circle(32);
+        let circle_group = context.geos.new_mesh_group(
+            8,
+            config.format,
+            config.width,
+            config.height,
+            &vertices,
+            indices,
+            MeshLayout::default(),
+            TextureSheetDefinition::none(),
+            shader_path,
+            BlendMode::Premultiplied,
+        )?;
+        for i in 0..4 {
+            context.geos.instance_groups[circle_group].add_new_with_tex(
+                context.queue.clone(),
+                ComponentTransform::unit_square_transform_from_pixel_rect(PixelRect {
+                    xy: IVec2::new(450 + 56 * i, 380),
+                    wh: UVec2::new(48, 48),
+                    extent,
+                }),
+                ComponentTransform::default(),
+                Vec4::new(0.25 * (i + 1) as f32, 0.5, 1.0 - 0.2 * i as f32, 1.0),
+            );
+        }
+
+        // dialogue label, typed out with a little wave.
+        let dialogue = context.texts.new_text(
+            HalRect {
+                x: 450.0,
+                y: 16.0,
+                w: 320.0,
+                h: 64.0,
+            },
+            "hello! this text is typed out, one letter at a time.",
+            1.0,
+        );
+        let label = &mut context.texts.texts[dialogue];
+        label.reveal = Some(TextReveal::new(20.0, 0.25));
+        label.effects.push(TextEffect::Wave {
+            amplitude: 2.0,
+            frequency: 0.6,
+            speed: 6.0,
+        });
+
+        // a multi-line input field; click it to type.
+        let notes_input = context.texts.new_input(
+            HalRect {
+                x: 450.0,
+                y: 96.0,
+                w: 320.0,
+                h: 120.0,
+            },
+            1.0,
+            true,
+        );
+        (notes_input, array_group)
+    };
+    // the turned sprite carries a smaller one and a label along with it.
+    let turned = NodeRef::Instance {
+        group: array_group,
+        index: 0,
+    };
+    context.attach_at(
+        NodeRef::Instance {
+            group: array_group,
+            index: 1,
+        },
+        turned,
+        LocalTransform {
+            offset: Vec2::new(24.0, -8.0),
+            scale: Vec2::splat(0.5),
+            ..Default::default()
+        },
+    )?;
+    let turned_label = context.texts.new_text(
+        HalRect {
+            x: 0.0,
+            y: 0.0,
+            w: 64.0,
+            h: 24.0,
+        },
+        "turned",
+        1.0,
+    );
+    context.attach_at(
+        NodeRef::Text(turned_label),
+        turned,
+        LocalTransform {
+            offset: Vec2::new(-16.0, 20.0),
+            ..Default::default()
+        },
+    )?;
+    // drop a sprite in with a bounce, then keep the label pulsing.
+    let dropped = NodeRef::Instance {
+        group: array_group,
+        index: 7,
+    };
+    context.tweens.play_then(
+        Animation::sequence(vec![
+            Animation::delay(0.5),
+            Tween::position(dropped, Vec2::new(730.0, 240.0), 1.0)
+                .from(Vec4::new(730.0, 160.0, 0.0, 0.0))
+                .ease(Easing::BounceOut)
+                .into(),
+        ]),
+        Box::new(move |context| {
+            let pulse = Tween::color(
+                NodeRef::Text(turned_label),
+                Vec4::new(1.0, 0.8, 0.2, 1.0),
+                0.6,
+            )
+            .ease(Easing::QuadInOut);
+            context
+                .tweens
+                .play(Animation::from(pulse).yoyo().repeat_forever());
+            Ok(())
+        }),
+    );
+
+    // decode a sheet in the background, showing progress until it's ready to use.
+    let loading_label = context.texts.new_text(
+        HalRect {
+            x: 450.0,
+            y: 290.0,
+            w: 320.0,
+            h: 32.0,
+        },
+        "loading...",
+        1.0,
+    );
+    let terrain = context.geos.assets.texture_async(TextureSheetDefinition {
+        path: "examples/terrain-2d/terrain-2d.png".to_string(),
+        clusters: vec![TextureSheetClusterDefinition {
+            label: "dirt-exterior".to_string(),
+            offset: UVec2::new(0, 0),
+            cluster_size: UVec2::new(32, 32),
+            sub_size: UVec2::new(8, 8),
+            spacing: UVec2::new(0, 0),
+        }],
+        sampler: SamplerOptions::default(),
+    });
+    context.loading_hook = Some(Box::new(move |context, progress| {
+        if !progress.is_done() {
+            let text = format!("loading {}/{}", progress.loaded, progress.total);
+            context.texts.set_text(loading_label, &text);
+            return Ok(());
+        }
+        context.texts.set_text(loading_label, "");
+        let sheet = match context.geos.assets.take_loaded_texture(terrain) {
+            Some(sheet) => sheet,
+            None => return Ok(()),
+        };
+        // start the next loading screen's progress from zero.
+        context.geos.assets.clear_loads();
+        let shader = context.geos.assets.shader("examples/testing/shader.wgsl")?;
+        let extent = context.logical_size();
+        let config = context.config.lock().unwrap();
+        let group = context.geos.new_unit_square_with(
+            16,
+            config.format,
+            config.width,
+            config.height,
+            sheet,
+            shader,
+            BlendMode::Premultiplied,
+        )?;
+        for i in 0..16 {
+            context.geos.instance_groups[group].add_new(
+                context.queue.clone(),
+                ComponentTransform::unit_square_transform_from_pixel_rect(PixelRect {
+                    xy: IVec2::new(450 + 16 * (i % 4), 290 + 16 * (i / 4)),
+                    wh: UVec2::new(16, 16),
+                    extent,
+                }),
+                0,
+                i as usize,
+                Vec4::new(1.0, 1.0, 1.0, 1.0),
+            );
+        }
+        Ok(())
+    }));
+
+    // hot reload the shaders and sheets loaded above.
+    context.watch_assets();
+    window.set_ime_allowed(true);
+    let mut cursor_position = (0.0, 0.0);
 
     // if event_loop's ControlFlow is not Poll, it's
     // necessary to request an initial frame on Wayland.
@@ -91,7 +344,24 @@ async fn run() -> Result<(), Box<dyn Error>> {
             event,
         } = event
         {
+            context.texts.input_event(notes_input, &event);
             match event {
+                WindowEvent::CursorMoved { position, .. } => {
+                    let position = context.to_logical(position);
+                    cursor_position = (position.x as f64, position.y as f64);
+                }
+                WindowEvent::MouseInput {
+                    state: ElementState::Pressed,
+                    button: MouseButton::Left,
+                    ..
+                } => {
+                    context
+                        .texts
+                        .click_input(cursor_position.0, cursor_position.1);
+                }
+                WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                    context.set_scale_factor(scale_factor);
+                }
                 WindowEvent::Resized(size) => {
                     context.resize(size);
                     window.request_redraw();
@@ -99,8 +369,51 @@ async fn run() -> Result<(), Box<dyn Error>> {
                 WindowEvent::RedrawRequested => {
                     // it is unfortunate the errors from these functions
                     // don't ?-bubble out of this closure. todo, find a way...
+                    // spin the parent sprite; its children follow in update.
+                    let delta_time = context.delta_time;
+                    context.geos.instance_groups[array_group]
+                        .instance_buffer_manager
+                        .data[0]
+                        .rotate(90.0 * delta_time);
                     context.update().expect("event loop context update error");
+                    // debug outlines, redrawn every frame.
+                    let green = Vec4::new(0.2, 1.0, 0.4, 1.0);
+                    context
+                        .draw()
+                        .rect(
+                            Vec2::new(450.0, 96.0),
+                            Vec2::new(320.0, 120.0),
+                            ShapeStyle::Stroke(1.0, green),
+                        )
+                        .circle(
+                            Vec2::new(cursor_position.0 as f32, cursor_position.1 as f32),
+                            6.0,
+                            ShapeStyle::Stroke(1.5, green),
+                        );
+                    // a sprite following the cursor, queued for this frame only.
+                    let cursor_world = context.screen_to_world(Vec2::new(
+                        cursor_position.0 as f32,
+                        cursor_position.1 as f32,
+                    ));
+                    context.sprites(0).draw(
+                        SpriteRef::new(0, 3),
+                        cursor_world + Vec2::splat(8.0),
+                        Vec2::new(24.0, 24.0),
+                        Vec4::new(1.0, 1.0, 1.0, 0.8),
+                    );
+                    // its outline, in world pixels so it stays on the sprite as the camera moves.
+                    context.draw_world().rect(
+                        cursor_world + Vec2::splat(8.0),
+                        Vec2::new(24.0, 24.0),
+                        ShapeStyle::Stroke(1.0, green),
+                    );
+                    let mut shape_errors = context.draw().take_errors();
+                    shape_errors.extend(context.draw_world().take_errors());
+                    for error in shape_errors {
+                        eprintln!("a shape wasn't drawn: {}", error);
+                    }
                     context.render().expect("event loop context render error");
+                    window.request_redraw();
                 }
                 WindowEvent::CloseRequested => {
                     target.exit();
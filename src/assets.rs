@@ -0,0 +1,964 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    error::Error,
+    ops::Deref,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use glam::UVec2;
+use image::RgbaImage;
+use wgpu::{
+    Device, Extent3d, FilterMode, Queue, ShaderModule, ShaderModuleDescriptor, ShaderSource,
+    Texture, TextureDescriptor, TextureView, TextureViewDimension,
+};
+
+use crate::atlas::PackedAtlas;
+use crate::font::BitmapFont;
+use crate::ldtk::LdtkProject;
+use crate::tiled::TiledMap;
+use crate::types::{SheetTexture, TextureSheet, TextureSheetDefinition};
+use crate::vfs::Vfs;
+
+static NEXT_HANDLE_ID: AtomicUsize = AtomicUsize::new(0);
+
+// a shared reference to a loaded asset. clones are cheap, and an asset stays loaded while
+// any handle to it exists outside of the cache. a reloaded asset keeps its id, and handles
+// to any of its versions keep it loaded.
+pub struct Handle<T> {
+    pub id: usize,
+    asset: Arc<T>,
+    // shared by every version of the asset, so the cache can count all its handles.
+    id_ref: Arc<()>,
+}
+
+impl<T> Handle<T> {
+    fn new(asset: T) -> Self {
+        Self {
+            id: NEXT_HANDLE_ID.fetch_add(1, Ordering::Relaxed),
+            asset: Arc::new(asset),
+            id_ref: Arc::new(()),
+        }
+    }
+
+    // true if both handles point at the same version of the same asset.
+    pub fn same(&self, other: &Handle<T>) -> bool {
+        Arc::ptr_eq(&self.asset, &other.asset)
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id,
+            asset: self.asset.clone(),
+            id_ref: self.id_ref.clone(),
+        }
+    }
+}
+
+impl<T> Deref for Handle<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.asset
+    }
+}
+
+pub struct Shader {
+    pub path: String,
+    pub module: ShaderModule,
+}
+
+// loaded assets of one type, keyed by path.
+pub struct AssetCache<T> {
+    entries: HashMap<String, Handle<T>>,
+}
+
+impl<T> AssetCache<T> {
+    fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, path: &str) -> Option<Handle<T>> {
+        self.entries.get(path).cloned()
+    }
+
+    fn insert(&mut self, path: &str, asset: T) -> Handle<T> {
+        let handle = Handle::new(asset);
+        self.entries.insert(path.to_string(), handle.clone());
+        handle
+    }
+
+    // swap in a new version of the asset at `path`, keeping its id.
+    fn replace(&mut self, path: &str, asset: T) {
+        if let Some(handle) = self.entries.get_mut(path) {
+            handle.asset = Arc::new(asset);
+        }
+    }
+
+    // the cached version of `handle`, if it has been reloaded since the handle was made.
+    pub fn newer(&self, handle: &Handle<T>) -> Option<Handle<T>> {
+        self.entries
+            .values()
+            .find(|h| h.id == handle.id && !h.same(handle))
+            .cloned()
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &String> {
+        self.entries.keys()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // drop every asset only the cache still refers to, in any version. returns how many were
+    // dropped.
+    fn unload_unused(&mut self) -> usize {
+        let before = self.entries.len();
+        self.entries
+            .retain(|_, handle| Arc::strong_count(&handle.id_ref) > 1);
+        before - self.entries.len()
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum LoadStatus {
+    Pending,
+    Loaded,
+    Failed(String),
+}
+
+// one background texture load, as started by Assets::texture_async. it holds its sheet
+// only until take_loaded_texture hands it over or clear_loads drops it, so finished loads
+// don't keep their textures from being unloaded.
+pub struct AssetLoad {
+    pub key: String,
+    pub status: LoadStatus,
+    pub handle: Option<Handle<TextureSheet>>,
+    sheet_infos: Vec<TextureSheetDefinition>,
+    array: bool,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct LoadProgress {
+    pub loaded: usize,
+    pub failed: usize,
+    pub total: usize,
+}
+
+impl LoadProgress {
+    pub fn is_done(&self) -> bool {
+        self.loaded + self.failed == self.total
+    }
+
+    pub fn fraction(&self) -> f32 {
+        if self.total == 0 {
+            1.0
+        } else {
+            (self.loaded + self.failed) as f32 / self.total as f32
+        }
+    }
+}
+
+struct DecodeJob {
+    ticket: usize,
+    sheet_infos: Vec<TextureSheetDefinition>,
+    strict: bool,
+    vfs: Arc<Mutex<Vfs>>,
+}
+
+// the job's ticket, its images and any warnings from decoding them.
+type DecodeResult = (usize, Result<Vec<RgbaImage>, String>, Vec<String>);
+
+// decode threads. images come back over a channel and are uploaded on the main thread.
+struct DecodePool {
+    jobs: Sender<DecodeJob>,
+    results: Receiver<DecodeResult>,
+}
+
+impl DecodePool {
+    fn new() -> Self {
+        let (jobs, job_receiver) = channel::<DecodeJob>();
+        let (result_sender, results) = channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let threads = thread::available_parallelism().map_or(2, |n| n.get().clamp(1, 8));
+        for i in 0..threads {
+            let job_receiver = job_receiver.clone();
+            let result_sender: Sender<DecodeResult> = result_sender.clone();
+            thread::Builder::new()
+                .name(format!("virae decode {}", i))
+                .spawn(move || loop {
+                    // the pool shuts down once Assets, and with it the job sender, is dropped.
+                    let job = match job_receiver.lock().unwrap().recv() {
+                        Ok(job) => job,
+                        Err(_) => break,
+                    };
+                    let mut warnings = vec![];
+                    let images =
+                        decode_sheets(&job.sheet_infos, job.strict, &job.vfs, &mut warnings)
+                            .map_err(|e| e.to_string());
+                    if result_sender.send((job.ticket, images, warnings)).is_err() {
+                        break;
+                    }
+                })
+                .expect("spawn decode thread");
+        }
+        Self { jobs, results }
+    }
+}
+
+// loads textures, shaders and fonts once per path and hands out shared handles.
+pub struct Assets {
+    pub device: Arc<Mutex<Device>>,
+    pub queue: Arc<Mutex<Queue>>,
+    pub textures: AssetCache<SheetTexture>,
+    pub shaders: AssetCache<Shader>,
+    pub fonts: AssetCache<BitmapFont>,
+    // fail texture loads on missing files instead of substituting a checkerboard.
+    pub strict_textures: bool,
+    // where texture, shader and font paths are read from.
+    pub vfs: Arc<Mutex<Vfs>>,
+    // background loads, by the ticket texture_async returned, until clear_loads.
+    pub loads: HashMap<usize, AssetLoad>,
+    next_ticket: usize,
+    // what was substituted while loading, e.g. missing textures. drain it to report them.
+    pub warnings: Vec<String>,
+    // each cached font's page sheet, so a cache hit needn't re-read the descriptor.
+    font_pages: HashMap<String, TextureSheetDefinition>,
+    decode_pool: Option<DecodePool>,
+}
+
+impl Assets {
+    pub fn new(device: Arc<Mutex<Device>>, queue: Arc<Mutex<Queue>>) -> Self {
+        Self {
+            device,
+            queue,
+            textures: AssetCache::new(),
+            shaders: AssetCache::new(),
+            fonts: AssetCache::new(),
+            strict_textures: false,
+            vfs: Arc::new(Mutex::new(Vfs::new())),
+            loads: HashMap::new(),
+            next_ticket: 0,
+            warnings: vec![],
+            font_pages: HashMap::new(),
+            decode_pool: None,
+        }
+    }
+
+    // the texture is shared with sheets loaded from the same path, as long as they agree on
+    // mipmaps; the clusters and sampler are this sheet's own.
+    pub fn texture(
+        &mut self,
+        sheet_info: TextureSheetDefinition,
+    ) -> Result<Handle<TextureSheet>, Box<dyn Error>> {
+        let key = texture_key(std::slice::from_ref(&sheet_info), false);
+        let texture = match self.textures.get(&key) {
+            Some(texture) => texture,
+            None => {
+                let texture = load_texture(
+                    self.device.clone(),
+                    self.queue.clone(),
+                    sheet_info.clone(),
+                    self.strict_textures,
+                    &self.vfs,
+                    &mut self.warnings,
+                )?;
+                self.textures.insert(&key, texture)
+            }
+        };
+        new_sheet(&self.device, vec![sheet_info], texture)
+    }
+
+    // shared like texture, by the list of layer paths.
+    pub fn texture_array(
+        &mut self,
+        sheet_infos: Vec<TextureSheetDefinition>,
+    ) -> Result<Handle<TextureSheet>, Box<dyn Error>> {
+        let key = texture_key(&sheet_infos, true);
+        let texture = match self.textures.get(&key) {
+            Some(texture) => texture,
+            None => {
+                let texture = load_texture_array(
+                    self.device.clone(),
+                    self.queue.clone(),
+                    sheet_infos.clone(),
+                    self.strict_textures,
+                    &self.vfs,
+                    &mut self.warnings,
+                )?;
+                self.textures.insert(&key, texture)
+            }
+        };
+        new_sheet(&self.device, sheet_infos, texture)
+    }
+
+    // packed atlases have no single source file, so they aren't cached or reloaded.
+    pub fn atlas(&mut self, atlas: PackedAtlas) -> Result<Handle<TextureSheet>, Box<dyn Error>> {
+        let sheet_info = atlas.sheet_info.clone();
+        let texture = load_texture_from_atlas(self.device.clone(), self.queue.clone(), atlas)?;
+        new_sheet(&self.device, vec![sheet_info], Handle::new(texture))
+    }
+
+    // the same sheet over the cached version of its texture, if that has been reloaded.
+    pub fn retextured(&self, sheet: &TextureSheet) -> Option<Handle<TextureSheet>> {
+        let texture = self.textures.newer(&sheet.texture)?;
+        new_sheet(&self.device, sheet.layers.clone(), texture).ok()
+    }
+
+    pub fn shader(&mut self, path: &str) -> Result<Handle<Shader>, Box<dyn Error>> {
+        if let Some(handle) = self.shaders.get(path) {
+            return Ok(handle);
+        }
+        let shader = load_shader(self.device.clone(), path, &self.vfs)?;
+        Ok(self.shaders.insert(path, shader))
+    }
+
+    // a bmfont descriptor and its page texture.
+    pub fn font(
+        &mut self,
+        path: &str,
+    ) -> Result<(Handle<BitmapFont>, Handle<TextureSheet>), Box<dyn Error>> {
+        let cached = self.fonts.get(path).zip(self.font_pages.get(path).cloned());
+        let (font, sheet_info) = match cached {
+            Some(cached) => cached,
+            None => {
+                let (font, sheet_info) = BitmapFont::from_fnt(path, &self.vfs.lock().unwrap())?;
+                self.font_pages.insert(path.to_string(), sheet_info.clone());
+                (self.fonts.insert(path, font), sheet_info)
+            }
+        };
+        Ok((font, self.texture(sheet_info)?))
+    }
+
+    // a Tiled map. it isn't cached: each call gives a fresh tilemap to edit.
+    pub fn tiled(&self, path: &str) -> Result<TiledMap, Box<dyn Error>> {
+        TiledMap::load(path, &self.vfs.lock().unwrap())
+    }
+
+    // an LDtk project, also fresh on each call.
+    pub fn ldtk(&self, path: &str) -> Result<LdtkProject, Box<dyn Error>> {
+        LdtkProject::load(path, &self.vfs.lock().unwrap())
+    }
+
+    // packs, directories and embedded files mounted later take priority over earlier ones.
+    pub fn mount_pack(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
+        self.vfs.lock().unwrap().mount_pack(path)
+    }
+
+    pub fn mount_dir(&mut self, dir: &str) {
+        self.vfs.lock().unwrap().mount_dir(dir);
+    }
+
+    pub fn mount_embedded(&mut self, path: &str, bytes: &'static [u8]) {
+        self.vfs.lock().unwrap().mount_embedded(path, bytes);
+    }
+
+    // start decoding a sheet in the background. returns a ticket for load_status and
+    // take_loaded_texture; the texture is uploaded by a later poll_loads.
+    pub fn texture_async(&mut self, sheet_info: TextureSheetDefinition) -> usize {
+        self.start_load(vec![sheet_info], false)
+    }
+
+    pub fn texture_array_async(&mut self, sheet_infos: Vec<TextureSheetDefinition>) -> usize {
+        self.start_load(sheet_infos, true)
+    }
+
+    fn start_load(&mut self, sheet_infos: Vec<TextureSheetDefinition>, array: bool) -> usize {
+        let key = texture_key(&sheet_infos, array);
+        let ticket = self.next_ticket;
+        self.next_ticket += 1;
+        let cached = self
+            .textures
+            .get(&key)
+            .map(|texture| new_sheet(&self.device, sheet_infos.clone(), texture));
+        // a texture already being decoded is shared like a cached one, once it's done.
+        let decoding = self
+            .loads
+            .values()
+            .any(|l| l.key == key && l.status == LoadStatus::Pending);
+        let status = match &cached {
+            Some(Ok(_)) => LoadStatus::Loaded,
+            Some(Err(e)) => LoadStatus::Failed(e.to_string()),
+            None if sheet_infos.is_empty() => {
+                LoadStatus::Failed("texture array needs at least one sheet".to_string())
+            }
+            None if decoding => LoadStatus::Pending,
+            None => {
+                let pool = self.decode_pool.get_or_insert_with(DecodePool::new);
+                pool.jobs
+                    .send(DecodeJob {
+                        ticket,
+                        sheet_infos: sheet_infos.clone(),
+                        strict: self.strict_textures,
+                        vfs: self.vfs.clone(),
+                    })
+                    .expect("decode threads stopped");
+                LoadStatus::Pending
+            }
+        };
+        self.loads.insert(
+            ticket,
+            AssetLoad {
+                key,
+                status,
+                handle: cached.and_then(Result::ok),
+                sheet_infos,
+                array,
+            },
+        );
+        ticket
+    }
+
+    // upload whatever the decode threads have finished. call once a frame on the main thread;
+    // Context::update does. returns how many loads completed.
+    pub fn poll_loads(&mut self) -> usize {
+        let pool = match &self.decode_pool {
+            Some(pool) => pool,
+            None => return 0,
+        };
+        let finished: Vec<DecodeResult> = pool.results.try_iter().collect();
+        let count = finished.len();
+        for (ticket, images, warnings) in finished {
+            self.warnings.extend(warnings);
+            let Some(load) = self.loads.get(&ticket) else {
+                continue;
+            };
+            let key = load.key.clone();
+            // the same texture may have been loaded synchronously meanwhile; its handle is
+            // shared so both count towards one entry.
+            let texture = match self.textures.get(&key) {
+                Some(texture) => Ok(texture),
+                None => images.and_then(|images| {
+                    texture_from_images(
+                        self.device.clone(),
+                        self.queue.clone(),
+                        images,
+                        load.sheet_infos.clone(),
+                        load.array,
+                    )
+                    .map_err(|e| e.to_string())
+                    .map(|texture| self.textures.insert(&key, texture))
+                }),
+            };
+            // every load waiting on this texture gets its own sheet over it.
+            for load in self
+                .loads
+                .values_mut()
+                .filter(|l| l.key == key && l.status == LoadStatus::Pending)
+            {
+                let sheet = texture.clone().and_then(|texture| {
+                    new_sheet(&self.device, load.sheet_infos.clone(), texture)
+                        .map_err(|e| e.to_string())
+                });
+                match sheet {
+                    Ok(sheet) => {
+                        load.handle = Some(sheet);
+                        load.status = LoadStatus::Loaded;
+                    }
+                    Err(message) => load.status = LoadStatus::Failed(message),
+                }
+            }
+        }
+        count
+    }
+
+    // None once the load has been cleared.
+    pub fn load_status(&self, ticket: usize) -> Option<&LoadStatus> {
+        self.loads.get(&ticket).map(|load| &load.status)
+    }
+
+    // the loaded sheet, handed over once: later calls give None. the load still counts
+    // towards progress until clear_loads.
+    pub fn take_loaded_texture(&mut self, ticket: usize) -> Option<Handle<TextureSheet>> {
+        self.loads.get_mut(&ticket)?.handle.take()
+    }
+
+    // forget every finished load, releasing sheets that weren't taken, so progress starts
+    // over for the next loading screen. pending loads are kept.
+    pub fn clear_loads(&mut self) {
+        self.loads
+            .retain(|_, load| load.status == LoadStatus::Pending);
+    }
+
+    pub fn progress(&self) -> LoadProgress {
+        let mut progress = LoadProgress {
+            loaded: 0,
+            failed: 0,
+            total: self.loads.len(),
+        };
+        for load in self.loads.values() {
+            match load.status {
+                LoadStatus::Loaded => progress.loaded += 1,
+                LoadStatus::Failed(_) => progress.failed += 1,
+                LoadStatus::Pending => (),
+            }
+        }
+        progress
+    }
+
+    // reload every asset loaded from `path`. returns whether any were.
+    // reload every asset loaded from `path`. returns whether any were. an asset that fails to
+    // load, e.g. a file caught halfway through being saved, keeps its old version and the
+    // error goes to `warnings`.
+    //
+    // groups pick up new textures and shaders through GeoManager::refresh_assets. fonts aren't
+    // held by groups, so holders of a font handle swap it with `fonts.newer`.
+    pub fn reload(&mut self, path: &str) -> bool {
+        let mut reloaded = false;
+
+        let texture_keys: Vec<String> = self
+            .textures
+            .entries
+            .iter()
+            .filter(|(_, texture)| texture.sources.iter().any(|s| s.path == path))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in texture_keys {
+            let texture = &self.textures.entries[&key];
+            let sources = texture.sources.clone();
+            let texture = if texture.array {
+                load_texture_array(
+                    self.device.clone(),
+                    self.queue.clone(),
+                    sources,
+                    self.strict_textures,
+                    &self.vfs,
+                    &mut self.warnings,
+                )
+            } else {
+                let sheet_info = sources.into_iter().next().unwrap();
+                load_texture(
+                    self.device.clone(),
+                    self.queue.clone(),
+                    sheet_info,
+                    self.strict_textures,
+                    &self.vfs,
+                    &mut self.warnings,
+                )
+            };
+            match texture {
+                Ok(texture) => {
+                    self.textures.replace(&key, texture);
+                    reloaded = true;
+                }
+                Err(e) => self.reload_failed(path, e),
+            }
+        }
+
+        if self.shaders.entries.contains_key(path) {
+            match load_shader(self.device.clone(), path, &self.vfs) {
+                Ok(shader) => {
+                    self.shaders.replace(path, shader);
+                    reloaded = true;
+                }
+                Err(e) => self.reload_failed(path, e),
+            }
+        }
+
+        if self.fonts.entries.contains_key(path) {
+            let font = self
+                .vfs
+                .lock()
+                .unwrap()
+                .read_to_string(path)
+                .and_then(|source| BitmapFont::from_fnt_source(path, &source));
+            match font {
+                Ok((font, sheet_info)) => {
+                    self.fonts.replace(path, font);
+                    self.font_pages.insert(path.to_string(), sheet_info);
+                    reloaded = true;
+                }
+                Err(e) => self.reload_failed(path, e),
+            }
+        }
+
+        reloaded
+    }
+
+    fn reload_failed(&mut self, path: &str, error: Box<dyn Error>) {
+        self.warnings.push(format!(
+            "{}: couldn't reload, keeping the old version: {}",
+            path, error
+        ));
+    }
+
+    // every path an asset was loaded from, for registering with the FileWatcher.
+    pub fn source_paths(&self) -> Vec<String> {
+        let mut paths: Vec<String> = self
+            .textures
+            .entries
+            .values()
+            .flat_map(|texture| texture.sources.iter().map(|s| s.path.clone()))
+            .chain(self.shaders.paths().cloned())
+            .chain(self.fonts.paths().cloned())
+            .filter(|path| !path.is_empty())
+            .collect();
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+
+    // drop every asset no handle outside the cache refers to. returns how many were dropped.
+    pub fn unload_unused(&mut self) -> usize {
+        let unloaded = self.textures.unload_unused()
+            + self.shaders.unload_unused()
+            + self.fonts.unload_unused();
+        let fonts = &self.fonts;
+        self.font_pages
+            .retain(|path, _| fonts.entries.contains_key(path));
+        unloaded
+    }
+}
+
+// textures are shared by their paths and whether they have mip levels; the rest of the
+// sampler options are per sheet. array keys are marked so a one layer array isn't taken for
+// a plain sheet.
+fn texture_key(sheet_infos: &[TextureSheetDefinition], array: bool) -> String {
+    let paths = sheet_infos
+        .iter()
+        .map(|s| s.path.as_str())
+        .collect::<Vec<_>>()
+        .join("|");
+    let mipmaps = sheet_infos.first().is_some_and(|s| s.sampler.mipmaps);
+    match array {
+        true => format!("array:{} mipmaps:{}", paths, mipmaps),
+        false => format!("{} mipmaps:{}", paths, mipmaps),
+    }
+}
+
+// a sheet drawing `layers` from a loaded texture, sampled by the first layer's options.
+fn new_sheet(
+    device: &Mutex<Device>,
+    layers: Vec<TextureSheetDefinition>,
+    texture: Handle<SheetTexture>,
+) -> Result<Handle<TextureSheet>, Box<dyn Error>> {
+    let options = layers.first().map(|l| l.sampler).unwrap_or_default();
+    if options.anisotropy > 1 && options.filter != FilterMode::Linear {
+        let path = layers.first().map_or("", |l| l.path.as_str());
+        return Err(format!("{}: anisotropic filtering requires linear filtering", path).into());
+    }
+    let sampler = device
+        .lock()
+        .unwrap()
+        .create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: options.address_mode,
+            address_mode_v: options.address_mode,
+            address_mode_w: options.address_mode,
+            mag_filter: options.filter,
+            min_filter: options.filter,
+            mipmap_filter: options.filter,
+            anisotropy_clamp: options.anisotropy.clamp(1, 16),
+            ..Default::default()
+        });
+    Ok(Handle::new(TextureSheet {
+        layers,
+        texture,
+        sampler,
+    }))
+}
+
+fn load_shader(
+    device: Arc<Mutex<Device>>,
+    path: &str,
+    vfs: &Mutex<Vfs>,
+) -> Result<Shader, Box<dyn Error>> {
+    let source = vfs.lock().unwrap().read_to_string(path)?;
+    let device = device.lock().unwrap();
+    let module = device.create_shader_module(ShaderModuleDescriptor {
+        label: Some(path),
+        source: ShaderSource::Wgsl(Cow::Borrowed(&source)),
+    });
+    Ok(Shader {
+        path: path.to_string(),
+        module,
+    })
+}
+
+// an empty path means untextured, and loads a white pixel. a path that doesn't exist is an
+// error in strict mode, otherwise it loads a checkerboard covering the sheet's clusters.
+fn load_image(
+    sheet_info: &TextureSheetDefinition,
+    strict: bool,
+    vfs: &Mutex<Vfs>,
+    warnings: &mut Vec<String>,
+) -> Result<RgbaImage, Box<dyn Error>> {
+    if sheet_info.path.is_empty() {
+        return Ok(image::load_from_memory(include_bytes!("../images/1x1white.png"))?.to_rgba8());
+    }
+    // only the read holds the vfs lock; decoding doesn't.
+    let bytes = {
+        let vfs = vfs.lock().unwrap();
+        match vfs.exists(&sheet_info.path) {
+            true => Some(vfs.read(&sheet_info.path)?),
+            false => None,
+        }
+    };
+    match bytes {
+        Some(bytes) => Ok(image::load_from_memory(&bytes)?.to_rgba8()),
+        None if strict => Err(format!("{}: texture not found", sheet_info.path).into()),
+        None => {
+            warnings.push(format!(
+                "{}: texture not found, using a missing texture",
+                sheet_info.path
+            ));
+            Ok(missing_image(sheet_info))
+        }
+    }
+}
+
+// magenta and black checkers, sized to cover every cluster so sub-images still line up.
+fn missing_image(sheet_info: &TextureSheetDefinition) -> RgbaImage {
+    let size = sheet_info
+        .clusters
+        .iter()
+        .fold(UVec2::splat(16), |size, c| {
+            size.max(c.offset + c.cluster_size)
+        });
+    RgbaImage::from_fn(size.x, size.y, |x, y| {
+        if (x / 8 + y / 8) % 2 == 0 {
+            image::Rgba([255, 0, 255, 255])
+        } else {
+            image::Rgba([0, 0, 0, 255])
+        }
+    })
+}
+
+// upload one or more same-sized images as the layers of a texture, with its view.
+fn upload_texture(
+    device: &Device,
+    queue: &Queue,
+    images: &[RgbaImage],
+    mipmaps: bool,
+    array: bool,
+    label: &str,
+) -> Result<(Texture, TextureView), Box<dyn Error>> {
+    let dimensions = images[0].dimensions();
+    let extent = Extent3d {
+        width: dimensions.0,
+        height: dimensions.1,
+        depth_or_array_layers: images.len() as u32,
+    };
+    let mip_level_count = if mipmaps {
+        extent.max_mips(wgpu::TextureDimension::D2)
+    } else {
+        1
+    };
+    let texture = device.create_texture(&TextureDescriptor {
+        size: extent,
+        mip_level_count,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        label: Some(label),
+        view_formats: &[],
+    });
+
+    // each mip level is downscaled from the full image on the cpu.
+    for (layer, image) in images.iter().enumerate() {
+        for mip_level in 0..mip_level_count {
+            let level_extent = extent.mip_level_size(mip_level, wgpu::TextureDimension::D2);
+            let level_image = if mip_level == 0 {
+                image.clone()
+            } else {
+                image::imageops::resize(
+                    image,
+                    level_extent.width,
+                    level_extent.height,
+                    image::imageops::FilterType::Triangle,
+                )
+            };
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer as u32,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &level_image,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * level_extent.width),
+                    rows_per_image: Some(level_extent.height),
+                },
+                Extent3d {
+                    depth_or_array_layers: 1,
+                    ..level_extent
+                },
+            );
+        }
+    }
+    let view = texture.create_view(&wgpu::TextureViewDescriptor {
+        dimension: Some(if array {
+            TextureViewDimension::D2Array
+        } else {
+            TextureViewDimension::D2
+        }),
+        ..Default::default()
+    });
+    Ok((texture, view))
+}
+
+// decoding happens before any gpu lock is taken, so it can also run on a worker thread.
+fn decode_sheets(
+    sheet_infos: &[TextureSheetDefinition],
+    strict: bool,
+    vfs: &Mutex<Vfs>,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<RgbaImage>, Box<dyn Error>> {
+    let mut images = vec![];
+    for sheet_info in sheet_infos.iter() {
+        images.push(load_image(sheet_info, strict, vfs, warnings)?);
+    }
+    Ok(images)
+}
+
+// array sheets pad smaller images to the largest, anchored top left, so pixel rects within
+// each sheet are unchanged. mip levels follow the first sheet's sampler options.
+//
+// a one layer array gets an unused second layer: the gl backend makes one layer textures
+// plain 2d ones, which then sample black through an array view.
+fn texture_from_images(
+    device: Arc<Mutex<Device>>,
+    queue: Arc<Mutex<Queue>>,
+    images: Vec<RgbaImage>,
+    sheet_infos: Vec<TextureSheetDefinition>,
+    array: bool,
+) -> Result<SheetTexture, Box<dyn Error>> {
+    let dimensions = images.iter().fold(UVec2::ONE, |d, image| {
+        d.max(UVec2::new(image.width(), image.height()))
+    });
+    let images: Vec<RgbaImage> = if array {
+        let mut images: Vec<RgbaImage> = images
+            .into_iter()
+            .map(|image| {
+                let mut padded = RgbaImage::new(dimensions.x, dimensions.y);
+                image::imageops::replace(&mut padded, &image, 0, 0);
+                padded
+            })
+            .collect();
+        if images.len() == 1 {
+            images.push(RgbaImage::new(dimensions.x, dimensions.y));
+        }
+        images
+    } else {
+        images
+    };
+    let label = if array {
+        "texture array"
+    } else {
+        &sheet_infos[0].path
+    };
+    let device = device.lock().unwrap();
+    let queue = queue.lock().unwrap();
+    let (texture, view) = upload_texture(
+        &device,
+        &queue,
+        &images,
+        sheet_infos[0].sampler.mipmaps,
+        array,
+        label,
+    )?;
+
+    Ok(SheetTexture {
+        sources: sheet_infos,
+        array,
+        dimensions,
+        texture,
+        view,
+    })
+}
+
+fn load_texture(
+    device: Arc<Mutex<Device>>,
+    queue: Arc<Mutex<Queue>>,
+    sheet_info: TextureSheetDefinition,
+    strict: bool,
+    vfs: &Mutex<Vfs>,
+    warnings: &mut Vec<String>,
+) -> Result<SheetTexture, Box<dyn Error>> {
+    let images = decode_sheets(std::slice::from_ref(&sheet_info), strict, vfs, warnings)?;
+    texture_from_images(device, queue, images, vec![sheet_info], false)
+}
+
+fn load_texture_from_atlas(
+    device: Arc<Mutex<Device>>,
+    queue: Arc<Mutex<Queue>>,
+    atlas: PackedAtlas,
+) -> Result<SheetTexture, Box<dyn Error>> {
+    let device = device.lock().unwrap();
+    let queue = queue.lock().unwrap();
+    let dimensions = UVec2::new(atlas.image.width(), atlas.image.height());
+    let (texture, view) = upload_texture(
+        &device,
+        &queue,
+        &[atlas.image],
+        atlas.sheet_info.sampler.mipmaps,
+        false,
+        "packed atlas",
+    )?;
+
+    Ok(SheetTexture {
+        sources: vec![atlas.sheet_info],
+        array: false,
+        dimensions,
+        texture,
+        view,
+    })
+}
+
+// several sheets as layers of one texture array.
+fn load_texture_array(
+    device: Arc<Mutex<Device>>,
+    queue: Arc<Mutex<Queue>>,
+    sheet_infos: Vec<TextureSheetDefinition>,
+    strict: bool,
+    vfs: &Mutex<Vfs>,
+    warnings: &mut Vec<String>,
+) -> Result<SheetTexture, Box<dyn Error>> {
+    if sheet_infos.is_empty() {
+        return Err("texture array needs at least one sheet".into());
+    }
+    let images = decode_sheets(&sheet_infos, strict, vfs, warnings)?;
+    texture_from_images(device, queue, images, sheet_infos, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn old_handles_keep_a_reloaded_asset_cached() {
+        let mut cache = AssetCache::new();
+        let old = cache.insert("font.fnt", 1);
+        cache.replace("font.fnt", 2);
+        assert_eq!(cache.unload_unused(), 0);
+
+        let newer = cache.newer(&old).unwrap();
+        assert_eq!((newer.id, *newer), (old.id, 2));
+        drop(newer);
+        assert_eq!(cache.unload_unused(), 0);
+
+        drop(old);
+        assert_eq!(cache.unload_unused(), 1);
+        assert!(cache.is_empty());
+    }
+}
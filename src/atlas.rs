@@ -0,0 +1,240 @@
+use std::{
+    error::Error,
+    fs::{read_dir, read_to_string, write},
+    path::Path,
+    time::UNIX_EPOCH,
+};
+
+use glam::UVec2;
+use image::{GenericImage, ImageReader, RgbaImage};
+
+use crate::types::{SamplerOptions, TextureSheetClusterDefinition, TextureSheetDefinition};
+
+pub struct AtlasOptions {
+    // transparent pixels between frames.
+    pub padding: u32,
+    // pixels of each frame's edge repeated outward, so filtering at the border doesn't bleed.
+    pub extrude: u32,
+    pub max_size: u32,
+    // when set, the packed image and frame list are written to `<cache_path>.png` and
+    // `<cache_path>.atlas`, and reused while the source files are unchanged.
+    pub cache_path: Option<String>,
+}
+
+impl Default for AtlasOptions {
+    fn default() -> Self {
+        Self {
+            padding: 1,
+            extrude: 1,
+            max_size: 4096,
+            cache_path: None,
+        }
+    }
+}
+
+// a packed atlas image. every frame is a one-sub cluster labelled by its file stem,
+// so it can be looked up with TextureSheet::cluster_index and drawn as sub 0.
+pub struct PackedAtlas {
+    pub image: RgbaImage,
+    pub sheet_info: TextureSheetDefinition,
+}
+
+impl PackedAtlas {
+    // pack every png directly inside `dir`, in file name order.
+    pub fn pack_directory(dir: &str, options: &AtlasOptions) -> Result<Self, Box<dyn Error>> {
+        let mut paths = vec![];
+        for entry in read_dir(dir)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("png"))
+            {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        if paths.is_empty() {
+            return Err(format!("{}: no png files to pack", dir).into());
+        }
+
+        // the cache is keyed on the options and every source file's name, size and mtime.
+        let mut fingerprint = format!(
+            "padding={} extrude={} max_size={}",
+            options.padding, options.extrude, options.max_size
+        );
+        for path in paths.iter() {
+            let metadata = path.metadata()?;
+            let modified = metadata.modified()?.duration_since(UNIX_EPOCH)?.as_millis();
+            fingerprint.push_str(&format!(
+                " {}:{}:{}",
+                path.file_name().unwrap().to_string_lossy(),
+                metadata.len(),
+                modified
+            ));
+        }
+        if let Some(cache_path) = &options.cache_path {
+            if let Some(atlas) = Self::load_cache(cache_path, &fingerprint) {
+                return Ok(atlas);
+            }
+        }
+
+        let mut frames = vec![];
+        for path in paths.iter() {
+            let name = path.file_stem().unwrap().to_string_lossy().to_string();
+            frames.push((name, ImageReader::open(path)?.decode()?.to_rgba8()));
+        }
+        let mut atlas = Self::pack(frames, options)?;
+
+        if let Some(cache_path) = &options.cache_path {
+            atlas.write_cache(cache_path, &fingerprint)?;
+            atlas.sheet_info.path = format!("{}.png", cache_path);
+        }
+        Ok(atlas)
+    }
+
+    // pack named images into one, tallest first onto shelves.
+    pub fn pack(
+        frames: Vec<(String, RgbaImage)>,
+        options: &AtlasOptions,
+    ) -> Result<Self, Box<dyn Error>> {
+        let border = options.extrude + options.padding;
+        let cell = |image: &RgbaImage| UVec2::new(image.width(), image.height()) + border * 2;
+
+        let mut order: Vec<usize> = (0..frames.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse((frames[i].1.height(), frames[i].1.width())));
+
+        // start from a square that could hold the total area, widening until the shelves fit.
+        let area: u32 = frames
+            .iter()
+            .map(|(_, image)| cell(image).element_product())
+            .sum();
+        let widest = frames
+            .iter()
+            .map(|(_, image)| cell(image).x)
+            .max()
+            .unwrap_or(1);
+        let mut width = ((area as f32).sqrt().ceil() as u32)
+            .max(widest)
+            .next_power_of_two();
+        let (positions, height) = loop {
+            if width > options.max_size {
+                return Err(format!("atlas: frames don't fit in {0}x{0}", options.max_size).into());
+            }
+            let mut positions = vec![UVec2::ZERO; frames.len()];
+            let mut cursor = UVec2::ZERO;
+            let mut shelf_height = 0;
+            for &i in order.iter() {
+                let size = cell(&frames[i].1);
+                if cursor.x + size.x > width {
+                    cursor = UVec2::new(0, cursor.y + shelf_height);
+                    shelf_height = 0;
+                }
+                positions[i] = cursor;
+                cursor.x += size.x;
+                shelf_height = shelf_height.max(size.y);
+            }
+            let height = (cursor.y + shelf_height).next_power_of_two();
+            if height <= options.max_size {
+                break (positions, height);
+            }
+            width *= 2;
+        };
+
+        let mut image = RgbaImage::new(width, height);
+        let mut clusters = vec![];
+        for ((label, frame), position) in frames.into_iter().zip(positions) {
+            let xy = position + border;
+            let wh = UVec2::new(frame.width(), frame.height());
+            image.copy_from(&frame, xy.x, xy.y)?;
+            extrude(&mut image, xy, wh, options.extrude);
+            clusters.push(TextureSheetClusterDefinition {
+                label,
+                offset: xy,
+                cluster_size: wh,
+                sub_size: wh,
+                spacing: UVec2::ZERO,
+            });
+        }
+
+        Ok(Self {
+            image,
+            sheet_info: TextureSheetDefinition {
+                path: "".to_string(),
+                clusters,
+                sampler: SamplerOptions::default(),
+            },
+        })
+    }
+
+    fn load_cache(cache_path: &str, fingerprint: &str) -> Option<Self> {
+        let image_path = format!("{}.png", cache_path);
+        let source = read_to_string(format!("{}.atlas", cache_path)).ok()?;
+        let mut lines = source.lines();
+        if lines.next()? != fingerprint {
+            return None;
+        }
+        let mut clusters = vec![];
+        for line in lines {
+            // x y w h label, with the label last so it may contain spaces.
+            let mut fields = line.splitn(5, ' ');
+            let mut int = || fields.next()?.parse::<u32>().ok();
+            let (x, y, w, h) = (int()?, int()?, int()?, int()?);
+            clusters.push(TextureSheetClusterDefinition {
+                label: fields.next()?.to_string(),
+                offset: UVec2::new(x, y),
+                cluster_size: UVec2::new(w, h),
+                sub_size: UVec2::new(w, h),
+                spacing: UVec2::ZERO,
+            });
+        }
+        let image = ImageReader::open(&image_path)
+            .ok()?
+            .decode()
+            .ok()?
+            .to_rgba8();
+        Some(Self {
+            image,
+            sheet_info: TextureSheetDefinition {
+                path: image_path,
+                clusters,
+                sampler: SamplerOptions::default(),
+            },
+        })
+    }
+
+    fn write_cache(&self, cache_path: &str, fingerprint: &str) -> Result<(), Box<dyn Error>> {
+        if let Some(parent) = Path::new(cache_path).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        self.image.save(format!("{}.png", cache_path))?;
+        let mut source = fingerprint.to_string();
+        for c in self.sheet_info.clusters.iter() {
+            source.push_str(&format!(
+                "\n{} {} {} {} {}",
+                c.offset.x, c.offset.y, c.sub_size.x, c.sub_size.y, c.label
+            ));
+        }
+        write(format!("{}.atlas", cache_path), source)?;
+        Ok(())
+    }
+}
+
+// repeat the outermost rows and columns of the frame at `xy` outward by `amount` pixels.
+fn extrude(image: &mut RgbaImage, xy: UVec2, wh: UVec2, amount: u32) {
+    if amount == 0 || wh.x == 0 || wh.y == 0 {
+        return;
+    }
+    let (left, top) = (xy.x - amount, xy.y - amount);
+    let (right, bottom) = (xy.x + wh.x + amount, xy.y + wh.y + amount);
+    for y in top..bottom {
+        for x in left..right {
+            if x >= xy.x && x < xy.x + wh.x && y >= xy.y && y < xy.y + wh.y {
+                continue;
+            }
+            let source_x = x.clamp(xy.x, xy.x + wh.x - 1);
+            let source_y = y.clamp(xy.y, xy.y + wh.y - 1);
+            let pixel = *image.get_pixel(source_x, source_y);
+            image.put_pixel(x, y, pixel);
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use std::collections::HashMap;
+
+use glam::{IVec2, UVec2};
+
+use crate::tilemap::TileId;
+
+// mask bits for each kind of rule. north is the previous row of the tilemap.
+pub const EDGE_N: u8 = 1;
+pub const EDGE_E: u8 = 1 << 1;
+pub const EDGE_S: u8 = 1 << 2;
+pub const EDGE_W: u8 = 1 << 3;
+
+pub const BLOB_N: u8 = 1;
+pub const BLOB_NE: u8 = 1 << 1;
+pub const BLOB_E: u8 = 1 << 2;
+pub const BLOB_SE: u8 = 1 << 3;
+pub const BLOB_S: u8 = 1 << 4;
+pub const BLOB_SW: u8 = 1 << 5;
+pub const BLOB_W: u8 = 1 << 6;
+pub const BLOB_NW: u8 = 1 << 7;
+
+pub const CORNER_NE: u8 = 1;
+pub const CORNER_SE: u8 = 1 << 1;
+pub const CORNER_SW: u8 = 1 << 2;
+pub const CORNER_NW: u8 = 1 << 3;
+
+const N: IVec2 = IVec2::new(0, -1);
+const E: IVec2 = IVec2::new(1, 0);
+const S: IVec2 = IVec2::new(0, 1);
+const W: IVec2 = IVec2::new(-1, 0);
+
+// which neighbours of a cell pick its tile.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Neighbours {
+    // the four edges, 16 tiles.
+    Edges,
+    // all eight, with a diagonal only counted when both edges beside it match: the
+    // 47 tile blob.
+    Blob,
+    // Wang corners: a corner is set when the three cells around it match too, 16 tiles.
+    // suits sheets drawn as one big blob with its inner corners cut out.
+    Corners,
+}
+
+impl Neighbours {
+    // the mask for a cell, given whether the cell at an offset from it is the same terrain.
+    pub fn mask(&self, matches: impl Fn(IVec2) -> bool) -> u8 {
+        let (n, e, s, w) = (matches(N), matches(E), matches(S), matches(W));
+        let bit = |set: bool, bit: u8| if set { bit } else { 0 };
+        match self {
+            Neighbours::Edges => bit(n, EDGE_N) | bit(e, EDGE_E) | bit(s, EDGE_S) | bit(w, EDGE_W),
+            Neighbours::Blob => {
+                bit(n, BLOB_N)
+                    | bit(e, BLOB_E)
+                    | bit(s, BLOB_S)
+                    | bit(w, BLOB_W)
+                    | bit(n && e && matches(N + E), BLOB_NE)
+                    | bit(s && e && matches(S + E), BLOB_SE)
+                    | bit(s && w && matches(S + W), BLOB_SW)
+                    | bit(n && w && matches(N + W), BLOB_NW)
+            }
+            Neighbours::Corners => {
+                bit(n && e && matches(N + E), CORNER_NE)
+                    | bit(s && e && matches(S + E), CORNER_SE)
+                    | bit(s && w && matches(S + W), CORNER_SW)
+                    | bit(n && w && matches(N + W), CORNER_NW)
+            }
+        }
+    }
+
+    // every mask a cell can get, ascending. lay a cluster out in this order to use
+    // AutotileRule::layout with it directly.
+    pub fn masks(&self) -> Vec<u8> {
+        match self {
+            Neighbours::Edges | Neighbours::Corners => (0..16).collect(),
+            Neighbours::Blob => (0..=255u8)
+                .filter(|mask| {
+                    let has = |bit: u8| mask & bit != 0;
+                    [
+                        (BLOB_NE, BLOB_N, BLOB_E),
+                        (BLOB_SE, BLOB_S, BLOB_E),
+                        (BLOB_SW, BLOB_S, BLOB_W),
+                        (BLOB_NW, BLOB_N, BLOB_W),
+                    ]
+                    .iter()
+                    .all(|(corner, a, b)| !has(*corner) || (has(*a) && has(*b)))
+                })
+                .collect(),
+        }
+    }
+}
+
+// one terrain's tiles by neighbour mask. every tile in a rule counts as the same terrain,
+// and placing any of them with Tilemap::set_tile picks the right one for the cell and
+// re-picks its neighbours. masks with several tiles choose between them by weight, and
+// masks with none use the fallback.
+//
+// variants are picked from a hash of the cell and `seed`, so a cell keeps its variant
+// when its neighbours change.
+#[derive(Clone, Debug)]
+pub struct AutotileRule {
+    pub neighbours: Neighbours,
+    pub tiles: HashMap<u8, Vec<(TileId, f32)>>,
+    pub fallback: Vec<(TileId, f32)>,
+    // whether cells off the map match, so terrain runs off the edge unbroken.
+    pub edges_match: bool,
+    pub seed: u32,
+}
+
+impl AutotileRule {
+    pub fn new(neighbours: Neighbours) -> Self {
+        Self {
+            neighbours,
+            tiles: HashMap::new(),
+            fallback: vec![],
+            edges_match: true,
+            seed: 0,
+        }
+    }
+
+    pub fn tile(self, mask: u8, id: TileId) -> Self {
+        self.variant(mask, id, 1.0)
+    }
+
+    pub fn variant(mut self, mask: u8, id: TileId, weight: f32) -> Self {
+        self.tiles.entry(mask).or_default().push((id, weight));
+        self
+    }
+
+    // consecutive tiles from `first`, one per mask, e.g. the subs of a cluster added in
+    // order. repeated masks become variants.
+    pub fn layout(mut self, first: TileId, masks: &[u8]) -> Self {
+        for (i, mask) in masks.iter().enumerate() {
+            self = self.tile(*mask, first + i as TileId);
+        }
+        self
+    }
+
+    pub fn fallback(mut self, id: TileId, weight: f32) -> Self {
+        self.fallback.push((id, weight));
+        self
+    }
+
+    // every tile in the rule, variants and fallback included.
+    pub fn ids(&self) -> impl Iterator<Item = TileId> + '_ {
+        self.tiles
+            .values()
+            .chain(std::iter::once(&self.fallback))
+            .flat_map(|variants| variants.iter().map(|(tile, _)| *tile))
+    }
+
+    pub fn contains(&self, id: TileId) -> bool {
+        self.ids().any(|tile| tile == id)
+    }
+
+    // the tile for a cell at `xy` whose neighbours give `mask`.
+    pub fn pick(&self, mask: u8, xy: UVec2) -> Option<TileId> {
+        let variants = self
+            .tiles
+            .get(&mask)
+            .filter(|variants| !variants.is_empty())
+            .unwrap_or(&self.fallback);
+        let total: f32 = variants.iter().map(|(_, weight)| weight).sum();
+        let mut roll = cell_hash(xy, self.seed) * total;
+        for (id, weight) in variants.iter() {
+            if roll < *weight {
+                return Some(*id);
+            }
+            roll -= weight;
+        }
+        variants.last().map(|(id, _)| *id)
+    }
+}
+
+// a stable value in 0..1 for a cell.
+fn cell_hash(xy: UVec2, seed: u32) -> f32 {
+    let mut h = xy.x.wrapping_mul(0x9e37_79b1)
+        ^ xy.y.wrapping_mul(0x85eb_ca77)
+        ^ seed.wrapping_mul(0xc2b2_ae3d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x2c1b_3c6d);
+    h ^= h >> 12;
+    h = h.wrapping_mul(0x297a_2d39);
+    h ^= h >> 15;
+    (h >> 8) as f32 / (1 << 24) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec2;
+
+    use crate::tilemap::{TileDef, Tilemap};
+    use crate::types::SpriteRef;
+
+    // a mask from the offsets that match.
+    fn mask_of(neighbours: Neighbours, matching: &[IVec2]) -> u8 {
+        neighbours.mask(|offset| matching.contains(&offset))
+    }
+
+    #[test]
+    fn edges_mask_counts_only_edges() {
+        assert_eq!(mask_of(Neighbours::Edges, &[]), 0);
+        assert_eq!(mask_of(Neighbours::Edges, &[N, E]), EDGE_N | EDGE_E);
+        // diagonals don't count.
+        assert_eq!(mask_of(Neighbours::Edges, &[S, W, S + W]), EDGE_S | EDGE_W);
+        assert_eq!(Neighbours::Edges.masks().len(), 16);
+    }
+
+    #[test]
+    fn blob_mask_needs_both_edges_for_a_diagonal() {
+        assert_eq!(mask_of(Neighbours::Blob, &[N + E, N]), BLOB_N);
+        assert_eq!(
+            mask_of(Neighbours::Blob, &[N, E, N + E]),
+            BLOB_N | BLOB_E | BLOB_NE
+        );
+        let all = [N, E, S, W, N + E, S + E, S + W, N + W];
+        assert_eq!(mask_of(Neighbours::Blob, &all), 0xff);
+        let masks = Neighbours::Blob.masks();
+        assert_eq!(masks.len(), 47);
+        assert!(!masks.contains(&BLOB_NE));
+    }
+
+    #[test]
+    fn corners_mask_needs_all_three_cells() {
+        assert_eq!(mask_of(Neighbours::Corners, &[N, E]), 0);
+        assert_eq!(mask_of(Neighbours::Corners, &[N, E, N + E]), CORNER_NE);
+        let all = [N, E, S, W, N + E, S + E, S + W, N + W];
+        assert_eq!(
+            mask_of(Neighbours::Corners, &all),
+            CORNER_NE | CORNER_SE | CORNER_SW | CORNER_NW
+        );
+    }
+
+    #[test]
+    fn pick_uses_the_mask_then_the_fallback() {
+        let rule = AutotileRule::new(Neighbours::Edges)
+            .layout(10, &[0, EDGE_N, EDGE_E])
+            .fallback(99, 1.0);
+        assert_eq!(rule.pick(0, UVec2::ZERO), Some(10));
+        assert_eq!(rule.pick(EDGE_E, UVec2::new(3, 4)), Some(12));
+        assert_eq!(rule.pick(EDGE_S, UVec2::ZERO), Some(99));
+    }
+
+    #[test]
+    fn pick_skips_zero_weight_variants_and_is_stable() {
+        let rule = AutotileRule::new(Neighbours::Edges)
+            .variant(0, 1, 0.0)
+            .variant(0, 2, 1.0);
+        for y in 0..8 {
+            for x in 0..8 {
+                let xy = UVec2::new(x, y);
+                assert_eq!(rule.pick(0, xy), Some(2));
+                assert_eq!(rule.pick(0, xy), rule.pick(0, xy));
+            }
+        }
+    }
+
+    #[test]
+    fn set_tile_repicks_neighbours() {
+        let mut map = Tilemap::new(0, UVec2::new(3, 1), Vec2::splat(16.0));
+        let first = map.add_tile(TileDef::new(SpriteRef::new(0, 0)));
+        for sub in 1..16 {
+            map.add_tile(TileDef::new(SpriteRef::new(0, sub)));
+        }
+        let masks = Neighbours::Edges.masks();
+        let mut rule = AutotileRule::new(Neighbours::Edges).layout(first, &masks);
+        rule.edges_match = false;
+        map.add_autotile(rule);
+        let layer = map.add_layer("ground");
+        for x in 0..3 {
+            map.set_tile(layer, UVec2::new(x, 0), first);
+        }
+        assert_eq!(map.tile(layer, UVec2::new(0, 0)), first + EDGE_E as TileId);
+        assert_eq!(
+            map.tile(layer, UVec2::new(1, 0)),
+            first + (EDGE_E | EDGE_W) as TileId
+        );
+        assert_eq!(map.tile(layer, UVec2::new(2, 0)), first + EDGE_W as TileId);
+    }
+
+    #[test]
+    fn pick_is_none_without_a_tile_or_fallback() {
+        assert_eq!(
+            AutotileRule::new(Neighbours::Blob).pick(0xff, UVec2::ZERO),
+            None
+        );
+        let rule = AutotileRule::new(Neighbours::Edges).tile(EDGE_N, 5);
+        assert_eq!(rule.pick(EDGE_N, UVec2::ZERO), Some(5));
+        assert_eq!(rule.pick(EDGE_S, UVec2::ZERO), None);
+    }
+
+    #[test]
+    fn pick_keeps_to_the_rule_with_bad_weights() {
+        let rules = [
+            AutotileRule::new(Neighbours::Edges)
+                .variant(0, 1, 0.0)
+                .variant(0, 2, 0.0),
+            AutotileRule::new(Neighbours::Edges)
+                .variant(0, 1, -1.0)
+                .variant(0, 2, 1.0),
+            AutotileRule::new(Neighbours::Edges)
+                .variant(0, 1, f32::NAN)
+                .variant(0, 2, 1.0),
+        ];
+        for rule in rules {
+            for x in 0..8 {
+                let picked = rule.pick(0, UVec2::new(x, 0));
+                assert!(matches!(picked, Some(1 | 2)), "picked {:?}", picked);
+            }
+        }
+    }
+
+    #[test]
+    fn unmatched_cells_keep_their_tile() {
+        let mut map = Tilemap::new(0, UVec2::new(2, 1), Vec2::splat(16.0));
+        let lone = map.add_tile(TileDef::new(SpriteRef::new(0, 0)));
+        let row = map.add_tile(TileDef::new(SpriteRef::new(0, 1)));
+        // only a tile with neighbours both sides has a match, and there's no fallback.
+        let mut rule = AutotileRule::new(Neighbours::Edges)
+            .tile(EDGE_E | EDGE_W, row)
+            .tile(0, lone);
+        rule.edges_match = false;
+        map.add_autotile(rule);
+        let layer = map.add_layer("ground");
+        map.set_tile(layer, UVec2::new(0, 0), lone);
+        assert_eq!(map.tile(layer, UVec2::new(0, 0)), lone);
+        map.set_tile(layer, UVec2::new(1, 0), lone);
+        assert_eq!(map.tile(layer, UVec2::new(0, 0)), lone);
+        assert_eq!(map.tile(layer, UVec2::new(1, 0)), lone);
+    }
+}
@@ -0,0 +1,25 @@
+use std::{env, error::Error, fs::read, path::Path};
+
+use virae::vfs::PackBuilder;
+
+// usage: virae-pack <output pack> <file or directory>...
+// entries are named by the paths given, so run it from where the game resolves asset paths.
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() < 2 {
+        return Err("usage: virae-pack <output pack> <file or directory>...".into());
+    }
+    let mut builder = PackBuilder::new();
+    let mut count = 0;
+    for input in args[1..].iter() {
+        if Path::new(input).is_dir() {
+            count += builder.add_dir(input)?;
+        } else {
+            builder.add(input, read(input)?);
+            count += 1;
+        }
+    }
+    builder.write(&args[0])?;
+    println!("packed {} files into {}", count, args[0]);
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use glam::{Mat4, UVec2, Vec2};
+
+// instances are placed in world space, which is measured in logical pixels: at zoom 1 one
+// world unit covers one pixel of Context::logical_size. the camera maps it to the screen
+// through the groups' view matrix.
+//
+// with y_down (the default) y grows downward and `position` is the world point at the top
+// left of the screen, so world and screen coordinates agree until the camera moves. with
+// y pointing up, `position` is at the bottom left instead. either way an instance's location
+// is the top left corner of its rect as drawn, and positive rotation turns from +x to +y.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Camera {
+    pub position: Vec2,
+    pub zoom: f32,
+    pub y_down: bool,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            position: Vec2::ZERO,
+            zoom: 1.0,
+            y_down: true,
+        }
+    }
+}
+
+impl Camera {
+    // world to clip space, for a screen `extent` logical pixels in size.
+    pub fn view_matrix(&self, extent: UVec2) -> Mat4 {
+        let size = extent.as_vec2() / self.zoom;
+        let (left, right) = (self.position.x, self.position.x + size.x);
+        let (bottom, top) = match self.y_down {
+            true => (self.position.y + size.y, self.position.y),
+            false => (self.position.y, self.position.y + size.y),
+        };
+        Mat4::orthographic_lh(left, right, bottom, top, -1.0, 1.0)
+    }
+
+    // world position to logical pixels from the screen's top left, e.g. to place a text label.
+    pub fn world_to_screen(&self, world: Vec2, extent: UVec2) -> Vec2 {
+        let screen = (world - self.position) * self.zoom;
+        match self.y_down {
+            true => screen,
+            false => Vec2::new(screen.x, extent.y as f32 - screen.y),
+        }
+    }
+
+    // logical pixels from the screen's top left, e.g. the cursor, to a world position.
+    pub fn screen_to_world(&self, screen: Vec2, extent: UVec2) -> Vec2 {
+        let screen = match self.y_down {
+            true => screen,
+            false => Vec2::new(screen.x, extent.y as f32 - screen.y),
+        };
+        screen / self.zoom + self.position
+    }
+}
@@ -0,0 +1,408 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    ops::Range,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use glam::{IVec2, UVec2, Vec4};
+use wgpu::Queue;
+
+use crate::geo::GeoInstances;
+use crate::types::{ComponentTransform, PixelRect, SamplerOptions, TextureSheetDefinition};
+use crate::vfs::Vfs;
+
+// a glyph's rect on the sheet. offset and advance are signed, as in .fnt files, so a glyph
+// may be drawn left of or above the cursor, or step it back.
+#[derive(Copy, Clone)]
+pub struct BitmapGlyph {
+    pub xy: UVec2,
+    pub wh: UVec2,
+    pub offset: IVec2,
+    pub advance: i32,
+}
+
+// a font whose glyphs are rects on a texture sheet, drawn as unit square instances.
+// all measurements are in sheet pixels and multiplied by the integer scale when drawn. a
+// scale of 0 draws at 1.
+pub struct BitmapFont {
+    pub glyphs: HashMap<char, BitmapGlyph>,
+    pub kerning: HashMap<(char, char), i32>,
+    pub line_height: u32,
+    pub fallback: Option<char>,
+}
+
+impl BitmapFont {
+    // a monospaced font from a sheet cluster: each sub-image in order is the next char of `chars`.
+    pub fn from_cluster(
+        sheet_info: &TextureSheetDefinition,
+        cluster_index: usize,
+        chars: &str,
+    ) -> Self {
+        let c /*cluster*/ = &sheet_info.clusters[cluster_index];
+        let glyphs = chars
+            .chars()
+            .enumerate()
+            .map(|(sub_index, ch)| {
+                (
+                    ch,
+                    BitmapGlyph {
+                        xy: c.sub_offset(sub_index),
+                        wh: c.sub_size,
+                        offset: IVec2::ZERO,
+                        advance: c.sub_size.x as i32,
+                    },
+                )
+            })
+            .collect::<HashMap<_, _>>();
+        let fallback = if glyphs.contains_key(&'?') {
+            Some('?')
+        } else {
+            None
+        };
+        Self {
+            glyphs,
+            kerning: HashMap::new(),
+            line_height: c.sub_size.y,
+            fallback,
+        }
+    }
+
+    // an angelcode bmfont text descriptor (.fnt), read through `vfs` like Assets::font.
+    // the returned sheet definition points at the font's page image.
+    pub fn from_fnt(
+        path: &str,
+        vfs: &Vfs,
+    ) -> Result<(Self, TextureSheetDefinition), Box<dyn Error>> {
+        Self::from_fnt_source(path, &vfs.read_to_string(path)?)
+    }
+
+    // a bmfont descriptor already read from `path`, e.g. out of a pack.
+    pub fn from_fnt_source(
+        path: &str,
+        source: &str,
+    ) -> Result<(Self, TextureSheetDefinition), Box<dyn Error>> {
+        let mut font = Self {
+            glyphs: HashMap::new(),
+            kerning: HashMap::new(),
+            line_height: 0,
+            fallback: None,
+        };
+        let mut page_file = None;
+
+        for line in source.lines() {
+            let mut tokens = fnt_tokens(line).into_iter();
+            let tag = match tokens.next() {
+                Some((tag, _)) => tag,
+                None => continue,
+            };
+            let fields: HashMap<String, String> = tokens.collect();
+            let int = |key: &str| -> Result<i32, Box<dyn Error>> {
+                Ok(fields
+                    .get(key)
+                    .ok_or(format!("{}: '{}' line missing {}", path, tag, key))?
+                    .parse::<i32>()?)
+            };
+            // sizes and positions can't be negative, so they're parsed as such rather than
+            // wrapping. ids that aren't chars, e.g. -1 from some exporters, are skipped.
+            let uint = |key: &str| -> Result<u32, Box<dyn Error>> {
+                Ok(fields
+                    .get(key)
+                    .ok_or(format!("{}: '{}' line missing {}", path, tag, key))?
+                    .parse::<u32>()?)
+            };
+            match tag.as_str() {
+                "common" => {
+                    font.line_height = uint("lineHeight")?;
+                    if fields.get("pages").is_some_and(|p| p != "1") {
+                        return Err(
+                            format!("{}: multi-page bitmap fonts are unsupported", path).into()
+                        );
+                    }
+                }
+                "page" => {
+                    page_file = fields.get("file").cloned();
+                }
+                "char" => {
+                    let ch = match u32::try_from(int("id")?).ok().and_then(char::from_u32) {
+                        Some(ch) => ch,
+                        None => continue,
+                    };
+                    font.glyphs.insert(
+                        ch,
+                        BitmapGlyph {
+                            xy: UVec2::new(uint("x")?, uint("y")?),
+                            wh: UVec2::new(uint("width")?, uint("height")?),
+                            offset: IVec2::new(int("xoffset")?, int("yoffset")?),
+                            advance: int("xadvance")?,
+                        },
+                    );
+                }
+                "kerning" => {
+                    let first = u32::try_from(int("first")?).ok().and_then(char::from_u32);
+                    let second = u32::try_from(int("second")?).ok().and_then(char::from_u32);
+                    if let (Some(first), Some(second)) = (first, second) {
+                        font.kerning.insert((first, second), int("amount")?);
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        if font.glyphs.contains_key(&'?') {
+            font.fallback = Some('?');
+        }
+        let page_file = page_file.ok_or(format!("{}: no page image", path))?;
+        let page_path = Path::new(path)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(page_file);
+        Ok((
+            font,
+            TextureSheetDefinition {
+                path: page_path.to_string_lossy().to_string(),
+                clusters: vec![],
+                sampler: SamplerOptions::default(),
+            },
+        ))
+    }
+
+    fn glyph(&self, ch: char) -> Option<&BitmapGlyph> {
+        self.glyphs
+            .get(&ch)
+            .or_else(|| self.fallback.and_then(|f| self.glyphs.get(&f)))
+    }
+
+    // destination and source rects of every visible glyph in `text`, laid out from `xy`.
+    pub fn layout(&self, text: &str, xy: IVec2, scale: u32) -> Vec<(IVec2, UVec2, UVec2)> {
+        let scale = scale.max(1);
+        let scale_i = scale as i32;
+        let mut quads = vec![];
+        let mut cursor = xy;
+        let mut previous: Option<char> = None;
+        for ch in text.chars() {
+            if ch == '\n' {
+                cursor = IVec2::new(xy.x, cursor.y + (self.line_height * scale) as i32);
+                previous = None;
+                continue;
+            }
+            if let Some(kern) = previous.and_then(|p| self.kerning.get(&(p, ch))) {
+                cursor.x += kern * scale_i;
+            }
+            if let Some(glyph) = self.glyph(ch) {
+                if glyph.wh.x > 0 && glyph.wh.y > 0 {
+                    quads.push((cursor + glyph.offset * scale_i, glyph.wh * scale, glyph.xy));
+                }
+                cursor.x += glyph.advance * scale_i;
+            }
+            previous = Some(ch);
+        }
+        quads
+    }
+
+    pub fn measure(&self, text: &str, scale: u32) -> UVec2 {
+        let scale = scale.max(1);
+        let mut size = UVec2::ZERO;
+        for line in text.split('\n') {
+            let mut width = 0;
+            let mut previous: Option<char> = None;
+            for ch in line.chars() {
+                if let Some(kern) = previous.and_then(|p| self.kerning.get(&(p, ch))) {
+                    width += kern;
+                }
+                if let Some(glyph) = self.glyph(ch) {
+                    width += glyph.advance;
+                }
+                previous = Some(ch);
+            }
+            size.x = size.x.max(width.max(0) as u32 * scale);
+            size.y += self.line_height * scale;
+        }
+        size
+    }
+
+    // add one instance per visible glyph to the group, tinted by `color`.
+    pub fn add_text(
+        &self,
+        group: &mut GeoInstances,
+        queue: Arc<Mutex<Queue>>,
+        text: &str,
+        xy: IVec2,
+        scale: u32,
+        color: Vec4,
+    ) -> Range<usize> {
+        let scale = scale.max(1);
+        let start = group.instance_buffer_manager.data.len();
+        let sheet_dimensions = group.sheet.texture.dimensions;
+        for (dest_xy, dest_wh, src_xy) in self.layout(text, xy, scale) {
+            group.add_new_with_tex(
+                queue.clone(),
+                ComponentTransform::world_rect(dest_xy.as_vec2(), dest_wh.as_vec2()),
+                ComponentTransform::tex_transform_from_pixel_rect(PixelRect {
+                    xy: src_xy.as_ivec2(),
+                    wh: dest_wh / scale,
+                    extent: sheet_dimensions,
+                }),
+                color,
+            );
+        }
+        start..group.instance_buffer_manager.data.len()
+    }
+
+    // reuse a range from add_text for new text. glyphs past the range are dropped,
+    // unused instances in the range are hidden.
+    pub fn set_text(
+        &self,
+        group: &mut GeoInstances,
+        range: Range<usize>,
+        text: &str,
+        xy: IVec2,
+        scale: u32,
+        color: Vec4,
+    ) {
+        let scale = scale.max(1);
+        let sheet_dimensions = group.sheet.texture.dimensions;
+        let mut quads = self.layout(text, xy, scale).into_iter();
+        for instance in group.instance_buffer_manager.data[range].iter_mut() {
+            match quads.next() {
+                Some((dest_xy, dest_wh, src_xy)) => {
+                    instance.transform =
+                        ComponentTransform::world_rect(dest_xy.as_vec2(), dest_wh.as_vec2());
+                    instance.tex_transform =
+                        ComponentTransform::tex_transform_from_pixel_rect(PixelRect {
+                            xy: src_xy.as_ivec2(),
+                            wh: dest_wh / scale,
+                            extent: sheet_dimensions,
+                        });
+                    instance.color = color;
+                }
+                None => instance.color = Vec4::ZERO,
+            }
+            instance.needs_update = true;
+        }
+    }
+}
+
+// split a bmfont line into its tag and key=value pairs, keeping quoted values whole.
+fn fnt_tokens(line: &str) -> Vec<(String, String)> {
+    let mut tokens = vec![];
+    let mut chars = line.trim().chars().peekable();
+    while chars.peek().is_some() {
+        while chars.peek().is_some_and(|c| c.is_whitespace()) {
+            chars.next();
+        }
+        let mut key = String::new();
+        while let Some(&c) = chars.peek() {
+            if c == '=' || c.is_whitespace() {
+                break;
+            }
+            key.push(c);
+            chars.next();
+        }
+        let mut value = String::new();
+        if chars.peek() == Some(&'=') {
+            chars.next();
+            if chars.peek() == Some(&'"') {
+                chars.next();
+                for c in chars.by_ref() {
+                    if c == '"' {
+                        break;
+                    }
+                    value.push(c);
+                }
+            } else {
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() {
+                        break;
+                    }
+                    value.push(c);
+                    chars.next();
+                }
+            }
+        }
+        if !key.is_empty() {
+            tokens.push((key, value));
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FNT: &str = r#"info face="Pixel Sans" size=8
+common lineHeight=10 base=8 scaleW=64 scaleH=64 pages=1
+page id=0 file="pixel sans.png"
+chars count=3
+char id=65 x=0 y=0 width=6 height=8 xoffset=-1 yoffset=-2 xadvance=5
+char id=66 x=6 y=0 width=6 height=8 xoffset=0 yoffset=0 xadvance=6
+char id=-1 x=12 y=0 width=6 height=8 xoffset=0 yoffset=0 xadvance=6
+kerning first=65 second=66 amount=-1
+"#;
+
+    #[test]
+    fn tokens_keep_quoted_spaces() {
+        let tokens = fnt_tokens(r#"page id=0 file="pixel sans.png"  "#);
+        assert_eq!(
+            tokens,
+            vec![
+                ("page".to_string(), String::new()),
+                ("id".to_string(), "0".to_string()),
+                ("file".to_string(), "pixel sans.png".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_signed_offsets_and_skips_bad_ids() {
+        let (font, sheet) = BitmapFont::from_fnt_source("fonts/pixel.fnt", FNT).unwrap();
+        assert_eq!(
+            sheet.path,
+            Path::new("fonts").join("pixel sans.png").to_string_lossy()
+        );
+        assert_eq!(font.line_height, 10);
+        assert_eq!(font.glyphs.len(), 2);
+        let a = font.glyphs[&'A'];
+        assert_eq!((a.offset, a.advance), (IVec2::new(-1, -2), 5));
+        assert_eq!(font.kerning[&('A', 'B')], -1);
+
+        // the negative offset draws A left of and above the cursor, and kerning pulls B in.
+        let quads = font.layout("AB", IVec2::new(10, 10), 2);
+        assert_eq!(quads[0].0, IVec2::new(8, 6));
+        assert_eq!(quads[1].0, IVec2::new(10 + 5 * 2 - 2, 10));
+        assert_eq!(font.measure("AB", 1), UVec2::new(10, 10));
+    }
+
+    #[test]
+    fn missing_keys_are_errors() {
+        let source = FNT.replace(" width=6 height=8 xoffset=-1", " height=8 xoffset=-1");
+        assert!(BitmapFont::from_fnt_source("pixel.fnt", &source).is_err());
+        let source = FNT.replace("page id=0 file=\"pixel sans.png\"\n", "");
+        assert!(BitmapFont::from_fnt_source("pixel.fnt", &source).is_err());
+    }
+
+    #[test]
+    fn negative_sizes_are_errors() {
+        let source = FNT.replace(
+            "width=6 height=8 xoffset=-1",
+            "width=-6 height=8 xoffset=-1",
+        );
+        assert!(BitmapFont::from_fnt_source("pixel.fnt", &source).is_err());
+    }
+
+    #[test]
+    fn rejects_multiple_pages() {
+        let source = FNT.replace("pages=1", "pages=2");
+        assert!(BitmapFont::from_fnt_source("pixel.fnt", &source).is_err());
+    }
+
+    #[test]
+    fn reads_through_the_vfs() {
+        let mut vfs = Vfs::new();
+        vfs.mount_embedded("fonts/pixel.fnt", FNT.as_bytes());
+        let (font, _) = BitmapFont::from_fnt("fonts/pixel.fnt", &vfs).unwrap();
+        assert!(font.glyphs.contains_key(&'B'));
+    }
+}
@@ -1,40 +1,43 @@
 #![allow(dead_code)]
+use crate::assets::{Assets, Handle, Shader};
+use crate::atlas::PackedAtlas;
+use crate::camera::Camera;
 use crate::types::{
-    ComponentTransform, GeoUniformMatrix, GeoUniformVec2, InstanceBufferManager,
-    RenderPipelineRecord, TextureSheet, TextureSheetDefinition, UNIT_SQUARE_BUFFER_LAYOUT,
+    BlendMode, ComponentTransform, DrawRun, GeoUniformMatrix, GeoUniformVec2,
+    InstanceBufferManager, InstanceData, MeshIndices, MeshLayout, RenderPipelineRecord, SortMode,
+    SpriteRef, TextureSheet, TextureSheetDefinition, UNIT_SQUARE_BUFFER_LAYOUT,
     UNIT_SQUARE_INDICES, UNIT_SQUARE_VERTICES,
 };
-use image::io::Reader;
-use image::RgbaImage;
+use bytemuck::Pod;
 use std::{
-    borrow::Cow,
     error::Error,
-    fs::read_to_string,
     mem::size_of,
-    path::Path,
     sync::{Arc, Mutex},
 };
+use wgpu::TextureViewDimension;
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
-    BlendState, ColorTargetState, ColorWrites,
+    BindGroupLayout, ColorTargetState, ColorWrites, PipelineLayout, RenderPipeline, ShaderModule,
 };
 
 use glam::{Mat4, UVec2, Vec2, Vec4};
 use wgpu::{
     BindGroup, BindGroupEntry, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource,
-    BindingType, Buffer, BufferBindingType, BufferSize, BufferUsages, Device, Extent3d, Face,
-    FragmentState, MultisampleState, PrimitiveState, Queue, RenderPipelineDescriptor,
-    ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureDescriptor, TextureFormat,
-    VertexState,
+    BindingType, Buffer, BufferBindingType, BufferSize, BufferUsages, Device, Face, FragmentState,
+    IndexFormat, MultisampleState, PrimitiveState, Queue, RenderPipelineDescriptor, ShaderStages,
+    TextureFormat, VertexState,
 };
 
 // various things needed to render geometry.
 pub struct GeoInstances {
     pub render_pipeline_record: RenderPipelineRecord,
+    pub bind_group_layout: BindGroupLayout,
     pub bind_group: BindGroup,
     pub vertex_buffer: Buffer,
     pub index_buffer: Buffer,
-    pub sheet: TextureSheet,
+    pub index_count: u32,
+    pub index_format: IndexFormat,
+    pub sheet: Handle<TextureSheet>,
     pub view_matrix_uniform: GeoUniformMatrix,
     pub screen_size_uniform: GeoUniformVec2,
     pub instance_buffer_manager: InstanceBufferManager,
@@ -55,102 +58,183 @@ impl GeoInstances {
             transform,
             self.sheet.cluster_sub_transform(cluster_index, sub_index),
             color,
+            0,
         );
         index
     }
 
+    // add an instance drawing from one layer of a texture array group.
+    pub fn add_new_from_layer(
+        &mut self,
+        queue: Arc<Mutex<Queue>>,
+        transform: ComponentTransform,
+        layer: usize,
+        cluster_index: usize,
+        sub_index: usize,
+        color: Vec4,
+    ) -> usize {
+        let index = self.instance_buffer_manager.data.len();
+        self.instance_buffer_manager.add_instance(
+            queue,
+            transform,
+            self.sheet
+                .layer_cluster_sub_transform(layer, cluster_index, sub_index),
+            color,
+            layer as u32,
+        );
+        index
+    }
+
+    pub fn add_new_with_tex(
+        &mut self,
+        queue: Arc<Mutex<Queue>>,
+        transform: ComponentTransform,
+        tex_transform: ComponentTransform,
+        color: Vec4,
+    ) -> usize {
+        let index = self.instance_buffer_manager.data.len();
+        self.instance_buffer_manager
+            .add_instance(queue, transform, tex_transform, color, 0);
+        index
+    }
+
     pub fn mark_all_for_update(&mut self) {
         for instance in self.instance_buffer_manager.data.iter_mut() {
             instance.needs_update = true;
         }
     }
 
-    pub fn recalc_screen_instances(&mut self, queue: Arc<Mutex<Queue>>, screen: UVec2) {
-        self.instance_buffer_manager
-            .recalc_screen_instances(queue, screen);
+    pub fn recalc_screen_instances(&mut self, queue: Arc<Mutex<Queue>>) {
+        self.instance_buffer_manager.recalc_screen_instances(queue);
+    }
+
+    pub fn set_sort_mode(&mut self, sort_mode: SortMode) {
+        self.instance_buffer_manager.set_sort_mode(sort_mode);
     }
 }
 
-fn load_texture(
-    device: Arc<Mutex<Device>>,
-    queue: Arc<Mutex<Queue>>,
-    sheet_info: TextureSheetDefinition,
-) -> Result<TextureSheet, Box<dyn Error>> {
-    let device = device.lock().unwrap();
-    let queue = queue.lock().unwrap();
-    let (image, path): (RgbaImage, String) = {
-        let texture_exists = Path::new(&sheet_info.path).try_exists()?;
-        if texture_exists {
-            let result: (RgbaImage, String) = (
-                Reader::open(sheet_info.path.clone())?.decode()?.to_rgba8(),
-                sheet_info.path.clone(),
-            );
-            result
-        } else {
-            let result: (RgbaImage, String) = (
-                image::load_from_memory(include_bytes!("../images/1x1white.png"))?.to_rgba8(),
-                "../images/1x1white.png".to_string(),
-            );
-            result
-        }
-    };
-
-    let dimensions = image.dimensions();
-    let extent = Extent3d {
-        width: dimensions.0,
-        height: dimensions.1,
-        depth_or_array_layers: 1,
-    };
-    let texture = device.create_texture(&TextureDescriptor {
-        size: extent,
-        mip_level_count: 1,
-        sample_count: 1,
-        dimension: wgpu::TextureDimension::D2,
-        format: wgpu::TextureFormat::Rgba8UnormSrgb,
-        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-        label: Some(&path),
-        view_formats: &[],
-    });
-    queue.write_texture(
-        wgpu::ImageCopyTexture {
-            texture: &texture,
-            mip_level: 0,
-            origin: wgpu::Origin3d::ZERO,
-            aspect: wgpu::TextureAspect::All,
+fn create_bind_group(
+    device: &Device,
+    layout: &BindGroupLayout,
+    view_matrix_uniform: &GeoUniformMatrix,
+    screen_size_uniform: &GeoUniformVec2,
+    sheet: &TextureSheet,
+) -> BindGroup {
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: view_matrix_uniform.buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: screen_size_uniform.buffer.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 2,
+                resource: BindingResource::TextureView(&sheet.texture.view),
+            },
+            BindGroupEntry {
+                binding: 3,
+                resource: BindingResource::Sampler(&sheet.sampler),
+            },
+        ],
+        label: None,
+    })
+}
+
+fn create_render_pipeline(
+    device: &Device,
+    label: &str,
+    layout: &PipelineLayout,
+    shader_module: &ShaderModule,
+    format: TextureFormat,
+    blend_mode: BlendMode,
+    mesh_layout: &MeshLayout,
+) -> RenderPipeline {
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(layout),
+        vertex: VertexState {
+            module: shader_module,
+            entry_point: "vs_main",
+            buffers: &[
+                mesh_layout.vertex.clone(),
+                UNIT_SQUARE_BUFFER_LAYOUT[1].clone(),
+            ],
         },
-        &image,
-        wgpu::ImageDataLayout {
-            offset: 0,
-            bytes_per_row: Some(4 * dimensions.0),
-            rows_per_image: Some(dimensions.1),
+        fragment: Some(FragmentState {
+            module: shader_module,
+            entry_point: "fs_main",
+            targets: &[Some(ColorTargetState {
+                format,
+                blend: blend_mode.blend_state(),
+                write_mask: ColorWrites::ALL,
+            })],
+        }),
+        primitive: PrimitiveState {
+            topology: mesh_layout.topology,
+            cull_mode: Some(Face::Back),
+            ..Default::default()
         },
-        extent,
-    );
-    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-    let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-        address_mode_u: wgpu::AddressMode::ClampToEdge,
-        address_mode_v: wgpu::AddressMode::ClampToEdge,
-        address_mode_w: wgpu::AddressMode::ClampToEdge,
-        mag_filter: wgpu::FilterMode::Nearest,
-        min_filter: wgpu::FilterMode::Nearest,
-        mipmap_filter: wgpu::FilterMode::Nearest,
-        ..Default::default()
-    });
-
-    Ok(TextureSheet {
-        sheet_info,
-        dimensions: UVec2::new(dimensions.0, dimensions.1),
-        texture,
-        sampler,
-        view,
+        depth_stencil: None,
+        multisample: MultisampleState::default(),
+        multiview: None,
     })
 }
 
+// immediate sprites for one group, in world pixels. see Context::sprites. sprites are drawn
+// on the batch's layer, ordered among instances like Instance::layer; it starts at 0.
+pub struct SpriteBatch<'a> {
+    pub group: &'a mut GeoInstances,
+    pub layer: f32,
+}
+
+impl SpriteBatch<'_> {
+    // the layer for sprites drawn after this, e.g. to put debug markers over everything.
+    pub fn layer(&mut self, layer: f32) -> &mut Self {
+        self.layer = layer;
+        self
+    }
+
+    pub fn draw(&mut self, sprite: SpriteRef, xy: Vec2, wh: Vec2, color: Vec4) -> &mut Self {
+        self.draw_transformed(sprite, ComponentTransform::world_rect(xy, wh), color)
+    }
+
+    pub fn draw_transformed(
+        &mut self,
+        sprite: SpriteRef,
+        transform: ComponentTransform,
+        color: Vec4,
+    ) -> &mut Self {
+        let tex_transform =
+            self.group
+                .sheet
+                .layer_cluster_sub_transform(sprite.layer, sprite.cluster, sprite.sub);
+        self.group.instance_buffer_manager.transient.push((
+            self.layer,
+            InstanceData {
+                transform: transform.to_instance_mat4(self.group.instance_buffer_manager.y_down),
+                tex_transform: tex_transform.to_mat4(),
+                color,
+                tex_layer: sprite.layer as u32,
+                padding: [0; 3],
+            },
+        ));
+        self
+    }
+}
+
 pub struct GeoManager {
     pub device: Arc<Mutex<Device>>,
     pub queue: Arc<Mutex<Queue>>,
     pub format: TextureFormat,
     pub instance_groups: Vec<GeoInstances>,
+    pub assets: Assets,
+    pub camera: Camera,
+    // the screen in logical pixels, which the camera's view covers at zoom 1.
+    pub extent: UVec2,
 }
 
 impl GeoManager {
@@ -160,23 +244,72 @@ impl GeoManager {
         format: TextureFormat,
     ) -> Self {
         Self {
-            device,
-            queue,
+            device: device.clone(),
+            queue: queue.clone(),
             format,
             instance_groups: vec![],
+            assets: Assets::new(device.clone(), queue.clone()),
+            camera: Camera::default(),
+            extent: UVec2::ONE,
         }
     }
 
+    // retained plus this frame's transient instances.
     pub fn num_instances(&self, group_index: usize) -> u32 {
         self.instance_groups[group_index]
             .instance_buffer_manager
-            .data
-            .len() as u32
+            .draw_count() as u32
+    }
+
+    // every group's draw runs with their group index, in group order. Context::render sorts
+    // them by layer, along with the tilemaps' runs.
+    pub fn draw_runs(&self) -> Vec<(usize, DrawRun)> {
+        self.instance_groups
+            .iter()
+            .enumerate()
+            .flat_map(|(group_index, group)| {
+                group
+                    .instance_buffer_manager
+                    .draw_runs()
+                    .into_iter()
+                    .map(move |run| (group_index, run))
+            })
+            .collect()
+    }
+
+    pub fn view_matrix(&self) -> Mat4 {
+        self.camera.view_matrix(self.extent)
+    }
+
+    // upload the view matrix where the camera has moved since. flipping the camera's y axis
+    // rewrites every instance.
+    pub fn sync_view(&mut self, queue: Arc<Mutex<Queue>>) {
+        let queue = queue.lock().unwrap();
+        let view_matrix = self.view_matrix();
+        for ig in self.instance_groups.iter_mut() {
+            let manager = &mut ig.instance_buffer_manager;
+            if manager.y_down != self.camera.y_down {
+                manager.y_down = self.camera.y_down;
+                manager.order_dirty = true;
+                for instance in manager.data.iter_mut() {
+                    instance.needs_update = true;
+                }
+            }
+            if ig.view_matrix_uniform.matrix != view_matrix {
+                ig.view_matrix_uniform.matrix = view_matrix;
+                queue.write_buffer(
+                    &ig.view_matrix_uniform.buffer,
+                    0,
+                    bytemuck::cast_slice(&[view_matrix]),
+                );
+            }
+        }
     }
 
+    // `width` and `height` are the render target's size in physical pixels.
     pub fn update_view(&mut self, queue: Arc<Mutex<Queue>>, width: u32, height: u32) {
         let queue = queue.lock().unwrap();
-        let view_matrix = Mat4::orthographic_lh(-1.0, 1.0, -1.0, 1.0, -1.0, 1.0);
+        let view_matrix = self.view_matrix();
         let screen_size = Vec2::new(width as f32, height as f32);
         for ig in self.instance_groups.iter_mut() {
             ig.view_matrix_uniform.matrix = view_matrix;
@@ -196,54 +329,58 @@ impl GeoManager {
 
     pub fn reload_shader(
         &mut self,
-        device: Arc<Mutex<wgpu::Device>>,
+        _device: Arc<Mutex<wgpu::Device>>,
         shader_path: &str,
     ) -> Result<(), Box<dyn Error>> {
-        let device = device.lock().unwrap();
+        self.reload_asset(shader_path);
+        Ok(())
+    }
 
-        // for every instance group...
-        for ig in self.instance_groups.iter_mut() {
-            // does the instance group use this shader path?
-            if ig.render_pipeline_record.shader_path == *shader_path.to_string() {
-                // if so, rebuild the shader.
-                ig.render_pipeline_record.shader_module =
-                    device.create_shader_module(ShaderModuleDescriptor {
-                        label: Some(&*format!("shader {}", shader_path)),
-                        source: ShaderSource::Wgsl(Cow::Borrowed(&*read_to_string(shader_path)?)),
-                    });
-
-                // and rebuild the render pipeline.
-                ig.render_pipeline_record.render_pipeline =
-                    device.create_render_pipeline(&RenderPipelineDescriptor {
-                        label: Some(&*format!("pipeline {}", shader_path)),
-                        layout: Some(&ig.render_pipeline_record.pipeline_layout),
-                        vertex: VertexState {
-                            module: &ig.render_pipeline_record.shader_module,
-                            entry_point: "vs_main",
-                            buffers: &UNIT_SQUARE_BUFFER_LAYOUT,
-                        },
-                        fragment: Some(FragmentState {
-                            module: &ig.render_pipeline_record.shader_module,
-                            entry_point: "fs_main",
-                            targets: &[Some(ColorTargetState {
-                                format: ig.render_pipeline_record.format,
-                                blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
-                                write_mask: ColorWrites::ALL,
-                            })],
-                        }),
-                        primitive: PrimitiveState {
-                            cull_mode: None,
-                            ..Default::default()
-                        },
-                        depth_stencil: None,
-                        multisample: MultisampleState::default(),
-                        multiview: None,
-                    });
+    // reload whatever was loaded from `path`, then point every group using it at the new version.
+    // failures are left in assets.warnings.
+    pub fn reload_asset(&mut self, path: &str) {
+        if self.assets.reload(path) {
+            self.refresh_assets();
+        }
+    }
+
+    // rebuild bind groups and pipelines of groups holding outdated texture or shader handles.
+    pub fn refresh_assets(&mut self) {
+        let sheets: Vec<Option<Handle<TextureSheet>>> = self
+            .instance_groups
+            .iter()
+            .map(|ig| self.assets.retextured(&ig.sheet))
+            .collect();
+        let device = self.device.lock().unwrap();
+        for (ig, sheet) in self.instance_groups.iter_mut().zip(sheets) {
+            if let Some(sheet) = sheet {
+                ig.bind_group = create_bind_group(
+                    &device,
+                    &ig.bind_group_layout,
+                    &ig.view_matrix_uniform,
+                    &ig.screen_size_uniform,
+                    &sheet,
+                );
+                ig.sheet = sheet;
+            }
+            if let Some(shader) = self.assets.shaders.newer(&ig.render_pipeline_record.shader) {
+                // rebuild the render pipeline, keeping its blend mode.
+                let record = &mut ig.render_pipeline_record;
+                record.render_pipeline = create_render_pipeline(
+                    &device,
+                    &format!("pipeline {}", shader.path),
+                    &record.pipeline_layout,
+                    &shader.module,
+                    record.format,
+                    record.blend_mode,
+                    &record.mesh_layout,
+                );
+                record.shader = shader;
             }
         }
-        Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn new_unit_square(
         &mut self,
         max_instances: usize,
@@ -252,32 +389,166 @@ impl GeoManager {
         height: u32,
         sheet_info: TextureSheetDefinition,
         shader_path: &str,
+        blend_mode: BlendMode,
     ) -> Result<usize, Box<dyn Error>> {
         // prepare texture sheet data
-        let sheet = load_texture(self.device.clone(), self.queue.clone(), sheet_info)?;
+        let sheet = self.assets.texture(sheet_info)?;
+        let shader = self.assets.shader(shader_path)?;
+        self.new_unit_square_with(
+            max_instances,
+            format,
+            width,
+            height,
+            sheet,
+            shader,
+            blend_mode,
+        )
+    }
 
-        let device = self.device.lock().unwrap();
+    // a unit square group drawing from a runtime packed atlas.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_unit_square_atlas(
+        &mut self,
+        max_instances: usize,
+        format: TextureFormat,
+        width: u32,
+        height: u32,
+        atlas: PackedAtlas,
+        shader_path: &str,
+        blend_mode: BlendMode,
+    ) -> Result<usize, Box<dyn Error>> {
+        let sheet = self.assets.atlas(atlas)?;
+        let shader = self.assets.shader(shader_path)?;
+        self.new_unit_square_with(
+            max_instances,
+            format,
+            width,
+            height,
+            sheet,
+            shader,
+            blend_mode,
+        )
+    }
 
-        // compile shader code
-        let shader_module = device.create_shader_module(ShaderModuleDescriptor {
-            label: Some(shader_path),
-            source: ShaderSource::Wgsl(Cow::Borrowed(&*read_to_string(shader_path)?)),
-        });
+    // a unit square group drawing from several sheets at once. its shader must sample a
+    // texture_2d_array, using the per-instance layer at @location(14).
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_unit_square_array(
+        &mut self,
+        max_instances: usize,
+        format: TextureFormat,
+        width: u32,
+        height: u32,
+        sheet_infos: Vec<TextureSheetDefinition>,
+        shader_path: &str,
+        blend_mode: BlendMode,
+    ) -> Result<usize, Box<dyn Error>> {
+        let sheet = self.assets.texture_array(sheet_infos)?;
+        let shader = self.assets.shader(shader_path)?;
+        self.new_unit_square_with(
+            max_instances,
+            format,
+            width,
+            height,
+            sheet,
+            shader,
+            blend_mode,
+        )
+    }
+
+    // a unit square group from already loaded assets, e.g. several groups sharing one sheet.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_unit_square_with(
+        &mut self,
+        max_instances: usize,
+        format: TextureFormat,
+        width: u32,
+        height: u32,
+        sheet: Handle<TextureSheet>,
+        shader: Handle<Shader>,
+        blend_mode: BlendMode,
+    ) -> Result<usize, Box<dyn Error>> {
+        self.new_mesh_group_with(
+            max_instances,
+            format,
+            width,
+            height,
+            &UNIT_SQUARE_VERTICES,
+            MeshIndices::U16(UNIT_SQUARE_INDICES.to_vec()),
+            MeshLayout::default(),
+            sheet,
+            shader,
+            blend_mode,
+        )
+    }
+
+    // an instanced group of any mesh, e.g. a circle or polygon from the mesh module.
+    // instances are transformed like unit squares, so meshes should span the same
+    // x 0..1, y 0..-1 as UNIT_SQUARE_VERTICES to line up with pixel rects. back faces are
+    // culled, so triangles must wind like the unit square's.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_mesh_group<V: Pod>(
+        &mut self,
+        max_instances: usize,
+        format: TextureFormat,
+        width: u32,
+        height: u32,
+        vertices: &[V],
+        indices: MeshIndices,
+        layout: MeshLayout,
+        sheet_info: TextureSheetDefinition,
+        shader_path: &str,
+        blend_mode: BlendMode,
+    ) -> Result<usize, Box<dyn Error>> {
+        let sheet = self.assets.texture(sheet_info)?;
+        let shader = self.assets.shader(shader_path)?;
+        self.new_mesh_group_with(
+            max_instances,
+            format,
+            width,
+            height,
+            vertices,
+            indices,
+            layout,
+            sheet,
+            shader,
+            blend_mode,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_mesh_group_with<V: Pod>(
+        &mut self,
+        max_instances: usize,
+        format: TextureFormat,
+        width: u32,
+        height: u32,
+        vertices: &[V],
+        indices: MeshIndices,
+        layout: MeshLayout,
+        sheet: Handle<TextureSheet>,
+        shader: Handle<Shader>,
+        blend_mode: BlendMode,
+    ) -> Result<usize, Box<dyn Error>> {
+        if indices.is_empty() {
+            return Err("mesh group needs at least one index".into());
+        }
+        let device = self.device.lock().unwrap();
 
         // vertex and index buffers
         let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
-            label: Some("unit square vertices"),
-            contents: bytemuck::cast_slice(&UNIT_SQUARE_VERTICES),
+            label: Some("mesh vertices"),
+            contents: bytemuck::cast_slice(vertices),
             usage: BufferUsages::VERTEX,
         });
         let index_buffer = device.create_buffer_init(&BufferInitDescriptor {
-            label: Some("unit square indices"),
-            contents: bytemuck::cast_slice(&UNIT_SQUARE_INDICES),
+            label: Some("mesh indices"),
+            contents: indices.bytes(),
             usage: BufferUsages::INDEX,
         });
 
         // view matrix uniform setup
-        let view_matrix = Mat4::orthographic_lh(-1.0, 1.0, -1.0, 1.0, -1.0, 1.0);
+        let view_matrix = self.view_matrix();
         let view_matrix_uniform = GeoUniformMatrix {
             matrix: view_matrix,
             buffer: device.create_buffer_init(&BufferInitDescriptor {
@@ -330,7 +601,11 @@ impl GeoManager {
                     visibility: wgpu::ShaderStages::FRAGMENT,
                     ty: wgpu::BindingType::Texture {
                         multisampled: false,
-                        view_dimension: wgpu::TextureViewDimension::D2,
+                        view_dimension: if sheet.texture.array {
+                            TextureViewDimension::D2Array
+                        } else {
+                            TextureViewDimension::D2
+                        },
                         sample_type: wgpu::TextureSampleType::Float { filterable: true },
                     },
                     count: None,
@@ -343,28 +618,13 @@ impl GeoManager {
                 },
             ],
         });
-        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &bind_group_layout,
-            entries: &[
-                BindGroupEntry {
-                    binding: 0,
-                    resource: view_matrix_uniform.buffer.as_entire_binding(),
-                },
-                BindGroupEntry {
-                    binding: 1,
-                    resource: screen_size_uniform.buffer.as_entire_binding(),
-                },
-                BindGroupEntry {
-                    binding: 2,
-                    resource: BindingResource::TextureView(&sheet.view),
-                },
-                BindGroupEntry {
-                    binding: 3,
-                    resource: BindingResource::Sampler(&sheet.sampler),
-                },
-            ],
-            label: None,
-        });
+        let bind_group = create_bind_group(
+            &device,
+            &bind_group_layout,
+            &view_matrix_uniform,
+            &screen_size_uniform,
+            &sheet,
+        );
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: None,
             bind_group_layouts: &[&bind_group_layout],
@@ -373,35 +633,20 @@ impl GeoManager {
 
         // render pipeline itself, with necessary components for reconstruction retained.
         let render_pipeline_record = RenderPipelineRecord {
-            render_pipeline: device.create_render_pipeline(&RenderPipelineDescriptor {
-                label: Some("unit square pipeline"),
-                layout: Some(&pipeline_layout),
-                vertex: VertexState {
-                    module: &shader_module,
-                    entry_point: "vs_main",
-                    buffers: &UNIT_SQUARE_BUFFER_LAYOUT,
-                },
-                fragment: Some(FragmentState {
-                    module: &shader_module,
-                    entry_point: "fs_main",
-                    targets: &[Some(ColorTargetState {
-                        format,
-                        blend: Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
-                        write_mask: ColorWrites::ALL,
-                    })],
-                }),
-                primitive: PrimitiveState {
-                    cull_mode: Some(Face::Back),
-                    ..Default::default()
-                },
-                depth_stencil: None,
-                multisample: MultisampleState::default(),
-                multiview: None,
-            }),
+            render_pipeline: create_render_pipeline(
+                &device,
+                "mesh pipeline",
+                &pipeline_layout,
+                &shader.module,
+                format,
+                blend_mode,
+                &layout,
+            ),
             pipeline_layout,
-            shader_module,
-            shader_path: shader_path.to_string(),
+            shader,
+            mesh_layout: layout,
             format,
+            blend_mode,
         };
 
         // drop device here because it's used to make the instance buffer below.
@@ -410,9 +655,12 @@ impl GeoManager {
         let index = self.instance_groups.len();
         self.instance_groups.push(GeoInstances {
             render_pipeline_record,
+            bind_group_layout,
             bind_group,
             vertex_buffer,
             index_buffer,
+            index_count: indices.len() as u32,
+            index_format: indices.format(),
             sheet,
             view_matrix_uniform,
             screen_size_uniform,
@@ -0,0 +1,445 @@
+use std::{borrow::Cow, collections::HashMap, mem::size_of};
+
+use etagere::{size2, BucketedAtlasAllocator};
+use glam::Mat4;
+use glyphon::{CacheKey, Color, FontSystem, SwashCache, SwashContent, TextBounds};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroup, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
+    BindingResource, BindingType, Buffer, BufferAddress, BufferBindingType, BufferDescriptor,
+    BufferSize, BufferUsages, ColorTargetState, ColorWrites, Device, Extent3d, FragmentState,
+    ImageCopyTexture, ImageDataLayout, MultisampleState, Origin3d, PrimitiveState, Queue,
+    RenderPass, RenderPipeline, RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource,
+    ShaderStages, Texture, TextureAspect, TextureDescriptor, TextureDimension, TextureFormat,
+    TextureSampleType, TextureUsages, TextureViewDescriptor, TextureViewDimension, VertexAttribute,
+    VertexBufferLayout, VertexFormat, VertexState, VertexStepMode,
+};
+
+use crate::types::BlendMode;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GlyphVertex {
+    position: [f32; 2],
+    uv: [f32; 2],
+    color: [f32; 4],
+}
+
+const GLYPH_BUFFER_LAYOUT: VertexBufferLayout = VertexBufferLayout {
+    array_stride: size_of::<GlyphVertex>() as BufferAddress,
+    step_mode: VertexStepMode::Vertex,
+    attributes: &[
+        VertexAttribute {
+            offset: 0,
+            shader_location: 0,
+            format: VertexFormat::Float32x2,
+        },
+        VertexAttribute {
+            offset: size_of::<[f32; 2]>() as BufferAddress,
+            shader_location: 1,
+            format: VertexFormat::Float32x2,
+        },
+        VertexAttribute {
+            offset: size_of::<[f32; 4]>() as BufferAddress,
+            shader_location: 2,
+            format: VertexFormat::Float32x4,
+        },
+    ],
+};
+
+// one laid out glyph to draw this frame. x and y are its origin in physical pixels, where
+// glyphon would put it: the PhysicalGlyph position, with the run's line_y added to y.
+pub struct GlyphQuad {
+    pub cache_key: CacheKey,
+    pub x: i32,
+    pub y: i32,
+    pub color: Color,
+    pub bounds: TextBounds,
+}
+
+// where a rasterized glyph sits in the atlas, and its placement relative to its origin.
+#[derive(Copy, Clone)]
+struct AtlasGlyph {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    left: i32,
+    top: i32,
+}
+
+// draws glyphs that glyphon can't move one at a time: the animated and partially revealed
+// labels of a TextCollection. glyphs are rasterized by their cache keys into an atlas of its
+// own, which doubles in size when it fills up, and is cleared and refilled at the largest
+// size the device allows.
+pub struct GlyphRenderer {
+    pipeline: RenderPipeline,
+    bind_group_layout: BindGroupLayout,
+    bind_group: BindGroup,
+    transform: Buffer,
+    texture: Texture,
+    format: TextureFormat,
+    size: u32,
+    allocator: BucketedAtlasAllocator,
+    // None for glyphs with nothing to draw, like spaces.
+    glyphs: HashMap<CacheKey, Option<AtlasGlyph>>,
+    vertex_buffer: Buffer,
+    vertex_count: u32,
+    srgb: bool,
+}
+
+impl GlyphRenderer {
+    pub fn new(device: &Device, format: TextureFormat) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(size_of::<Mat4>() as u64),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader_module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("glyphs"),
+            source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("glyphs.wgsl"))),
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("glyphs pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader_module,
+                entry_point: "vs_main",
+                buffers: &[GLYPH_BUFFER_LAYOUT],
+            },
+            fragment: Some(FragmentState {
+                module: &shader_module,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: BlendMode::Premultiplied.blend_state(),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+
+        // colors are made linear, like glyphon does, when the target decodes srgb.
+        let srgb = format.is_srgb();
+        let format = if srgb {
+            TextureFormat::Rgba8UnormSrgb
+        } else {
+            TextureFormat::Rgba8Unorm
+        };
+        let size = 512;
+        let transform = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("glyphs transform"),
+            contents: bytemuck::cast_slice(&[Mat4::IDENTITY]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let texture = atlas_texture(device, format, size);
+        let bind_group = atlas_bind_group(device, &bind_group_layout, &transform, &texture);
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            bind_group,
+            transform,
+            texture,
+            format,
+            size,
+            allocator: BucketedAtlasAllocator::new(size2(size as i32, size as i32)),
+            glyphs: HashMap::new(),
+            vertex_buffer: glyph_buffer(device, 1024),
+            vertex_count: 0,
+            srgb,
+        }
+    }
+
+    // `width` and `height` are the render size in physical pixels.
+    #[allow(clippy::too_many_arguments)]
+    pub fn prepare(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        font_system: &mut FontSystem,
+        cache: &mut SwashCache,
+        quads: &[GlyphQuad],
+        width: u32,
+        height: u32,
+    ) {
+        let screen_matrix = Mat4::orthographic_lh(0.0, width as f32, height as f32, 0.0, -1.0, 1.0);
+        queue.write_buffer(&self.transform, 0, bytemuck::cast_slice(&[screen_matrix]));
+
+        let max_size = device.limits().max_texture_dimension_2d;
+        let mut cleared_at_max = false;
+        let vertices = loop {
+            let (vertices, full) = self.vertices(queue, font_system, cache, quads, width, height);
+            // at the largest size, the atlas is emptied once for this frame's glyphs, and
+            // whatever still doesn't fit is left out.
+            if !full || cleared_at_max {
+                break vertices;
+            }
+            cleared_at_max = self.size >= max_size;
+            self.resize(device, (self.size * 2).min(max_size));
+        };
+
+        let bytes: &[u8] = bytemuck::cast_slice(&vertices);
+        if bytes.len() as u64 > self.vertex_buffer.size() {
+            self.vertex_buffer = glyph_buffer(device, bytes.len());
+        }
+        if !bytes.is_empty() {
+            queue.write_buffer(&self.vertex_buffer, 0, bytes);
+        }
+        self.vertex_count = vertices.len() as u32;
+    }
+
+    pub fn render<'pass>(&'pass self, pass: &mut RenderPass<'pass>) {
+        if self.vertex_count == 0 {
+            return;
+        }
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.draw(0..self.vertex_count, 0..1);
+    }
+
+    // two triangles per glyph, clipped to its bounds and the screen like glyphon clips. the
+    // flag is set when a glyph didn't fit in the atlas.
+    fn vertices(
+        &mut self,
+        queue: &Queue,
+        font_system: &mut FontSystem,
+        cache: &mut SwashCache,
+        quads: &[GlyphQuad],
+        width: u32,
+        height: u32,
+    ) -> (Vec<GlyphVertex>, bool) {
+        let mut vertices = Vec::with_capacity(quads.len() * 6);
+        let mut full = false;
+        for quad in quads {
+            let glyph = match self.glyph(queue, font_system, cache, quad.cache_key) {
+                Some(Some(glyph)) => glyph,
+                Some(None) => continue,
+                None => {
+                    full = true;
+                    continue;
+                }
+            };
+
+            let (mut x, mut y) = (quad.x + glyph.left, quad.y - glyph.top);
+            let (mut u, mut v) = (glyph.x as i32, glyph.y as i32);
+            let (mut w, mut h) = (glyph.width as i32, glyph.height as i32);
+            let min_x = quad.bounds.left.max(0);
+            let min_y = quad.bounds.top.max(0);
+            let max_x = quad.bounds.right.min(width as i32);
+            let max_y = quad.bounds.bottom.min(height as i32);
+            if x < min_x {
+                u += min_x - x;
+                w -= min_x - x;
+                x = min_x;
+            }
+            if y < min_y {
+                v += min_y - y;
+                h -= min_y - y;
+                y = min_y;
+            }
+            w = w.min(max_x - x);
+            h = h.min(max_y - y);
+            if w <= 0 || h <= 0 {
+                continue;
+            }
+
+            let color = [
+                self.channel(quad.color.r()),
+                self.channel(quad.color.g()),
+                self.channel(quad.color.b()),
+                quad.color.a() as f32 / 255.0,
+            ];
+            let corner = |cx: i32, cy: i32| GlyphVertex {
+                position: [(x + cx) as f32, (y + cy) as f32],
+                uv: [(u + cx) as f32, (v + cy) as f32],
+                color,
+            };
+            vertices.extend([
+                corner(0, 0),
+                corner(w, 0),
+                corner(0, h),
+                corner(w, 0),
+                corner(w, h),
+                corner(0, h),
+            ]);
+        }
+        (vertices, full)
+    }
+
+    // the atlas entry for a glyph, rasterizing and uploading it the first time it's drawn.
+    // None when the atlas has no room for it.
+    fn glyph(
+        &mut self,
+        queue: &Queue,
+        font_system: &mut FontSystem,
+        cache: &mut SwashCache,
+        cache_key: CacheKey,
+    ) -> Option<Option<AtlasGlyph>> {
+        if let Some(glyph) = self.glyphs.get(&cache_key) {
+            return Some(*glyph);
+        }
+        let image = match cache.get_image_uncached(font_system, cache_key) {
+            Some(image) if image.placement.width > 0 && image.placement.height > 0 => image,
+            _ => {
+                self.glyphs.insert(cache_key, None);
+                return Some(None);
+            }
+        };
+        let (width, height) = (image.placement.width, image.placement.height);
+        let allocation = self
+            .allocator
+            .allocate(size2(width as i32, height as i32))?;
+
+        // everything is stored as rgba, masks as white with the coverage in alpha.
+        let pixels: Vec<u8> = match image.content {
+            SwashContent::Color => image.data,
+            SwashContent::Mask => image
+                .data
+                .iter()
+                .flat_map(|a| [255, 255, 255, *a])
+                .collect(),
+            SwashContent::SubpixelMask => image
+                .data
+                .chunks_exact(3)
+                .flat_map(|rgb| [255, 255, 255, rgb[0].max(rgb[1]).max(rgb[2])])
+                .collect(),
+        };
+        let glyph = AtlasGlyph {
+            x: allocation.rectangle.min.x as u32,
+            y: allocation.rectangle.min.y as u32,
+            width,
+            height,
+            left: image.placement.left,
+            top: image.placement.top,
+        };
+        queue.write_texture(
+            ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: Origin3d {
+                    x: glyph.x,
+                    y: glyph.y,
+                    z: 0,
+                },
+                aspect: TextureAspect::All,
+            },
+            &pixels,
+            ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(width * 4),
+                rows_per_image: None,
+            },
+            Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.glyphs.insert(cache_key, Some(glyph));
+        Some(Some(glyph))
+    }
+
+    // a new, empty atlas. every glyph is uploaded again as it's next drawn.
+    fn resize(&mut self, device: &Device, size: u32) {
+        self.size = size;
+        self.texture = atlas_texture(device, self.format, size);
+        self.bind_group = atlas_bind_group(
+            device,
+            &self.bind_group_layout,
+            &self.transform,
+            &self.texture,
+        );
+        self.allocator = BucketedAtlasAllocator::new(size2(size as i32, size as i32));
+        self.glyphs.clear();
+    }
+
+    fn channel(&self, value: u8) -> f32 {
+        let value = value as f32 / 255.0;
+        if !self.srgb {
+            value
+        } else if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    }
+}
+
+fn atlas_texture(device: &Device, format: TextureFormat, size: u32) -> Texture {
+    device.create_texture(&TextureDescriptor {
+        label: Some("glyphs atlas"),
+        size: Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format,
+        usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+        view_formats: &[],
+    })
+}
+
+fn atlas_bind_group(
+    device: &Device,
+    layout: &BindGroupLayout,
+    transform: &Buffer,
+    texture: &Texture,
+) -> BindGroup {
+    let view = texture.create_view(&TextureViewDescriptor::default());
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: transform.as_entire_binding(),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: BindingResource::TextureView(&view),
+            },
+        ],
+        label: None,
+    })
+}
+
+fn glyph_buffer(device: &Device, size: usize) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some("glyphs"),
+        size: (size as u64).next_power_of_two(),
+        usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
@@ -0,0 +1,33 @@
+// glyphs of animated labels, positioned in physical pixels and moved to clip space by
+// `transform`. uv is in atlas texels.
+@group(0) @binding(0)
+var<uniform> transform: mat4x4<f32>;
+@group(0) @binding(1)
+var atlas: texture_2d<f32>;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(vin: VertexInput) -> VertexOutput {
+    var result: VertexOutput;
+    result.position = transform * vec4(vin.position, 0.0, 1.0);
+    result.uv = vin.uv;
+    result.color = vin.color;
+    return result;
+}
+
+@fragment
+fn fs_main(vout: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureLoad(atlas, vec2<i32>(vout.uv), 0) * vout.color;
+    return vec4(color.rgb * color.a, color.a);
+}
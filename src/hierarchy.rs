@@ -0,0 +1,297 @@
+use std::{collections::HashMap, error::Error};
+
+use glam::Vec2;
+
+use crate::geo::GeoManager;
+use crate::text::TextCollection;
+
+// something that can be placed in the hierarchy: an instance of a geo group, or a text label.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum NodeRef {
+    Instance { group: usize, index: usize },
+    Text(usize),
+}
+
+// a child's placement relative to its parent, in world pixels. offset is from the
+// parent's pivot (a text label's top left) to the child's, before the parent's rotation
+// and scale are applied.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct LocalTransform {
+    pub offset: Vec2,
+    pub degrees: f32,
+    pub scale: Vec2,
+}
+
+impl Default for LocalTransform {
+    fn default() -> Self {
+        Self {
+            offset: Vec2::ZERO,
+            degrees: 0.0,
+            scale: Vec2::ONE,
+        }
+    }
+}
+
+// where a node ends up on screen: its pivot position, rotation clockwise in degrees, and
+// scale relative to the size it had when attached.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct WorldTransform {
+    pub position: Vec2,
+    pub degrees: f32,
+    pub scale: Vec2,
+}
+
+impl WorldTransform {
+    pub fn then(&self, local: &LocalTransform) -> WorldTransform {
+        WorldTransform {
+            position: self.position
+                + Vec2::from_angle(self.degrees.to_radians()).rotate(local.offset * self.scale),
+            degrees: self.degrees + local.degrees,
+            scale: self.scale * local.scale,
+        }
+    }
+
+    // the local transform that places a child at `world` under this parent.
+    pub fn relative(&self, world: &WorldTransform) -> LocalTransform {
+        LocalTransform {
+            offset: Vec2::from_angle(-self.degrees.to_radians())
+                .rotate(world.position - self.position)
+                / self.scale,
+            degrees: world.degrees - self.degrees,
+            scale: world.scale / self.scale,
+        }
+    }
+}
+
+struct Link {
+    parent: NodeRef,
+    local: LocalTransform,
+    // the child's unscaled size: pixels for instances, text scale for labels.
+    size: Vec2,
+    // the parent's size when the child was attached, to scale by when the parent is a root.
+    parent_size: Vec2,
+}
+
+// parent links between instances and labels. children follow their parent's translation,
+// rotation and scale; Context::update propagates them before instances are uploaded.
+// text labels don't rotate, so they only follow position and scale.
+pub struct Hierarchy {
+    links: HashMap<NodeRef, Link>,
+}
+
+impl Hierarchy {
+    pub fn new() -> Self {
+        Self {
+            links: HashMap::new(),
+        }
+    }
+
+    pub fn parent(&self, child: NodeRef) -> Option<NodeRef> {
+        self.links.get(&child).map(|link| link.parent)
+    }
+
+    pub fn children(&self, parent: NodeRef) -> Vec<NodeRef> {
+        self.links
+            .iter()
+            .filter(|(_, link)| link.parent == parent)
+            .map(|(child, _)| *child)
+            .collect()
+    }
+
+    pub fn local(&self, child: NodeRef) -> Option<&LocalTransform> {
+        self.links.get(&child).map(|link| &link.local)
+    }
+
+    pub fn local_mut(&mut self, child: NodeRef) -> Option<&mut LocalTransform> {
+        self.links.get_mut(&child).map(|link| &mut link.local)
+    }
+
+    // link `child` under `parent`. with no local transform given, the child keeps its
+    // current placement on screen.
+    pub fn attach(
+        &mut self,
+        child: NodeRef,
+        parent: NodeRef,
+        local: Option<LocalTransform>,
+        geos: &GeoManager,
+        texts: &TextCollection,
+    ) -> Result<(), Box<dyn Error>> {
+        let (_, size) = read_node(geos, texts, child)
+            .ok_or(format!("can't attach {:?}: no such node", child))?;
+        let (_, parent_size) = read_node(geos, texts, parent)
+            .ok_or(format!("can't attach to {:?}: no such node", parent))?;
+        let mut ancestor = Some(parent);
+        while let Some(node) = ancestor {
+            if node == child {
+                return Err(format!("can't attach {:?} to its own descendant", child).into());
+            }
+            ancestor = self.parent(node);
+        }
+
+        let mut worlds = HashMap::new();
+        let child_world = self.world(child, geos, texts, &mut worlds);
+        let size = self.links.get(&child).map_or(size, |link| link.size);
+        let local = match local {
+            Some(local) => local,
+            None => {
+                let parent_world = self.world(parent, geos, texts, &mut worlds).unwrap();
+                parent_world.relative(&child_world.unwrap())
+            }
+        };
+        self.links.insert(
+            child,
+            Link {
+                parent,
+                local,
+                size,
+                parent_size,
+            },
+        );
+        Ok(())
+    }
+
+    // unlink `child`, leaving it where it was last placed.
+    pub fn detach(&mut self, child: NodeRef) {
+        self.links.remove(&child);
+    }
+
+    // move `child` under `new_parent`, keeping its current placement on screen.
+    pub fn reparent(
+        &mut self,
+        child: NodeRef,
+        new_parent: NodeRef,
+        geos: &GeoManager,
+        texts: &TextCollection,
+    ) -> Result<(), Box<dyn Error>> {
+        self.attach(child, new_parent, None, geos, texts)
+    }
+
+    // place every linked node from its parent's world transform.
+    pub fn propagate(&self, geos: &mut GeoManager, texts: &mut TextCollection) {
+        let mut worlds = HashMap::new();
+        for child in self.links.keys() {
+            self.world(*child, geos, texts, &mut worlds);
+        }
+        for (child, link) in self.links.iter() {
+            if let Some(world) = worlds.get(child) {
+                write_node(geos, texts, *child, world, link.size);
+            }
+        }
+    }
+
+    fn world(
+        &self,
+        node: NodeRef,
+        geos: &GeoManager,
+        texts: &TextCollection,
+        worlds: &mut HashMap<NodeRef, WorldTransform>,
+    ) -> Option<WorldTransform> {
+        if let Some(world) = worlds.get(&node) {
+            return Some(*world);
+        }
+        let world = match self.links.get(&node) {
+            Some(link) => {
+                let mut parent = self.world(link.parent, geos, texts, worlds)?;
+                // roots are resized by hand rather than by the hierarchy, so their scale is
+                // how far they've been resized since the child was attached.
+                if !self.links.contains_key(&link.parent) {
+                    let (_, size) = read_node(geos, texts, link.parent)?;
+                    parent.scale = Vec2::select(
+                        link.parent_size.cmpne(Vec2::ZERO),
+                        size / link.parent_size,
+                        Vec2::ONE,
+                    );
+                }
+                parent.then(&link.local)
+            }
+            None => read_node(geos, texts, node)?.0,
+        };
+        worlds.insert(node, world);
+        Some(world)
+    }
+}
+
+impl Default for Hierarchy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// a node's placement as it stands, with scale 1, and its size.
+fn read_node(
+    geos: &GeoManager,
+    texts: &TextCollection,
+    node: NodeRef,
+) -> Option<(WorldTransform, Vec2)> {
+    match node {
+        NodeRef::Instance { group, index } => {
+            let instance = geos
+                .instance_groups
+                .get(group)?
+                .instance_buffer_manager
+                .data
+                .get(index)?;
+            let wh = instance.size();
+            let world = WorldTransform {
+                position: instance.position() + instance.transform.pivot_offset(geos.camera.y_down),
+                degrees: instance.transform.degrees,
+                scale: Vec2::ONE,
+            };
+            Some((world, wh))
+        }
+        NodeRef::Text(index) => {
+            let label = texts.texts.get(index)?;
+            let screen = Vec2::new(label.left as f32, label.top as f32);
+            let world = WorldTransform {
+                position: geos.camera.screen_to_world(screen, geos.extent),
+                degrees: 0.0,
+                scale: Vec2::ONE,
+            };
+            // labels keep their size on screen, so undo the camera's zoom.
+            let size = label.scale as f32 / geos.camera.zoom;
+            Some((world, Vec2::splat(size)))
+        }
+    }
+}
+
+fn write_node(
+    geos: &mut GeoManager,
+    texts: &mut TextCollection,
+    node: NodeRef,
+    world: &WorldTransform,
+    size: Vec2,
+) {
+    match node {
+        NodeRef::Instance { group, index } => {
+            let y_down = geos.camera.y_down;
+            let Some(instance) = geos
+                .instance_groups
+                .get_mut(group)
+                .and_then(|group| group.instance_buffer_manager.data.get_mut(index))
+            else {
+                return;
+            };
+            let wh = size * world.scale;
+            let mut placed = instance.transform;
+            placed.scale = wh.extend(placed.scale.z);
+            let xy = world.position - placed.pivot_offset(y_down);
+            // only touch instances that moved, so sorted groups aren't rewritten every frame.
+            if xy != instance.position()
+                || wh != instance.size()
+                || world.degrees != instance.transform.degrees
+            {
+                instance.set_position_px(xy);
+                instance.set_size_px(wh);
+                instance.set_rotation(world.degrees);
+            }
+        }
+        NodeRef::Text(index) => {
+            if let Some(label) = texts.texts.get_mut(index) {
+                let screen = geos.camera.world_to_screen(world.position, geos.extent);
+                label.left = screen.x as f64;
+                label.top = screen.y as f64;
+                label.scale = (size.x * world.scale.x * geos.camera.zoom) as f64;
+            }
+        }
+    }
+}
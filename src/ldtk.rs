@@ -0,0 +1,722 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    sync::{Arc, Mutex},
+};
+
+use glam::{IVec2, UVec2, Vec2, Vec4};
+use serde_json::Value;
+use wgpu::Queue;
+
+use crate::geo::GeoInstances;
+use crate::tiled::{json_array, json_i32, json_u32, parse_color, relative_to};
+use crate::tilemap::{Cell, TileDef, TileFlags, TileId, Tilemap};
+use crate::types::{
+    ComponentTransform, SamplerOptions, SpriteRef, TextureSheetClusterDefinition,
+    TextureSheetDefinition,
+};
+use crate::vfs::Vfs;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum FieldValue {
+    Null,
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    String(String),
+    Color(Vec4),
+    // a grid cell in the level.
+    Point(IVec2),
+    Enum(String),
+    FilePath(String),
+    EntityRef {
+        entity_iid: String,
+        level_iid: String,
+    },
+    Tile(SpriteRef),
+    Array(Vec<FieldValue>),
+}
+
+pub type Fields = HashMap<String, FieldValue>;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum WorldLayout {
+    Free,
+    GridVania,
+    LinearHorizontal,
+    LinearVertical,
+}
+
+// an entity to create in the world, in world pixels. position is the top left of its
+// bounds, already moved off its pivot. entities drawn with a tile get a sprite in the
+// project's sheets.
+#[derive(Clone, PartialEq, Debug)]
+pub struct EntitySpawn {
+    pub identifier: String,
+    pub iid: String,
+    pub layer: String,
+    pub position: Vec2,
+    pub size: Vec2,
+    pub pivot: Vec2,
+    pub tags: Vec<String>,
+    pub sprite: Option<SpriteRef>,
+    pub fields: Fields,
+}
+
+impl EntitySpawn {
+    pub fn transform(&self) -> ComponentTransform {
+        ComponentTransform::world_rect(self.position, self.size)
+    }
+
+    // add the entity's sprite to the project's group, returning the instance index.
+    pub fn spawn(&self, group: &mut GeoInstances, queue: Arc<Mutex<Queue>>) -> Option<usize> {
+        let sprite = self.sprite?;
+        Some(group.add_new_from_layer(
+            queue,
+            self.transform(),
+            sprite.layer,
+            sprite.cluster,
+            sprite.sub,
+            Vec4::ONE,
+        ))
+    }
+}
+
+// an IntGrid layer's values, row by row. 0 is an empty cell, as is any cell the values
+// don't reach.
+pub struct IntGrid {
+    pub name: String,
+    pub size: UVec2,
+    pub grid_size: f32,
+    pub position: Vec2,
+    pub values: Vec<i32>,
+    // the identifiers given to values in the layer's definition.
+    pub names: HashMap<i32, String>,
+}
+
+impl IntGrid {
+    pub fn value(&self, xy: UVec2) -> i32 {
+        if xy.x >= self.size.x || xy.y >= self.size.y {
+            return 0;
+        }
+        let index = xy.y as usize * self.size.x as usize + xy.x as usize;
+        self.values.get(index).copied().unwrap_or(0)
+    }
+
+    pub fn value_at(&self, world: Vec2) -> i32 {
+        let xy = ((world - self.position) / self.grid_size).floor();
+        if xy.cmplt(Vec2::ZERO).any() {
+            return 0;
+        }
+        self.value(xy.as_uvec2())
+    }
+}
+
+pub struct LdtkLevel {
+    pub identifier: String,
+    pub iid: String,
+    // the level's top left and size in world pixels.
+    pub position: Vec2,
+    pub size: Vec2,
+    pub depth: i32,
+    pub background: Option<Vec4>,
+    pub fields: Fields,
+    pub tilemap: Tilemap,
+    pub int_grids: Vec<IntGrid>,
+    pub entities: Vec<EntitySpawn>,
+}
+
+impl LdtkLevel {
+    pub fn int_grid(&self, name: &str) -> Option<&IntGrid> {
+        self.int_grids.iter().find(|grid| grid.name == name)
+    }
+
+    pub fn entities_named<'a>(
+        &'a self,
+        identifier: &'a str,
+    ) -> impl Iterator<Item = &'a EntitySpawn> {
+        self.entities
+            .iter()
+            .filter(move |entity| entity.identifier == identifier)
+    }
+
+    // add a hidden tilemap layer whose cells are TileFlags::SOLID where the IntGrid has one
+    // of `values`, so Tilemap::is_solid sees them. the grid must match the tile size.
+    pub fn add_solid_layer(
+        &mut self,
+        int_grid: &str,
+        values: &[i32],
+    ) -> Result<usize, Box<dyn Error>> {
+        let grid = self
+            .int_grids
+            .iter()
+            .find(|grid| grid.name == int_grid)
+            .ok_or(format!(
+                "level {} has no IntGrid {}",
+                self.identifier, int_grid
+            ))?;
+        if Vec2::splat(grid.grid_size) != self.tilemap.tile_size {
+            return Err(format!(
+                "IntGrid {} in level {} is on a {}px grid, not {}px",
+                int_grid, self.identifier, grid.grid_size, self.tilemap.tile_size.x
+            )
+            .into());
+        }
+        if grid.position != self.tilemap.position {
+            return Err(format!(
+                "IntGrid {} in level {} is offset from the level's tiles",
+                int_grid, self.identifier
+            )
+            .into());
+        }
+        let layer = self.tilemap.add_layer(&format!("{}:solid", int_grid));
+        self.tilemap.layers[layer].visible = false;
+        for y in 0..grid.size.y {
+            for x in 0..grid.size.x {
+                let xy = UVec2::new(x, y);
+                if values.contains(&grid.value(xy)) {
+                    self.tilemap.set_cell(
+                        layer,
+                        xy,
+                        Cell {
+                            id: 0,
+                            flags: TileFlags::SOLID,
+                        },
+                    );
+                }
+            }
+        }
+        Ok(layer)
+    }
+}
+
+// where a tileset's tiles ended up: its sheet layer in LdtkProject::sheets, and its
+// tiles from `first_tile` on in every level's tilemap.
+pub struct LdtkTileset {
+    pub uid: i64,
+    pub identifier: String,
+    pub sheet_layer: usize,
+    pub first_tile: TileId,
+    pub grid_size: u32,
+    columns: u32,
+    rows: u32,
+    spacing: u32,
+    padding: u32,
+}
+
+// an LDtk project (.ldtk, with its external .ldtkl levels) as one tilemap and entity list
+// per level. Tiles layers, auto-layers and IntGrid layers' auto tiles become tilemap
+// layers, bottom first; stacked tiles spill into extra layers of the same name. IntGrid
+// values are kept in `int_grids`.
+//
+// each tileset is one sheet in `sheets`. create a group from them with
+// GeoManager::new_unit_square_array and hand it to the levels:
+//
+//     let mut project = context.geos.assets.ldtk("maps/world.ldtk")?;
+//     project.set_group(context.geos.new_unit_square_array(.., project.sheets.clone(), ..)?);
+//
+// levels are placed by the world layout, in world pixels with y down. tiles are drawn at
+// each level's grid size; layers on a different grid are skipped.
+pub struct LdtkProject {
+    pub sheets: Vec<TextureSheetDefinition>,
+    pub tilesets: Vec<LdtkTileset>,
+    pub tiles: Vec<TileDef>,
+    pub tile_tags: HashMap<TileId, Vec<String>>,
+    pub tile_data: HashMap<TileId, String>,
+    pub levels: Vec<LdtkLevel>,
+    pub world_layout: WorldLayout,
+    // what was skipped or ignored while loading.
+    pub warnings: Vec<String>,
+    // cluster per sheet layer and rect, for entity and field tiles.
+    rects: HashMap<(usize, IVec2, UVec2), usize>,
+}
+
+struct Loader<'a> {
+    path: &'a str,
+    default_grid: u32,
+    // IntGrid value identifiers per layer definition uid.
+    int_grid_names: HashMap<i64, HashMap<i32, String>>,
+    project: LdtkProject,
+}
+
+impl LdtkProject {
+    pub fn load(path: &str, vfs: &Vfs) -> Result<Self, Box<dyn Error>> {
+        Self::from_json(path, &vfs.read_to_string(path)?, vfs)
+    }
+
+    pub fn from_json(path: &str, source: &str, vfs: &Vfs) -> Result<Self, Box<dyn Error>> {
+        let root: Value = serde_json::from_str(source).map_err(|e| format!("{}: {}", path, e))?;
+        if !root["jsonVersion"].is_string() {
+            return Err(format!("{}: not an LDtk project", path).into());
+        }
+        let world = json_array(&root, "worlds").first().unwrap_or(&root);
+        let world_layout = match world["worldLayout"].as_str().unwrap_or_default() {
+            "GridVania" => WorldLayout::GridVania,
+            "LinearHorizontal" => WorldLayout::LinearHorizontal,
+            "LinearVertical" => WorldLayout::LinearVertical,
+            _ => WorldLayout::Free,
+        };
+        let mut loader = Loader {
+            path,
+            default_grid: json_u32(&root, "defaultGridSize").max(1),
+            int_grid_names: HashMap::new(),
+            project: LdtkProject {
+                sheets: vec![],
+                tilesets: vec![],
+                tiles: vec![],
+                tile_tags: HashMap::new(),
+                tile_data: HashMap::new(),
+                levels: vec![],
+                world_layout,
+                warnings: vec![],
+                rects: HashMap::new(),
+            },
+        };
+        if json_array(&root, "worlds").len() > 1 {
+            loader.warn(
+                "multi-world projects are unsupported, only the first world is read".to_string(),
+            );
+        }
+        let defs = &root["defs"];
+        for tileset in json_array(defs, "tilesets") {
+            loader.add_tileset(tileset);
+        }
+        for layer in json_array(defs, "layers") {
+            let names = json_array(layer, "intGridValues")
+                .iter()
+                .map(|value| {
+                    (
+                        json_i32(value, "value"),
+                        value["identifier"].as_str().unwrap_or_default().to_string(),
+                    )
+                })
+                .collect();
+            loader
+                .int_grid_names
+                .insert(layer["uid"].as_i64().unwrap_or(-1), names);
+        }
+
+        let mut next = Vec2::ZERO;
+        for level in json_array(world, "levels") {
+            let external;
+            let level = match level["externalRelPath"].as_str() {
+                Some(file) if level["layerInstances"].is_null() => {
+                    let level_path = relative_to(path, file);
+                    external = serde_json::from_str::<Value>(&vfs.read_to_string(&level_path)?)
+                        .map_err(|e| format!("{}: {}", level_path, e))?;
+                    &external
+                }
+                _ => level,
+            };
+            let mut level = loader.level(level)?;
+            // linear layouts leave levels unplaced, one after another.
+            match world_layout {
+                WorldLayout::LinearHorizontal => {
+                    level.position = next;
+                    next.x += level.size.x;
+                }
+                WorldLayout::LinearVertical => {
+                    level.position = next;
+                    next.y += level.size.y;
+                }
+                _ => (),
+            }
+            let moved = level.position - level.tilemap.position;
+            if moved != Vec2::ZERO {
+                level.tilemap.position += moved;
+                for grid in level.int_grids.iter_mut() {
+                    grid.position += moved;
+                }
+                for entity in level.entities.iter_mut() {
+                    entity.position += moved;
+                }
+            }
+            loader.project.levels.push(level);
+        }
+        Ok(loader.project)
+    }
+
+    pub fn level(&self, identifier: &str) -> Option<&LdtkLevel> {
+        self.levels
+            .iter()
+            .find(|level| level.identifier == identifier)
+    }
+
+    // draw every level's tiles with this group, made from `sheets`.
+    pub fn set_group(&mut self, group: usize) {
+        for level in self.levels.iter_mut() {
+            level.tilemap.group = group;
+        }
+    }
+
+    // the level whose bounds hold a world position.
+    pub fn level_at(&self, world: Vec2) -> Option<&LdtkLevel> {
+        self.levels.iter().find(|level| {
+            world.cmpge(level.position).all() && world.cmplt(level.position + level.size).all()
+        })
+    }
+}
+
+impl Loader<'_> {
+    fn warn(&mut self, warning: String) {
+        self.project
+            .warnings
+            .push(format!("{}: {}", self.path, warning));
+    }
+
+    fn add_tileset(&mut self, tileset: &Value) {
+        let identifier = tileset["identifier"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        let Some(rel_path) = tileset["relPath"].as_str() else {
+            self.warn(format!("tileset {} has no image, skipping it", identifier));
+            return;
+        };
+        let grid_size = json_u32(tileset, "tileGridSize").max(1);
+        let spacing = json_u32(tileset, "spacing");
+        let padding = json_u32(tileset, "padding");
+        let size = UVec2::new(json_u32(tileset, "pxWid"), json_u32(tileset, "pxHei"));
+        let step = grid_size + spacing;
+        let cells = (size.saturating_sub(UVec2::splat(2 * padding)) + spacing) / step;
+        let sheet_layer = self.project.sheets.len();
+        self.project.sheets.push(TextureSheetDefinition {
+            path: relative_to(self.path, rel_path),
+            clusters: vec![TextureSheetClusterDefinition {
+                label: identifier.clone(),
+                offset: UVec2::splat(padding),
+                cluster_size: cells * step,
+                sub_size: UVec2::splat(grid_size),
+                spacing: UVec2::splat(spacing),
+            }],
+            sampler: SamplerOptions::default(),
+        });
+        let first_tile = self.project.tiles.len() as TileId + 1;
+        for sub in 0..(cells.x * cells.y) as usize {
+            self.project
+                .tiles
+                .push(TileDef::new(SpriteRef::on_layer(sheet_layer, 0, sub)));
+        }
+        for tag in json_array(tileset, "enumTags") {
+            let value = tag["enumValueId"].as_str().unwrap_or_default();
+            for tile in json_array(tag, "tileIds") {
+                let id = first_tile + tile.as_u64().unwrap_or(0) as TileId;
+                self.project
+                    .tile_tags
+                    .entry(id)
+                    .or_default()
+                    .push(value.to_string());
+            }
+        }
+        for data in json_array(tileset, "customData") {
+            let id = first_tile + json_u32(data, "tileId");
+            let text = data["data"].as_str().unwrap_or_default().to_string();
+            self.project.tile_data.insert(id, text);
+        }
+        self.project.tilesets.push(LdtkTileset {
+            uid: tileset["uid"].as_i64().unwrap_or(-1),
+            identifier,
+            sheet_layer,
+            first_tile,
+            grid_size,
+            columns: cells.x,
+            rows: cells.y,
+            spacing,
+            padding,
+        });
+    }
+
+    fn tileset(&self, uid: &Value) -> Option<&LdtkTileset> {
+        let uid = uid.as_i64()?;
+        self.project
+            .tilesets
+            .iter()
+            .find(|tileset| tileset.uid == uid)
+    }
+
+    fn level(&mut self, level: &Value) -> Result<LdtkLevel, Box<dyn Error>> {
+        let identifier = level["identifier"].as_str().unwrap_or_default().to_string();
+        let position = Vec2::new(
+            json_i32(level, "worldX") as f32,
+            json_i32(level, "worldY") as f32,
+        );
+        let size = Vec2::new(
+            json_u32(level, "pxWid") as f32,
+            json_u32(level, "pxHei") as f32,
+        );
+        // layers are listed top first.
+        let layers: Vec<&Value> = json_array(level, "layerInstances").iter().rev().collect();
+        let grid_size = layers
+            .iter()
+            .find(|layer| layer["__type"].as_str() != Some("Entities"))
+            .map_or(self.default_grid, |layer| json_u32(layer, "__gridSize"));
+        let tile_size = Vec2::splat(grid_size.max(1) as f32);
+        let mut tilemap = Tilemap::new(0, (size / tile_size).ceil().as_uvec2(), tile_size);
+        tilemap.position = position;
+        tilemap.tiles = self.project.tiles.clone();
+
+        let mut int_grids = vec![];
+        let mut entities = vec![];
+        for layer in layers {
+            let name = layer["__identifier"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string();
+            let offset = Vec2::new(
+                json_i32(layer, "__pxTotalOffsetX") as f32,
+                json_i32(layer, "__pxTotalOffsetY") as f32,
+            );
+            let visible = layer["visible"].as_bool().unwrap_or(true);
+            let opacity = layer["__opacity"].as_f64().unwrap_or(1.0) as f32;
+            let layer_grid = json_u32(layer, "__gridSize");
+            let cells = UVec2::new(json_u32(layer, "__cWid"), json_u32(layer, "__cHei"));
+            match layer["__type"].as_str().unwrap_or_default() {
+                "Entities" => {
+                    for entity in json_array(layer, "entityInstances") {
+                        entities.push(self.entity(entity, &name, position + offset));
+                    }
+                    continue;
+                }
+                "IntGrid" => {
+                    let values: Vec<i32> = json_array(layer, "intGridCsv")
+                        .iter()
+                        .map(|value| value.as_i64().unwrap_or(0) as i32)
+                        .collect();
+                    let cell_count = cells.x as usize * cells.y as usize;
+                    if values.len() != cell_count {
+                        self.warn(format!(
+                            "IntGrid {} in level {} has {} values for {} cells",
+                            name,
+                            identifier,
+                            values.len(),
+                            cell_count
+                        ));
+                    }
+                    int_grids.push(IntGrid {
+                        name: name.clone(),
+                        size: cells,
+                        grid_size: layer_grid as f32,
+                        position: position + offset,
+                        values,
+                        names: self
+                            .int_grid_names
+                            .get(&layer["layerDefUid"].as_i64().unwrap_or(-1))
+                            .cloned()
+                            .unwrap_or_default(),
+                    });
+                }
+                _ => (),
+            }
+
+            let tiles: Vec<&Value> = json_array(layer, "gridTiles")
+                .iter()
+                .chain(json_array(layer, "autoLayerTiles"))
+                .collect();
+            if tiles.is_empty() {
+                continue;
+            }
+            if layer_grid as f32 != tile_size.x {
+                self.warn(format!(
+                    "layer {} in level {} is on a {}px grid, not {}px, skipping it",
+                    name, identifier, layer_grid, tile_size.x
+                ));
+                continue;
+            }
+            if offset != Vec2::ZERO {
+                self.warn(format!(
+                    "layer {} in level {} is offset, which is ignored",
+                    name, identifier
+                ));
+            }
+            let Some(tileset) = self.tileset(&layer["__tilesetDefUid"]) else {
+                continue;
+            };
+            // stacked tiles go to the first of the layer's tilemap layers with room.
+            let mut stack: Vec<usize> = vec![];
+            let mut outside = 0;
+            for tile in tiles {
+                let px = json_pair(&tile["px"]);
+                let src = json_pair(&tile["src"]) - tileset.padding as i32;
+                if px.cmplt(IVec2::ZERO).any() {
+                    continue;
+                }
+                // a source rect off the tileset's grid has no tile.
+                if src.cmplt(IVec2::ZERO).any() {
+                    outside += 1;
+                    continue;
+                }
+                let xy = (px / layer_grid as i32).as_uvec2();
+                let sub = src.as_uvec2() / (tileset.grid_size + tileset.spacing);
+                if sub.x >= tileset.columns || sub.y >= tileset.rows {
+                    outside += 1;
+                    continue;
+                }
+                let flip = json_u32(tile, "f");
+                let mut flags = TileFlags::NONE;
+                if flip & 1 != 0 {
+                    flags.insert(TileFlags::FLIP_X);
+                }
+                if flip & 2 != 0 {
+                    flags.insert(TileFlags::FLIP_Y);
+                }
+                let cell = Cell {
+                    id: tileset.first_tile + sub.y * tileset.columns + sub.x,
+                    flags,
+                };
+                let free = stack
+                    .iter()
+                    .copied()
+                    .find(|layer| tilemap.tile(*layer, xy) == 0);
+                let target = match free {
+                    Some(target) => target,
+                    None => {
+                        let target = tilemap.add_layer(&name);
+                        tilemap.layers[target].visible = visible;
+                        // instance colors multiply premultiplied texels, so fade all four.
+                        tilemap.layers[target].color = Vec4::splat(opacity);
+                        stack.push(target);
+                        target
+                    }
+                };
+                tilemap.set_cell(target, xy, cell);
+            }
+            if outside > 0 {
+                self.warn(format!(
+                    "layer {} in level {} has {} tiles outside its tileset, skipping them",
+                    name, identifier, outside
+                ));
+            }
+        }
+
+        let fields = json_array(level, "fieldInstances")
+            .iter()
+            .map(|field| self.field(field))
+            .collect();
+        Ok(LdtkLevel {
+            identifier,
+            iid: level["iid"].as_str().unwrap_or_default().to_string(),
+            position,
+            size,
+            depth: json_i32(level, "worldDepth"),
+            background: level["__bgColor"].as_str().and_then(parse_color),
+            fields,
+            tilemap,
+            int_grids,
+            entities,
+        })
+    }
+
+    fn entity(&mut self, entity: &Value, layer: &str, origin: Vec2) -> EntitySpawn {
+        let size = Vec2::new(
+            json_u32(entity, "width") as f32,
+            json_u32(entity, "height") as f32,
+        );
+        let pivot = json_pair_f32(&entity["__pivot"]);
+        let px = json_pair(&entity["px"]).as_vec2();
+        EntitySpawn {
+            identifier: entity["__identifier"]
+                .as_str()
+                .unwrap_or_default()
+                .to_string(),
+            iid: entity["iid"].as_str().unwrap_or_default().to_string(),
+            layer: layer.to_string(),
+            position: origin + px - pivot * size,
+            size,
+            pivot,
+            tags: json_array(entity, "__tags")
+                .iter()
+                .filter_map(|tag| Some(tag.as_str()?.to_string()))
+                .collect(),
+            sprite: self.tile_rect(&entity["__tile"]),
+            fields: json_array(entity, "fieldInstances")
+                .iter()
+                .map(|field| self.field(field))
+                .collect(),
+        }
+    }
+
+    fn field(&mut self, field: &Value) -> (String, FieldValue) {
+        let name = field["__identifier"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+        let kind = field["__type"].as_str().unwrap_or_default();
+        (name, self.field_value(kind, &field["__value"]))
+    }
+
+    fn field_value(&mut self, kind: &str, value: &Value) -> FieldValue {
+        if let Some(inner) = kind
+            .strip_prefix("Array<")
+            .and_then(|k| k.strip_suffix('>'))
+        {
+            return FieldValue::Array(
+                value
+                    .as_array()
+                    .map(|values| {
+                        values
+                            .iter()
+                            .map(|value| self.field_value(inner, value))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            );
+        }
+        let text = || value.as_str().unwrap_or_default().to_string();
+        match kind {
+            _ if value.is_null() => FieldValue::Null,
+            "Int" => FieldValue::Int(value.as_i64().unwrap_or(0)),
+            "Float" => FieldValue::Float(value.as_f64().unwrap_or(0.0)),
+            "Bool" => FieldValue::Bool(value.as_bool().unwrap_or(false)),
+            "Color" => {
+                FieldValue::Color(value.as_str().and_then(parse_color).unwrap_or(Vec4::ZERO))
+            }
+            "Point" => FieldValue::Point(IVec2::new(json_i32(value, "cx"), json_i32(value, "cy"))),
+            "FilePath" => FieldValue::FilePath(text()),
+            "EntityRef" => FieldValue::EntityRef {
+                entity_iid: value["entityIid"].as_str().unwrap_or_default().to_string(),
+                level_iid: value["levelIid"].as_str().unwrap_or_default().to_string(),
+            },
+            "Tile" => self
+                .tile_rect(value)
+                .map_or(FieldValue::Null, FieldValue::Tile),
+            _ if kind.starts_with("LocalEnum.") || kind.starts_with("ExternEnum.") => {
+                FieldValue::Enum(text())
+            }
+            _ => FieldValue::String(text()),
+        }
+    }
+
+    // a sprite for a tileset rect, which may cover several tiles. each distinct rect gets
+    // a one-sub cluster in its tileset's sheet.
+    fn tile_rect(&mut self, rect: &Value) -> Option<SpriteRef> {
+        let layer = self.tileset(&rect["tilesetUid"])?.sheet_layer;
+        let xy = IVec2::new(json_i32(rect, "x"), json_i32(rect, "y"));
+        let wh = UVec2::new(json_u32(rect, "w"), json_u32(rect, "h"));
+        let clusters = &mut self.project.sheets[layer].clusters;
+        let cluster = *self
+            .project
+            .rects
+            .entry((layer, xy, wh))
+            .or_insert_with(|| {
+                clusters.push(TextureSheetClusterDefinition {
+                    label: format!("rect {} {} {} {}", xy.x, xy.y, wh.x, wh.y),
+                    offset: xy.as_uvec2(),
+                    cluster_size: wh,
+                    sub_size: wh,
+                    spacing: UVec2::ZERO,
+                });
+                clusters.len() - 1
+            });
+        Some(SpriteRef::on_layer(layer, cluster, 0))
+    }
+}
+
+fn json_pair(value: &Value) -> IVec2 {
+    let at = |i: usize| value[i].as_i64().unwrap_or(0) as i32;
+    IVec2::new(at(0), at(1))
+}
+
+fn json_pair_f32(value: &Value) -> Vec2 {
+    let at = |i: usize| value[i].as_f64().unwrap_or(0.0) as f32;
+    Vec2::new(at(0), at(1))
+}
@@ -2,7 +2,23 @@ pub use glam::{Mat4, Vec2, Vec3, Vec4};
 pub use wgpu::hal::Rect as HalRect;
 pub use winit::event::{Event, WindowEvent};
 
+pub mod assets;
+pub mod atlas;
+pub mod autotile;
+pub mod camera;
+pub mod font;
 pub mod geo;
-mod text;
+pub mod glyphs;
+pub mod hierarchy;
+pub mod ldtk;
+pub mod mesh;
+pub mod scaling;
+pub mod shapes;
+pub mod text;
+pub mod text_input;
+pub mod tiled;
+pub mod tilemap;
+pub mod tween;
 pub mod types;
+pub mod vfs;
 pub mod window;
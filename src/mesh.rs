@@ -0,0 +1,97 @@
+use std::f32::consts::TAU;
+
+use glam::{Vec2, Vec3};
+
+use crate::types::{MeshIndices, Vertex};
+
+// meshes for GeoManager::new_mesh_group. points are given like pixel rects: x right and
+// y down, 0..1 across the instance's rect. texture coordinates follow the same space.
+// triangles wind counter-clockwise on screen, like the unit square, since back faces are culled.
+
+fn vertex(point: Vec2) -> Vertex {
+    Vertex {
+        location: Vec3::new(point.x, -point.y, 0.0),
+        tex_coords: point,
+    }
+}
+
+// a circle filling the unit rect, as a fan of `segments` triangles.
+pub fn circle(segments: u16) -> (Vec<Vertex>, MeshIndices) {
+    let segments = segments.max(3);
+    let mut vertices = vec![vertex(Vec2::splat(0.5))];
+    for i in 0..segments {
+        let angle = i as f32 / segments as f32 * TAU;
+        vertices.push(vertex(
+            Vec2::splat(0.5) + Vec2::new(angle.cos(), angle.sin()) * 0.5,
+        ));
+    }
+    let mut indices = vec![];
+    for i in 0..segments {
+        indices.extend_from_slice(&[0, 1 + (i + 1) % segments, 1 + i]);
+    }
+    (vertices, MeshIndices::U16(indices))
+}
+
+// a convex polygon through `points`, in order either way round, as a triangle fan.
+pub fn polygon(points: &[Vec2]) -> (Vec<Vertex>, MeshIndices) {
+    let vertices: Vec<Vertex> = points.iter().map(|p| vertex(*p)).collect();
+    // twice the signed area, positive when the points run clockwise on screen.
+    let area: f32 = points
+        .iter()
+        .zip(points.iter().cycle().skip(1))
+        .map(|(a, b)| a.perp_dot(*b))
+        .sum();
+    let mut indices = vec![];
+    for i in 1..points.len().saturating_sub(1) as u32 {
+        match area > 0.0 {
+            true => indices.extend_from_slice(&[0, i + 1, i]),
+            false => indices.extend_from_slice(&[0, i, i + 1]),
+        }
+    }
+    let indices = MeshIndices::fitting(indices, vertices.len());
+    (vertices, indices)
+}
+
+// one pixel wide lines through `points`. draw with MeshLayout::line_strip().
+pub fn line_strip(points: &[Vec2]) -> (Vec<Vertex>, MeshIndices) {
+    let vertices: Vec<Vertex> = points.iter().map(|p| vertex(*p)).collect();
+    let indices = MeshIndices::fitting((0..points.len() as u32).collect(), vertices.len());
+    (vertices, indices)
+}
+
+// a 3x3 grid of quads. `border` is the edge size within the rect and `border_uv` the edge size
+// within the texture, so corners keep their look when the rect is stretched. since every
+// instance shares the mesh, `border` is for one rect size: border pixels / rect pixels.
+pub fn nine_slice(border: Vec2, border_uv: Vec2) -> (Vec<Vertex>, MeshIndices) {
+    let xs = [0.0, border.x, 1.0 - border.x, 1.0];
+    let ys = [0.0, border.y, 1.0 - border.y, 1.0];
+    let us = [0.0, border_uv.x, 1.0 - border_uv.x, 1.0];
+    let vs = [0.0, border_uv.y, 1.0 - border_uv.y, 1.0];
+    let mut vertices = vec![];
+    for row in 0..4 {
+        for col in 0..4 {
+            vertices.push(Vertex {
+                location: Vec3::new(xs[col], -ys[row], 0.0),
+                tex_coords: Vec2::new(us[col], vs[row]),
+            });
+        }
+    }
+    let mut indices = vec![];
+    for row in 0..3 {
+        for col in 0..3 {
+            let top_left = row * 4 + col;
+            let (top_right, bottom_left) = (top_left + 1, top_left + 4);
+            let bottom_right = bottom_left + 1;
+            // same winding as the unit square.
+            indices.extend_from_slice(&[
+                top_left,
+                bottom_right,
+                top_right,
+                bottom_right,
+                top_left,
+                bottom_left,
+            ]);
+        }
+    }
+    (vertices, MeshIndices::U16(indices))
+}
@@ -0,0 +1,237 @@
+use std::{
+    borrow::Cow,
+    mem::size_of,
+    sync::{Arc, Mutex},
+};
+
+use glam::{UVec2, Vec2, Vec4};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroup, BindGroupEntry, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource,
+    BindingType, Buffer, BufferBindingType, BufferSize, BufferUsages, ColorTargetState,
+    ColorWrites, CommandEncoder, Device, Extent3d, FragmentState, LoadOp, MultisampleState,
+    Operations, PrimitiveState, PrimitiveTopology, Queue, RenderPassColorAttachment,
+    RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, ShaderModuleDescriptor,
+    ShaderSource, ShaderStages, Texture, TextureDescriptor, TextureFormat, TextureUsages,
+    TextureView, VertexState,
+};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ScalingMode {
+    // largest whole multiple that fits, centered with bars around it.
+    IntegerLetterbox,
+    // largest scale that fits while keeping aspect, centered with bars.
+    Fit,
+    // smallest scale that covers the window while keeping aspect, cropping the overflow.
+    Fill,
+    // cover the window exactly, ignoring aspect.
+    Stretch,
+}
+
+// a fixed size offscreen target that the scene renders into, scaled onto the window.
+pub struct VirtualResolution {
+    pub size: UVec2,
+    pub mode: ScalingMode,
+    pub texture: Texture,
+    pub view: TextureView,
+    dest_uniform: Buffer,
+    bind_group: BindGroup,
+    pipeline: RenderPipeline,
+}
+
+impl VirtualResolution {
+    pub fn new(
+        device: Arc<Mutex<Device>>,
+        format: TextureFormat,
+        size: UVec2,
+        mode: ScalingMode,
+    ) -> Self {
+        let device = device.lock().unwrap();
+
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("virtual resolution target"),
+            size: Extent3d {
+                width: size.x,
+                height: size.y,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+        let dest_uniform = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("virtual resolution dest"),
+            contents: bytemuck::cast_slice(&[Vec4::new(-1.0, 1.0, 2.0, 2.0)]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: BufferSize::new(size_of::<Vec4>() as u64),
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: dest_uniform.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&view),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(&sampler),
+                },
+            ],
+            label: None,
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader_module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("virtual resolution blit"),
+            source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("scaling.wgsl"))),
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("virtual resolution pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader_module,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &shader_module,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            size,
+            mode,
+            texture,
+            view,
+            dest_uniform,
+            bind_group,
+            pipeline,
+        }
+    }
+
+    // where the virtual target lands in the window, as (top left, size) in window pixels.
+    // in Fill mode this extends past the window edges.
+    pub fn dest_rect(&self, window: UVec2) -> (Vec2, Vec2) {
+        let window = window.as_vec2();
+        let size = self.size.as_vec2();
+        let fit = (window.x / size.x).min(window.y / size.y);
+        let wh = match self.mode {
+            ScalingMode::IntegerLetterbox => size * fit.floor().max(1.0),
+            ScalingMode::Fit => size * fit,
+            ScalingMode::Fill => size * (window.x / size.x).max(window.y / size.y),
+            ScalingMode::Stretch => window,
+        };
+        (((window - wh) * 0.5).round(), wh)
+    }
+
+    // map a window pixel position into virtual pixels. positions in the bars map outside 0..size.
+    pub fn window_to_virtual(&self, window: UVec2, position: Vec2) -> Vec2 {
+        let (xy, wh) = self.dest_rect(window);
+        (position - xy) / wh * self.size.as_vec2()
+    }
+
+    pub fn contains(&self, virtual_position: Vec2) -> bool {
+        virtual_position.cmpge(Vec2::ZERO).all()
+            && virtual_position.cmplt(self.size.as_vec2()).all()
+    }
+
+    pub fn blit(
+        &self,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        target: &TextureView,
+        window: UVec2,
+    ) {
+        let (xy, wh) = self.dest_rect(window);
+        let window = window.as_vec2();
+        let dest = Vec4::new(
+            xy.x / window.x * 2.0 - 1.0,
+            1.0 - xy.y / window.y * 2.0,
+            wh.x / window.x * 2.0,
+            wh.y / window.y * 2.0,
+        );
+        queue.write_buffer(&self.dest_uniform, 0, bytemuck::cast_slice(&[dest]));
+
+        let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("virtual resolution blit"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..4, 0..1);
+    }
+}
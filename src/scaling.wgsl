@@ -0,0 +1,27 @@
+// draws the virtual resolution target onto the window.
+@group(0) @binding(0)
+var<uniform> dest: vec4<f32>; // ndc left, top, width, height
+@group(0) @binding(1)
+var source: texture_2d<f32>;
+@group(0) @binding(2)
+var source_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    // triangle strip over the unit square.
+    let uv = vec2<f32>(f32(index & 1u), f32(index >> 1u));
+    var result: VertexOutput;
+    result.position = vec4(dest.x + uv.x * dest.z, dest.y - uv.y * dest.w, 0.0, 1.0);
+    result.tex_coords = uv;
+    return result;
+}
+
+@fragment
+fn fs_main(vout: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(source, source_sampler, vout.tex_coords);
+}
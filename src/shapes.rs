@@ -0,0 +1,455 @@
+use std::{borrow::Cow, f32::consts::PI, mem::size_of};
+
+use glam::{Mat4, Vec2, Vec4};
+use lyon::math::{point, Box2D, Point};
+use lyon::path::{builder::BorderRadii, Path, Polygon, Winding};
+use lyon::tessellation::{
+    BuffersBuilder, FillOptions, FillTessellator, FillVertex, LineCap, LineJoin, StrokeOptions,
+    StrokeTessellator, StrokeVertex, TessellationError, TessellationResult, VertexBuffers,
+};
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroup, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
+    BindingType, Buffer, BufferAddress, BufferBindingType, BufferDescriptor, BufferSize,
+    BufferUsages, ColorTargetState, ColorWrites, Device, FragmentState, IndexFormat,
+    MultisampleState, PrimitiveState, Queue, RenderPass, RenderPipeline, RenderPipelineDescriptor,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureFormat, VertexAttribute,
+    VertexBufferLayout, VertexFormat, VertexState, VertexStepMode,
+};
+
+use crate::types::BlendMode;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShapeVertex {
+    pub position: [f32; 2],
+    pub color: [f32; 4],
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ShapeStyle {
+    Fill(Vec4),
+    // line width in logical pixels, and color.
+    Stroke(f32, Vec4),
+}
+
+// shapes queued for this frame, in logical pixels from the screen's top left, or in world
+// pixels for the batch from Context::draw_world. Context::render draws them over the geometry
+// groups and under text, then clears the batch.
+//
+// a shape lyon can't tessellate draws nothing, and its error is kept in `errors` until
+// take_errors drains it.
+pub struct ShapeBatch {
+    pub geometry: VertexBuffers<ShapeVertex, u32>,
+    pub tolerance: f32,
+    pub errors: Vec<TessellationError>,
+    fill: FillTessellator,
+    stroke: StrokeTessellator,
+}
+
+impl ShapeBatch {
+    pub fn new() -> Self {
+        Self {
+            geometry: VertexBuffers::new(),
+            tolerance: 0.1,
+            errors: vec![],
+            fill: FillTessellator::new(),
+            stroke: StrokeTessellator::new(),
+        }
+    }
+
+    // drop this frame's shapes. errors are kept until taken.
+    pub fn clear(&mut self) {
+        self.geometry.vertices.clear();
+        self.geometry.indices.clear();
+    }
+
+    // the errors of shapes that weren't drawn since the last call.
+    pub fn take_errors(&mut self) -> Vec<TessellationError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    pub fn line(&mut self, a: Vec2, b: Vec2, width: f32, color: Vec4) -> &mut Self {
+        self.polyline(&[a, b], false, width, color)
+    }
+
+    pub fn rect(&mut self, xy: Vec2, wh: Vec2, style: ShapeStyle) -> &mut Self {
+        let rect = Box2D::new(point(xy.x, xy.y), point(xy.x + wh.x, xy.y + wh.y));
+        let start = self.start();
+        let result = match style {
+            ShapeStyle::Fill(color) => {
+                let options = FillOptions::tolerance(self.tolerance);
+                let mut output = fill_builder(&mut self.geometry, color);
+                self.fill.tessellate_rectangle(&rect, &options, &mut output)
+            }
+            ShapeStyle::Stroke(width, color) => {
+                let options = self.stroke_options(width);
+                let mut output = stroke_builder(&mut self.geometry, color);
+                self.stroke
+                    .tessellate_rectangle(&rect, &options, &mut output)
+            }
+        };
+        self.finish(start, result)
+    }
+
+    pub fn rounded_rect(
+        &mut self,
+        xy: Vec2,
+        wh: Vec2,
+        radius: f32,
+        style: ShapeStyle,
+    ) -> &mut Self {
+        let rect = Box2D::new(point(xy.x, xy.y), point(xy.x + wh.x, xy.y + wh.y));
+        let radius = radius.min(wh.x * 0.5).min(wh.y * 0.5).max(0.0);
+        let mut builder = Path::builder();
+        builder.add_rounded_rectangle(&rect, &BorderRadii::new(radius), Winding::Positive);
+        self.path(&builder.build(), style)
+    }
+
+    pub fn circle(&mut self, center: Vec2, radius: f32, style: ShapeStyle) -> &mut Self {
+        let center = point(center.x, center.y);
+        let start = self.start();
+        let result = match style {
+            ShapeStyle::Fill(color) => {
+                let options = FillOptions::tolerance(self.tolerance);
+                let mut output = fill_builder(&mut self.geometry, color);
+                self.fill
+                    .tessellate_circle(center, radius, &options, &mut output)
+            }
+            ShapeStyle::Stroke(width, color) => {
+                let options = self.stroke_options(width);
+                let mut output = stroke_builder(&mut self.geometry, color);
+                self.stroke
+                    .tessellate_circle(center, radius, &options, &mut output)
+            }
+        };
+        self.finish(start, result)
+    }
+
+    // a closed polygon through `points`. fills may be concave or self-intersecting.
+    pub fn polygon(&mut self, points: &[Vec2], style: ShapeStyle) -> &mut Self {
+        if points.len() < 2 {
+            return self;
+        }
+        let points: Vec<Point> = points.iter().map(|p| point(p.x, p.y)).collect();
+        let polygon = Polygon {
+            points: &points,
+            closed: true,
+        };
+        let start = self.start();
+        let result = match style {
+            ShapeStyle::Fill(color) => {
+                let options = FillOptions::tolerance(self.tolerance);
+                let mut output = fill_builder(&mut self.geometry, color);
+                self.fill.tessellate_polygon(polygon, &options, &mut output)
+            }
+            ShapeStyle::Stroke(width, color) => {
+                let options = self.stroke_options(width);
+                let mut output = stroke_builder(&mut self.geometry, color);
+                self.stroke
+                    .tessellate_polygon(polygon, &options, &mut output)
+            }
+        };
+        self.finish(start, result)
+    }
+
+    // an open line through `points`, or closed back to the first point.
+    pub fn polyline(
+        &mut self,
+        points: &[Vec2],
+        closed: bool,
+        width: f32,
+        color: Vec4,
+    ) -> &mut Self {
+        if points.len() < 2 {
+            return self;
+        }
+        let points: Vec<Point> = points.iter().map(|p| point(p.x, p.y)).collect();
+        let polygon = Polygon {
+            points: &points,
+            closed,
+        };
+        let start = self.start();
+        let options = self.stroke_options(width);
+        let mut output = stroke_builder(&mut self.geometry, color);
+        let result = self
+            .stroke
+            .tessellate_polygon(polygon, &options, &mut output);
+        self.finish(start, result)
+    }
+
+    // part of a circle's outline. angles are in degrees, turning from +x toward +y, which is
+    // clockwise on screen while y points down.
+    pub fn arc(
+        &mut self,
+        center: Vec2,
+        radius: f32,
+        start_degrees: f32,
+        end_degrees: f32,
+        width: f32,
+        color: Vec4,
+    ) -> &mut Self {
+        let sweep = (end_degrees - start_degrees).to_radians();
+        // enough segments to stay within the tolerance.
+        let step = 2.0 * (1.0 - self.tolerance / radius.max(self.tolerance)).acos();
+        let segments = ((sweep.abs() / step.max(0.01)).ceil() as usize).clamp(1, 256);
+        let points: Vec<Vec2> = (0..=segments)
+            .map(|i| {
+                let angle = start_degrees.to_radians() + sweep * i as f32 / segments as f32;
+                center + Vec2::new(angle.cos(), angle.sin()) * radius
+            })
+            .collect();
+        let closed = sweep.abs() >= 2.0 * PI;
+        self.polyline(&points, closed, width, color)
+    }
+
+    pub fn path(&mut self, path: &Path, style: ShapeStyle) -> &mut Self {
+        let start = self.start();
+        let result = match style {
+            ShapeStyle::Fill(color) => {
+                let options = FillOptions::tolerance(self.tolerance);
+                let mut output = fill_builder(&mut self.geometry, color);
+                self.fill.tessellate_path(path, &options, &mut output)
+            }
+            ShapeStyle::Stroke(width, color) => {
+                let options = self.stroke_options(width);
+                let mut output = stroke_builder(&mut self.geometry, color);
+                self.stroke.tessellate_path(path, &options, &mut output)
+            }
+        };
+        self.finish(start, result)
+    }
+
+    // where the next shape's vertices and indices begin.
+    fn start(&self) -> (usize, usize) {
+        (self.geometry.vertices.len(), self.geometry.indices.len())
+    }
+
+    // keep a tessellated shape, or on failure drop whatever part of it was written.
+    fn finish(&mut self, start: (usize, usize), result: TessellationResult) -> &mut Self {
+        if let Err(error) = result {
+            self.geometry.vertices.truncate(start.0);
+            self.geometry.indices.truncate(start.1);
+            self.errors.push(error);
+        }
+        self
+    }
+
+    fn stroke_options(&self, width: f32) -> StrokeOptions {
+        StrokeOptions::tolerance(self.tolerance)
+            .with_line_width(width)
+            .with_line_cap(LineCap::Butt)
+            .with_line_join(LineJoin::Miter)
+    }
+}
+
+impl Default for ShapeBatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn fill_builder(
+    geometry: &mut VertexBuffers<ShapeVertex, u32>,
+    color: Vec4,
+) -> BuffersBuilder<'_, ShapeVertex, u32, impl Fn(FillVertex) -> ShapeVertex> {
+    BuffersBuilder::new(geometry, move |vertex: FillVertex| ShapeVertex {
+        position: vertex.position().to_array(),
+        color: color.to_array(),
+    })
+}
+
+fn stroke_builder(
+    geometry: &mut VertexBuffers<ShapeVertex, u32>,
+    color: Vec4,
+) -> BuffersBuilder<'_, ShapeVertex, u32, impl Fn(StrokeVertex) -> ShapeVertex> {
+    BuffersBuilder::new(geometry, move |vertex: StrokeVertex| ShapeVertex {
+        position: vertex.position().to_array(),
+        color: color.to_array(),
+    })
+}
+
+const SHAPE_BUFFER_LAYOUT: VertexBufferLayout<'_> = VertexBufferLayout {
+    array_stride: size_of::<ShapeVertex>() as BufferAddress,
+    step_mode: VertexStepMode::Vertex,
+    attributes: &[
+        VertexAttribute {
+            offset: 0,
+            shader_location: 0,
+            format: VertexFormat::Float32x2,
+        },
+        VertexAttribute {
+            offset: size_of::<[f32; 2]>() as BufferAddress,
+            shader_location: 1,
+            format: VertexFormat::Float32x4,
+        },
+    ],
+};
+
+// a batch's buffers on the gpu, and the transform its vertices are drawn through.
+struct ShapeBuffers {
+    transform: Buffer,
+    bind_group: BindGroup,
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+    index_count: u32,
+}
+
+impl ShapeBuffers {
+    fn new(device: &Device, layout: &BindGroupLayout, label: &str) -> Self {
+        let transform = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some(label),
+            contents: bytemuck::cast_slice(&[Mat4::IDENTITY]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: transform.as_entire_binding(),
+            }],
+            label: None,
+        });
+        Self {
+            transform,
+            bind_group,
+            vertex_buffer: shape_buffer(device, BufferUsages::VERTEX, 1024),
+            index_buffer: shape_buffer(device, BufferUsages::INDEX, 1024),
+            index_count: 0,
+        }
+    }
+
+    fn prepare(&mut self, device: &Device, queue: &Queue, batch: &ShapeBatch, transform: Mat4) {
+        queue.write_buffer(&self.transform, 0, bytemuck::cast_slice(&[transform]));
+        let vertices: &[u8] = bytemuck::cast_slice(&batch.geometry.vertices);
+        let indices: &[u8] = bytemuck::cast_slice(&batch.geometry.indices);
+        if vertices.len() as u64 > self.vertex_buffer.size() {
+            self.vertex_buffer = shape_buffer(device, BufferUsages::VERTEX, vertices.len());
+        }
+        if indices.len() as u64 > self.index_buffer.size() {
+            self.index_buffer = shape_buffer(device, BufferUsages::INDEX, indices.len());
+        }
+        if !indices.is_empty() {
+            queue.write_buffer(&self.vertex_buffer, 0, vertices);
+            queue.write_buffer(&self.index_buffer, 0, indices);
+        }
+        self.index_count = batch.geometry.indices.len() as u32;
+    }
+
+    fn render<'pass>(&'pass self, pass: &mut RenderPass<'pass>) {
+        if self.index_count == 0 {
+            return;
+        }
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.set_index_buffer(self.index_buffer.slice(..), IndexFormat::Uint32);
+        pass.draw_indexed(0..self.index_count, 0, 0..1);
+    }
+}
+
+// uploads the screen and world ShapeBatches each frame into buffers that grow as needed.
+// world shapes are drawn first, through the camera's view matrix, so they follow the
+// instances they outline.
+pub struct ShapeRenderer {
+    pub batch: ShapeBatch,
+    pub world_batch: ShapeBatch,
+    pipeline: RenderPipeline,
+    screen: ShapeBuffers,
+    world: ShapeBuffers,
+}
+
+impl ShapeRenderer {
+    pub fn new(device: &Device, format: TextureFormat) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: BufferSize::new(size_of::<Mat4>() as u64),
+                },
+                count: None,
+            }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader_module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("shapes"),
+            source: ShaderSource::Wgsl(Cow::Borrowed(include_str!("shapes.wgsl"))),
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("shapes pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader_module,
+                entry_point: "vs_main",
+                buffers: &[SHAPE_BUFFER_LAYOUT],
+            },
+            fragment: Some(FragmentState {
+                module: &shader_module,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: BlendMode::Premultiplied.blend_state(),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            batch: ShapeBatch::new(),
+            world_batch: ShapeBatch::new(),
+            pipeline,
+            screen: ShapeBuffers::new(device, &bind_group_layout, "shapes screen transform"),
+            world: ShapeBuffers::new(device, &bind_group_layout, "shapes world transform"),
+        }
+    }
+
+    // `view_matrix` maps world pixels to clip space, as for the instance groups.
+    pub fn prepare(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        logical_size: Vec2,
+        view_matrix: Mat4,
+    ) {
+        let screen_matrix =
+            Mat4::orthographic_lh(0.0, logical_size.x, logical_size.y, 0.0, -1.0, 1.0);
+        self.screen
+            .prepare(device, queue, &self.batch, screen_matrix);
+        self.world
+            .prepare(device, queue, &self.world_batch, view_matrix);
+    }
+
+    pub fn render<'pass>(&'pass self, pass: &mut RenderPass<'pass>) {
+        if self.screen.index_count == 0 && self.world.index_count == 0 {
+            return;
+        }
+        pass.set_pipeline(&self.pipeline);
+        self.world.render(pass);
+        self.screen.render(pass);
+    }
+
+    pub fn clear(&mut self) {
+        self.batch.clear();
+        self.world_batch.clear();
+    }
+}
+
+fn shape_buffer(device: &Device, usage: BufferUsages, size: usize) -> Buffer {
+    device.create_buffer(&BufferDescriptor {
+        label: Some("shapes"),
+        size: (size as u64).next_power_of_two(),
+        usage: usage | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
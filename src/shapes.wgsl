@@ -0,0 +1,27 @@
+// immediate shapes, positioned in logical or world pixels and moved to clip space by
+// `transform`.
+@group(0) @binding(0)
+var<uniform> transform: mat4x4<f32>;
+
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(vin: VertexInput) -> VertexOutput {
+    var result: VertexOutput;
+    result.position = transform * vec4(vin.position, 0.0, 1.0);
+    result.color = vin.color;
+    return result;
+}
+
+@fragment
+fn fs_main(vout: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4(vout.color.rgb * vout.color.a, vout.color.a);
+}
@@ -1,10 +1,82 @@
-use std::{error::Error, sync::Arc};
+use std::{error::Error, ops::Range, sync::Arc};
 
 use glyphon::{
-    Attrs, Buffer, Color, Family, FontSystem, Metrics, Resolution, Shaping, SwashCache, TextArea,
-    TextAtlas, TextBounds, TextRenderer,
+    Attrs, Buffer, Color, Family, FontSystem, LayoutRunIter, Metrics, RenderError, Resolution,
+    Shaping, SwashCache, TextArea, TextAtlas, TextBounds, TextRenderer,
 };
-use wgpu::{hal::Rect, MultisampleState, TextureFormat};
+use wgpu::{hal::Rect, MultisampleState, RenderPass, TextureFormat};
+use winit::event::WindowEvent;
+
+use crate::{
+    glyphs::{GlyphQuad, GlyphRenderer},
+    text_input::{TextInput, TextInputResponse},
+};
+
+// gradual reveal of a label's characters, dialogue box style.
+pub struct TextReveal {
+    pub chars_per_second: f32,
+    pub punctuation_pause: f32,
+    pub revealed: usize,
+    pub timer: f32,
+}
+
+impl TextReveal {
+    pub fn new(chars_per_second: f32, punctuation_pause: f32) -> Self {
+        Self {
+            chars_per_second,
+            punctuation_pause,
+            revealed: 0,
+            timer: 0.0,
+        }
+    }
+
+    pub fn skip_to_end(&mut self) {
+        self.revealed = usize::MAX;
+        self.timer = 0.0;
+    }
+
+    fn advance(&mut self, dt: f32, chars: &[char]) {
+        if self.revealed >= chars.len() {
+            self.timer = 0.0;
+            return;
+        }
+        self.timer += dt;
+        while self.revealed < chars.len() {
+            // the pause is spent after punctuation, before the next character shows.
+            let mut delay = 1.0 / self.chars_per_second.max(f32::EPSILON);
+            if self.revealed > 0 && is_pause_punctuation(chars[self.revealed - 1]) {
+                delay += self.punctuation_pause;
+            }
+            if self.timer < delay {
+                break;
+            }
+            self.timer -= delay;
+            self.revealed += 1;
+        }
+    }
+}
+
+fn is_pause_punctuation(c: char) -> bool {
+    matches!(c, '.' | ',' | '!' | '?' | ';' | ':' | '…')
+}
+
+// per-glyph effects, evaluated every frame. amplitudes are in logical pixels.
+#[derive(Copy, Clone)]
+pub enum TextEffect {
+    Wave {
+        amplitude: f32,
+        frequency: f32,
+        speed: f32,
+    },
+    Shake {
+        intensity: f32,
+        rate: f32,
+    },
+    Rainbow {
+        speed: f32,
+        spread: f32,
+    },
+}
 
 pub struct TextLabel {
     pub buffer: Buffer,
@@ -13,6 +85,161 @@ pub struct TextLabel {
     pub scale: f64,
     pub bounds: TextBounds,
     pub default_color: Color,
+    pub reveal: Option<TextReveal>,
+    pub effects: Vec<TextEffect>,
+    pub highlight: Option<(Range<usize>, Color)>,
+    chars: Vec<char>,
+}
+
+impl TextLabel {
+    pub fn layout_runs(&self) -> LayoutRunIter<'_> {
+        self.buffer.layout_runs()
+    }
+
+    // characters of every line in order, which is also the order glyphs are revealed in.
+    pub fn chars(&self) -> &[char] {
+        &self.chars
+    }
+
+    pub fn is_revealed(&self) -> bool {
+        match &self.reveal {
+            Some(reveal) => reveal.revealed >= self.chars.len(),
+            None => true,
+        }
+    }
+
+    fn draws_per_glyph(&self) -> bool {
+        !self.effects.is_empty() || self.highlight.is_some() || !self.is_revealed()
+    }
+
+    // layout position (x, line top) of a byte offset within a buffer line.
+    pub fn caret_position(&self, line: usize, index: usize) -> (f32, f32) {
+        let mut position = None;
+        for run in self.layout_runs().filter(|run| run.line_i == line) {
+            for glyph in run.glyphs.iter() {
+                if index < glyph.end {
+                    return (glyph.x, run.line_top);
+                }
+                position = Some((glyph.x + glyph.w, run.line_top));
+            }
+            if position.is_none() {
+                position = Some((0.0, run.line_top));
+            }
+        }
+        position.unwrap_or((0.0, line as f32 * self.buffer.metrics().line_height))
+    }
+
+    // called whenever the buffer's text changes.
+    fn text_changed(&mut self) {
+        self.chars = self
+            .buffer
+            .lines
+            .iter()
+            .flat_map(|line| line.text().chars())
+            .collect();
+    }
+
+    // one quad per laid out glyph that's been revealed, each placed from the glyph's own
+    // physical position with its effect offset added, so the line keeps its shaping.
+    fn glyph_quads(&self, time: f32, sf: f64, bounds: TextBounds, quads: &mut Vec<GlyphQuad>) {
+        let revealed = self.reveal.as_ref().map_or(usize::MAX, |r| r.revealed);
+        let scale = (self.scale * sf) as f32;
+        let (left, top) = ((self.left * sf) as f32, (self.top * sf) as f32);
+        let mut line_char_start = 0;
+        let mut current_line = 0;
+        // byte offset of each char in the current line, to find a glyph's char index.
+        let mut char_starts: Vec<usize> = vec![];
+        let mut char_starts_line = None;
+        for run in self.layout_runs() {
+            while current_line < run.line_i {
+                line_char_start += self.buffer.lines[current_line].text().chars().count();
+                current_line += 1;
+            }
+            if char_starts_line != Some(run.line_i) {
+                char_starts = run.text.char_indices().map(|(i, _)| i).collect();
+                char_starts_line = Some(run.line_i);
+            }
+            let line_y = (run.line_y * scale).round() as i32;
+            for glyph in run.glyphs.iter() {
+                let char_index =
+                    line_char_start + char_starts.partition_point(|start| *start < glyph.start);
+                if char_index >= revealed {
+                    continue;
+                }
+                let (dx, dy, color) = self.glyph_offset_and_color(char_index, time);
+                let physical = glyph.physical((left + dx * scale, top + dy * scale), scale);
+                quads.push(GlyphQuad {
+                    cache_key: physical.cache_key,
+                    x: physical.x,
+                    y: line_y + physical.y,
+                    color,
+                    bounds,
+                });
+            }
+        }
+    }
+
+    fn glyph_offset_and_color(&self, char_index: usize, time: f32) -> (f32, f32, Color) {
+        let (mut dx, mut dy) = (0.0, 0.0);
+        let mut color = self.default_color;
+        if let Some((range, highlight_color)) = &self.highlight {
+            if range.contains(&char_index) {
+                color = *highlight_color;
+            }
+        }
+        for effect in self.effects.iter() {
+            match *effect {
+                TextEffect::Wave {
+                    amplitude,
+                    frequency,
+                    speed,
+                } => {
+                    dy += (time * speed + char_index as f32 * frequency).sin() * amplitude;
+                }
+                TextEffect::Shake { intensity, rate } => {
+                    let tick = (time * rate).floor();
+                    dx += (hash_noise(char_index as f32, tick) * 2.0 - 1.0) * intensity;
+                    dy += (hash_noise(tick, char_index as f32 + 0.5) * 2.0 - 1.0) * intensity;
+                }
+                TextEffect::Rainbow { speed, spread } => {
+                    let hue = (time * speed + char_index as f32 * spread).rem_euclid(1.0);
+                    color = hue_to_color(hue, color.a());
+                }
+            }
+        }
+        (dx, dy, color)
+    }
+}
+
+fn hash_noise(a: f32, b: f32) -> f32 {
+    ((a * 12.9898 + b * 78.233).sin() * 43_758.547).rem_euclid(1.0)
+}
+
+fn hue_to_color(hue: f32, alpha: u8) -> Color {
+    let h = hue * 6.0;
+    let x = 1.0 - (h.rem_euclid(2.0) - 1.0).abs();
+    let (r, g, b) = match h as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    Color::rgba(
+        (r * 255.0) as u8,
+        (g * 255.0) as u8,
+        (b * 255.0) as u8,
+        alpha,
+    )
+}
+
+fn caret_color(input: &TextInput, time: f32) -> Color {
+    if input.focused && (time - input.blink_start).rem_euclid(1.0) < 0.5 {
+        Color::rgb(220, 220, 220)
+    } else {
+        Color::rgba(0, 0, 0, 0)
+    }
 }
 
 pub struct TextCollection {
@@ -21,6 +248,11 @@ pub struct TextCollection {
     pub text_renderer: TextRenderer,
     pub cache: SwashCache,
     pub atlas: TextAtlas,
+    pub glyph_renderer: GlyphRenderer,
+    pub time: f32,
+    pub inputs: Vec<TextInput>,
+    pub clipboard: String,
+    pub scale_factor: f64,
 }
 
 impl TextCollection {
@@ -28,6 +260,7 @@ impl TextCollection {
         device: Arc<std::sync::Mutex<wgpu::Device>>,
         queue: Arc<std::sync::Mutex<wgpu::Queue>>,
         swapchain_format: TextureFormat,
+        scale_factor: f64,
     ) -> Self {
         let device = device.lock().unwrap();
         let queue = queue.lock().unwrap();
@@ -37,6 +270,7 @@ impl TextCollection {
         let mut atlas = TextAtlas::new(&device, &queue, swapchain_format);
         let text_renderer =
             TextRenderer::new(&mut atlas, &device, MultisampleState::default(), None);
+        let glyph_renderer = GlyphRenderer::new(&device, swapchain_format);
 
         TextCollection {
             texts: vec![],
@@ -44,20 +278,22 @@ impl TextCollection {
             text_renderer,
             cache,
             atlas,
+            glyph_renderer,
+            time: 0.0,
+            inputs: vec![],
+            clipboard: String::new(),
+            scale_factor,
         }
     }
 
-    pub fn new_text(
-        &mut self,
-        rect: Rect<f64>,
-        text: &str,
-        display_scale_factor: f64,
-        text_scale_factor: f64,
-    ) {
+    // rect is in logical pixels; the display scale factor is applied when preparing.
+    pub fn new_text(&mut self, rect: Rect<f64>, text: &str, text_scale_factor: f64) -> usize {
         let mut buffer = Buffer::new(&mut self.font_system, Metrics::new(14.0, 18.0));
-        let physical_width = (rect.w * display_scale_factor) as f32;
-        let physical_height = (rect.h * display_scale_factor) as f32;
-        buffer.set_size(&mut self.font_system, physical_width, physical_height);
+        buffer.set_size(
+            &mut self.font_system,
+            (rect.w / text_scale_factor) as f32,
+            (rect.h / text_scale_factor) as f32,
+        );
         buffer.set_text(
             &mut self.font_system,
             text,
@@ -66,14 +302,151 @@ impl TextCollection {
         );
         buffer.shape_until_scroll(&mut self.font_system);
 
-        self.texts.push(TextLabel {
+        let index = self.texts.len();
+        let mut label = TextLabel {
             buffer,
             left: rect.x,
             top: rect.y,
             scale: text_scale_factor,
             bounds: TextBounds::default(),
             default_color: Color::rgb(220, 220, 220),
+            reveal: None,
+            effects: vec![],
+            highlight: None,
+            chars: vec![],
+        };
+        label.text_changed();
+        self.texts.push(label);
+        index
+    }
+
+    pub fn new_input(&mut self, rect: Rect<f64>, text_scale_factor: f64, multiline: bool) -> usize {
+        let caret_rect = Rect {
+            x: rect.x,
+            y: rect.y,
+            w: 8.0,
+            h: 18.0,
+        };
+        let label = self.new_text(rect, "", text_scale_factor);
+        let caret_label = self.new_text(caret_rect, "|", text_scale_factor);
+        let index = self.inputs.len();
+        self.inputs
+            .push(TextInput::new(label, caret_label, multiline));
+        self.sync_input(index);
+        index
+    }
+
+    pub fn focus_input(&mut self, index: Option<usize>) {
+        for (i, input) in self.inputs.iter_mut().enumerate() {
+            input.focused = Some(i) == index;
+            input.blink_start = self.time;
+        }
+        for i in 0..self.inputs.len() {
+            self.sync_input(i);
+        }
+    }
+
+    // focus whichever input lies under a screen position, placing its caret at the hit glyph.
+    pub fn click_input(&mut self, x: f64, y: f64) -> Option<usize> {
+        let hit = self.inputs.iter().position(|input| {
+            let label = &self.texts[input.label];
+            let (w, h) = label.buffer.size();
+            x >= label.left
+                && y >= label.top
+                && x <= label.left + (w as f64 * label.scale)
+                && y <= label.top + (h as f64 * label.scale)
         });
+        self.focus_input(hit);
+        if let Some(index) = hit {
+            let label = &self.texts[self.inputs[index].label];
+            let cursor = label.buffer.hit(
+                ((x - label.left) / label.scale) as f32,
+                ((y - label.top) / label.scale) as f32,
+            );
+            let input = &mut self.inputs[index];
+            input.anchor = None;
+            input.caret = match cursor {
+                Some(cursor) => {
+                    let line_start: usize = input
+                        .value
+                        .split('\n')
+                        .take(cursor.line)
+                        .map(|line| line.len() + 1)
+                        .sum();
+                    (line_start + cursor.index).min(input.value.len())
+                }
+                None => input.value.len(),
+            };
+            self.sync_input(index);
+        }
+        hit
+    }
+
+    pub fn input_event(&mut self, index: usize, event: &WindowEvent) -> TextInputResponse {
+        let response = self.inputs[index].handle_event(event, &mut self.clipboard);
+        if response != TextInputResponse::Ignored {
+            self.inputs[index].blink_start = self.time;
+            self.sync_input(index);
+        }
+        response
+    }
+
+    // push an input's value, selection and caret position out to its labels.
+    pub fn sync_input(&mut self, index: usize) {
+        let input = &self.inputs[index];
+        let display = input.display_text();
+        let caret = input.display_caret();
+        let highlight = input.selection().map(|range| {
+            let count = |to: usize| input.value[..to].chars().filter(|c| *c != '\n').count();
+            (
+                count(range.start)..count(range.end),
+                Color::rgb(120, 170, 255),
+            )
+        });
+        let (label_index, caret_index) = (input.label, input.caret_label);
+        let caret_color = caret_color(input, self.time);
+
+        self.set_text(label_index, &display);
+        let label = &mut self.texts[label_index];
+        label.highlight = highlight;
+        let line = display[..caret].matches('\n').count();
+        let line_start = display[..caret].rfind('\n').map_or(0, |i| i + 1);
+        let (x, top) = label.caret_position(line, caret - line_start);
+        let (left, label_top, scale) = (label.left, label.top, label.scale);
+
+        let caret_label = &mut self.texts[caret_index];
+        caret_label.left = left + (x as f64 - 2.0) * scale;
+        caret_label.top = label_top + top as f64 * scale;
+        caret_label.default_color = caret_color;
+    }
+
+    pub fn set_text(&mut self, index: usize, text: &str) {
+        let label = &mut self.texts[index];
+        label.buffer.set_text(
+            &mut self.font_system,
+            text,
+            Attrs::new().family(Family::SansSerif),
+            Shaping::Advanced,
+        );
+        label.buffer.shape_until_scroll(&mut self.font_system);
+        label.text_changed();
+        if let Some(reveal) = &mut label.reveal {
+            reveal.revealed = 0;
+            reveal.timer = 0.0;
+        }
+    }
+
+    // advance reveals and effect time. called once per frame by the context.
+    pub fn update(&mut self, dt: f32) {
+        self.time += dt;
+        for label in self.texts.iter_mut() {
+            if let Some(reveal) = &mut label.reveal {
+                reveal.advance(dt, &label.chars);
+            }
+        }
+        for input in self.inputs.iter() {
+            self.texts[input.caret_label].default_color = caret_color(input, self.time);
+        }
     }
 
     pub fn trim_atlas(&mut self) {
@@ -90,6 +463,34 @@ impl TextCollection {
         let device = device.lock().unwrap();
         let queue = queue.lock().unwrap();
 
+        // labels are laid out in logical pixels, glyphon draws in physical ones.
+        let sf = self.scale_factor;
+        let mut areas: Vec<TextArea> = vec![];
+        let mut quads: Vec<GlyphQuad> = vec![];
+        for t in self.texts.iter() {
+            let label_bounds = TextBounds {
+                left: (t.bounds.left as f64 * sf) as i32,
+                top: (t.bounds.top as f64 * sf) as i32,
+                right: (t.bounds.right as f64 * sf) as i32,
+                bottom: (t.bounds.bottom as f64 * sf) as i32,
+            };
+            if !t.draws_per_glyph() {
+                areas.push(TextArea {
+                    buffer: &t.buffer,
+                    left: (t.left * sf) as f32,
+                    top: (t.top * sf) as f32,
+                    scale: (t.scale * sf) as f32,
+                    bounds: label_bounds,
+                    default_color: t.default_color,
+                });
+                continue;
+            }
+
+            // animated or partially revealed labels go to the glyph renderer instead, which
+            // can move and color each glyph.
+            t.glyph_quads(self.time, sf, label_bounds, &mut quads);
+        }
+
         self.text_renderer.prepare(
             &device,
             &queue,
@@ -99,16 +500,25 @@ impl TextCollection {
                 width: screen_width,
                 height: screen_height,
             },
-            self.texts.iter().map(|t| TextArea {
-                buffer: &t.buffer,
-                left: t.left as f32,
-                top: t.top as f32,
-                scale: t.scale as f32,
-                bounds: t.bounds,
-                default_color: t.default_color,
-            }),
+            areas,
             &mut self.cache,
         )?;
+        self.glyph_renderer.prepare(
+            &device,
+            &queue,
+            &mut self.font_system,
+            &mut self.cache,
+            &quads,
+            screen_width,
+            screen_height,
+        );
+        Ok(())
+    }
+
+    // glyphon's labels, then the animated ones over them.
+    pub fn render<'pass>(&'pass self, pass: &mut RenderPass<'pass>) -> Result<(), RenderError> {
+        self.text_renderer.render(&self.atlas, pass)?;
+        self.glyph_renderer.render(pass);
         Ok(())
     }
 }
@@ -0,0 +1,536 @@
+use std::ops::Range;
+
+use winit::event::{ElementState, Ime, KeyEvent, WindowEvent};
+use winit::keyboard::{Key, ModifiersState, NamedKey};
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TextInputResponse {
+    Ignored,
+    Handled,
+    Changed,
+    Submitted,
+}
+
+// editing state for a text field. `label` and `caret_label` index into TextCollection.texts.
+// caret and anchor are byte offsets into `value`.
+pub struct TextInput {
+    pub label: usize,
+    pub caret_label: usize,
+    pub value: String,
+    pub caret: usize,
+    pub anchor: Option<usize>,
+    pub preedit: String,
+    pub multiline: bool,
+    pub focused: bool,
+    pub blink_start: f32,
+    modifiers: ModifiersState,
+    ime_enabled: bool,
+}
+
+impl TextInput {
+    pub fn new(label: usize, caret_label: usize, multiline: bool) -> Self {
+        Self {
+            label,
+            caret_label,
+            value: String::new(),
+            caret: 0,
+            anchor: None,
+            preedit: String::new(),
+            multiline,
+            focused: false,
+            blink_start: 0.0,
+            modifiers: ModifiersState::empty(),
+            ime_enabled: false,
+        }
+    }
+
+    pub fn selection(&self) -> Option<Range<usize>> {
+        match self.anchor {
+            Some(anchor) if anchor != self.caret => {
+                Some(anchor.min(self.caret)..anchor.max(self.caret))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn selected_text(&self) -> Option<&str> {
+        self.selection().map(|range| &self.value[range])
+    }
+
+    pub fn set_value(&mut self, value: &str) {
+        self.value = self.filter(value);
+        self.caret = self.value.len();
+        self.anchor = None;
+    }
+
+    pub fn select_all(&mut self) {
+        self.anchor = Some(0);
+        self.caret = self.value.len();
+    }
+
+    pub fn insert(&mut self, text: &str) {
+        let text = self.filter(text);
+        self.delete_selection();
+        self.value.insert_str(self.caret, &text);
+        self.caret += text.len();
+    }
+
+    pub fn delete_selection(&mut self) -> bool {
+        match self.selection() {
+            Some(range) => {
+                self.caret = range.start;
+                self.value.replace_range(range, "");
+                self.anchor = None;
+                true
+            }
+            None => {
+                self.anchor = None;
+                false
+            }
+        }
+    }
+
+    // text as it should be displayed, with any uncommitted ime text spliced in at the caret.
+    pub fn display_text(&self) -> String {
+        let mut text = self.value.clone();
+        text.insert_str(self.caret, &self.preedit);
+        text
+    }
+
+    pub fn display_caret(&self) -> usize {
+        self.caret + self.preedit.len()
+    }
+
+    fn filter(&self, text: &str) -> String {
+        text.chars()
+            .filter(|c| match c {
+                '\n' => self.multiline,
+                c => !c.is_control(),
+            })
+            .collect()
+    }
+
+    fn move_caret(&mut self, to: usize, extend: bool) {
+        if extend {
+            if self.anchor.is_none() {
+                self.anchor = Some(self.caret);
+            }
+        } else {
+            self.anchor = None;
+        }
+        self.caret = to;
+    }
+
+    fn prev_boundary(&self, from: usize) -> usize {
+        self.value[..from]
+            .char_indices()
+            .next_back()
+            .map_or(0, |(i, _)| i)
+    }
+
+    fn next_boundary(&self, from: usize) -> usize {
+        self.value[from..]
+            .chars()
+            .next()
+            .map_or(from, |c| from + c.len_utf8())
+    }
+
+    fn prev_word(&self, from: usize) -> usize {
+        let mut i = from;
+        while i > 0 && self.char_before(i).is_whitespace() {
+            i = self.prev_boundary(i);
+        }
+        while i > 0 && !self.char_before(i).is_whitespace() {
+            i = self.prev_boundary(i);
+        }
+        i
+    }
+
+    fn next_word(&self, from: usize) -> usize {
+        let mut i = from;
+        while i < self.value.len() && self.char_at(i).is_whitespace() {
+            i = self.next_boundary(i);
+        }
+        while i < self.value.len() && !self.char_at(i).is_whitespace() {
+            i = self.next_boundary(i);
+        }
+        i
+    }
+
+    // Changed if `value` is no longer `before`, e.g. after an empty paste it's only Handled.
+    fn changed_from(&self, before: &str) -> TextInputResponse {
+        if self.value == before {
+            TextInputResponse::Handled
+        } else {
+            TextInputResponse::Changed
+        }
+    }
+
+    fn char_before(&self, i: usize) -> char {
+        self.value[..i].chars().next_back().unwrap_or(' ')
+    }
+
+    fn char_at(&self, i: usize) -> char {
+        self.value[i..].chars().next().unwrap_or(' ')
+    }
+
+    fn line_start(&self, from: usize) -> usize {
+        self.value[..from].rfind('\n').map_or(0, |i| i + 1)
+    }
+
+    fn line_end(&self, from: usize) -> usize {
+        self.value[from..]
+            .find('\n')
+            .map_or(self.value.len(), |i| from + i)
+    }
+
+    // move up or down a line, keeping the character column where possible.
+    fn vertical(&self, from: usize, down: bool) -> usize {
+        let start = self.line_start(from);
+        let column = self.value[start..from].chars().count();
+        let target_start = if down {
+            let end = self.line_end(from);
+            if end == self.value.len() {
+                return end;
+            }
+            end + 1
+        } else {
+            if start == 0 {
+                return 0;
+            }
+            self.line_start(start - 1)
+        };
+        let target_end = self.line_end(target_start);
+        self.value[target_start..target_end]
+            .char_indices()
+            .nth(column)
+            .map_or(target_end, |(i, _)| target_start + i)
+    }
+
+    pub fn handle_event(
+        &mut self,
+        event: &WindowEvent,
+        clipboard: &mut String,
+    ) -> TextInputResponse {
+        match event {
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+                TextInputResponse::Ignored
+            }
+            _ if !self.focused => TextInputResponse::Ignored,
+            WindowEvent::Ime(ime) => match ime {
+                Ime::Enabled => {
+                    self.ime_enabled = true;
+                    TextInputResponse::Handled
+                }
+                Ime::Disabled => {
+                    self.ime_enabled = false;
+                    self.preedit.clear();
+                    TextInputResponse::Handled
+                }
+                Ime::Preedit(text, _) => {
+                    self.preedit = text.clone();
+                    TextInputResponse::Handled
+                }
+                Ime::Commit(text) => {
+                    self.preedit.clear();
+                    let before = self.value.clone();
+                    self.insert(text);
+                    self.changed_from(&before)
+                }
+            },
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        logical_key,
+                        text,
+                        state: ElementState::Pressed,
+                        ..
+                    },
+                ..
+            } => self.handle_key(logical_key, text.as_deref(), clipboard),
+            _ => TextInputResponse::Ignored,
+        }
+    }
+
+    fn handle_key(
+        &mut self,
+        key: &Key,
+        text: Option<&str>,
+        clipboard: &mut String,
+    ) -> TextInputResponse {
+        let shift = self.modifiers.shift_key();
+        let command = self.modifiers.control_key() || self.modifiers.super_key();
+        match key.as_ref() {
+            Key::Named(NamedKey::ArrowLeft) => {
+                let to = match (self.selection(), shift, command) {
+                    (Some(range), false, _) => range.start,
+                    (_, _, true) => self.prev_word(self.caret),
+                    _ => self.prev_boundary(self.caret),
+                };
+                self.move_caret(to, shift);
+                TextInputResponse::Handled
+            }
+            Key::Named(NamedKey::ArrowRight) => {
+                let to = match (self.selection(), shift, command) {
+                    (Some(range), false, _) => range.end,
+                    (_, _, true) => self.next_word(self.caret),
+                    _ => self.next_boundary(self.caret),
+                };
+                self.move_caret(to, shift);
+                TextInputResponse::Handled
+            }
+            Key::Named(NamedKey::ArrowUp) if self.multiline => {
+                self.move_caret(self.vertical(self.caret, false), shift);
+                TextInputResponse::Handled
+            }
+            Key::Named(NamedKey::ArrowDown) if self.multiline => {
+                self.move_caret(self.vertical(self.caret, true), shift);
+                TextInputResponse::Handled
+            }
+            Key::Named(NamedKey::Home) => {
+                let to = if command {
+                    0
+                } else {
+                    self.line_start(self.caret)
+                };
+                self.move_caret(to, shift);
+                TextInputResponse::Handled
+            }
+            Key::Named(NamedKey::End) => {
+                let to = if command {
+                    self.value.len()
+                } else {
+                    self.line_end(self.caret)
+                };
+                self.move_caret(to, shift);
+                TextInputResponse::Handled
+            }
+            // at either end with nothing selected there's nothing to delete.
+            Key::Named(NamedKey::Backspace) => {
+                let before = self.value.clone();
+                if !self.delete_selection() && self.caret > 0 {
+                    let from = if command {
+                        self.prev_word(self.caret)
+                    } else {
+                        self.prev_boundary(self.caret)
+                    };
+                    self.value.replace_range(from..self.caret, "");
+                    self.caret = from;
+                }
+                self.changed_from(&before)
+            }
+            Key::Named(NamedKey::Delete) => {
+                let before = self.value.clone();
+                if !self.delete_selection() && self.caret < self.value.len() {
+                    let to = if command {
+                        self.next_word(self.caret)
+                    } else {
+                        self.next_boundary(self.caret)
+                    };
+                    self.value.replace_range(self.caret..to, "");
+                }
+                self.changed_from(&before)
+            }
+            Key::Named(NamedKey::Enter) => {
+                if self.multiline && !command {
+                    self.insert("\n");
+                    TextInputResponse::Changed
+                } else {
+                    TextInputResponse::Submitted
+                }
+            }
+            Key::Character(c) if command => match c.to_lowercase().as_str() {
+                "a" => {
+                    self.select_all();
+                    TextInputResponse::Handled
+                }
+                "c" => {
+                    if let Some(selected) = self.selected_text() {
+                        *clipboard = selected.to_string();
+                    }
+                    TextInputResponse::Handled
+                }
+                "x" => match self.selected_text() {
+                    Some(selected) => {
+                        *clipboard = selected.to_string();
+                        self.delete_selection();
+                        TextInputResponse::Changed
+                    }
+                    None => TextInputResponse::Handled,
+                },
+                "v" => {
+                    let before = self.value.clone();
+                    self.insert(&clipboard.clone());
+                    self.changed_from(&before)
+                }
+                _ => TextInputResponse::Ignored,
+            },
+            // while ime is enabled, typed text arrives through Ime::Commit instead.
+            _ => match text {
+                Some(text) if !self.ime_enabled && !command => {
+                    if text.chars().all(|c| c.is_control()) {
+                        return TextInputResponse::Ignored;
+                    }
+                    let before = self.value.clone();
+                    self.insert(text);
+                    self.changed_from(&before)
+                }
+                _ => TextInputResponse::Ignored,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::keyboard::SmolStr;
+
+    use super::*;
+
+    fn input(value: &str, multiline: bool) -> TextInput {
+        let mut input = TextInput::new(0, 1, multiline);
+        input.focused = true;
+        input.set_value(value);
+        input
+    }
+
+    fn press(input: &mut TextInput, key: NamedKey, modifiers: ModifiersState) -> TextInputResponse {
+        input.modifiers = modifiers;
+        input.handle_key(&Key::Named(key), None, &mut String::new())
+    }
+
+    fn type_text(input: &mut TextInput, text: &str) -> TextInputResponse {
+        let key = Key::Character(SmolStr::new(text));
+        input.handle_key(&key, Some(text), &mut String::new())
+    }
+
+    #[test]
+    fn caret_steps_over_multibyte_chars() {
+        let mut field = input("aé😀", false);
+        assert_eq!(field.caret, 7);
+        press(&mut field, NamedKey::ArrowLeft, ModifiersState::empty());
+        assert_eq!(field.caret, 3);
+        press(&mut field, NamedKey::ArrowLeft, ModifiersState::empty());
+        assert_eq!(field.caret, 1);
+        assert_eq!(
+            press(&mut field, NamedKey::Delete, ModifiersState::empty()),
+            TextInputResponse::Changed
+        );
+        assert_eq!(field.value, "a😀");
+        press(&mut field, NamedKey::End, ModifiersState::empty());
+        press(&mut field, NamedKey::Backspace, ModifiersState::empty());
+        assert_eq!(field.value, "a");
+    }
+
+    #[test]
+    fn word_jumps_skip_spaces_then_the_word() {
+        let mut field = input("héllo  wörld", false);
+        press(&mut field, NamedKey::ArrowLeft, ModifiersState::CONTROL);
+        assert_eq!(&field.value[field.caret..], "wörld");
+        press(&mut field, NamedKey::ArrowLeft, ModifiersState::CONTROL);
+        assert_eq!(field.caret, 0);
+        press(&mut field, NamedKey::ArrowRight, ModifiersState::CONTROL);
+        assert_eq!(&field.value[..field.caret], "héllo");
+        press(&mut field, NamedKey::End, ModifiersState::empty());
+        press(&mut field, NamedKey::Backspace, ModifiersState::CONTROL);
+        assert_eq!(field.value, "héllo  ");
+    }
+
+    #[test]
+    fn up_and_down_keep_the_char_column() {
+        let mut field = input("ab\nαβγ\nx", true);
+        // after 'β' on the middle line.
+        field.caret = "ab\nαβ".len();
+        press(&mut field, NamedKey::ArrowUp, ModifiersState::empty());
+        assert_eq!(field.caret, 2);
+        press(&mut field, NamedKey::ArrowDown, ModifiersState::empty());
+        assert_eq!(field.caret, "ab\nαβ".len());
+        // the last line is shorter, so the caret goes to its end.
+        press(&mut field, NamedKey::ArrowDown, ModifiersState::empty());
+        assert_eq!(field.caret, field.value.len());
+        press(&mut field, NamedKey::ArrowDown, ModifiersState::empty());
+        assert_eq!(field.caret, field.value.len());
+    }
+
+    #[test]
+    fn shift_selects_and_typing_replaces_the_selection() {
+        let mut field = input("añb", false);
+        field.caret = 1;
+        press(&mut field, NamedKey::ArrowRight, ModifiersState::SHIFT);
+        assert_eq!(field.selected_text(), Some("ñ"));
+        assert_eq!(type_text(&mut field, "o"), TextInputResponse::Changed);
+        assert_eq!(field.value, "aob");
+        assert_eq!(field.caret, 2);
+        assert_eq!(field.selection(), None);
+    }
+
+    #[test]
+    fn deleting_a_selection_removes_only_it() {
+        let mut field = input("one twö three", false);
+        field.anchor = Some(4);
+        field.caret = "one twö ".len();
+        assert_eq!(
+            press(&mut field, NamedKey::Backspace, ModifiersState::empty()),
+            TextInputResponse::Changed
+        );
+        assert_eq!(field.value, "one three");
+        assert_eq!(field.caret, 4);
+    }
+
+    #[test]
+    fn edits_that_change_nothing_are_only_handled() {
+        let mut field = input("ab", false);
+        field.caret = 0;
+        assert_eq!(
+            press(&mut field, NamedKey::Backspace, ModifiersState::empty()),
+            TextInputResponse::Handled
+        );
+        field.caret = 2;
+        assert_eq!(
+            press(&mut field, NamedKey::Delete, ModifiersState::empty()),
+            TextInputResponse::Handled
+        );
+
+        let mut clipboard = String::new();
+        field.modifiers = ModifiersState::CONTROL;
+        let paste = Key::Character(SmolStr::new("v"));
+        assert_eq!(
+            field.handle_key(&paste, None, &mut clipboard),
+            TextInputResponse::Handled
+        );
+        // a single line field strips the newline, leaving nothing to paste.
+        clipboard.push('\n');
+        assert_eq!(
+            field.handle_key(&paste, None, &mut clipboard),
+            TextInputResponse::Handled
+        );
+        let cut = Key::Character(SmolStr::new("x"));
+        assert_eq!(
+            field.handle_key(&cut, None, &mut clipboard),
+            TextInputResponse::Handled
+        );
+
+        let commit = WindowEvent::Ime(Ime::Commit(String::new()));
+        assert_eq!(
+            field.handle_event(&commit, &mut clipboard),
+            TextInputResponse::Handled
+        );
+        assert_eq!(field.value, "ab");
+    }
+
+    #[test]
+    fn enter_submits_a_single_line_field() {
+        let mut field = input("ab", false);
+        assert_eq!(
+            press(&mut field, NamedKey::Enter, ModifiersState::empty()),
+            TextInputResponse::Submitted
+        );
+        let mut field = input("ab", true);
+        assert_eq!(
+            press(&mut field, NamedKey::Enter, ModifiersState::empty()),
+            TextInputResponse::Changed
+        );
+        assert_eq!(field.value, "ab\n");
+    }
+}
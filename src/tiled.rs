@@ -0,0 +1,1260 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    io::{Cursor, Read},
+    ops::Range,
+    path::Path,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use flate2::read::{GzDecoder, ZlibDecoder};
+use glam::{IVec2, UVec2, Vec2, Vec4};
+use image::ImageReader;
+use roxmltree::{Document, Node};
+use serde_json::Value;
+
+use crate::tilemap::{Cell, TileDef, TileFlags, TileId, Tilemap};
+use crate::types::{
+    SamplerOptions, SpriteRef, TextureSheetClusterDefinition, TextureSheetDefinition,
+};
+use crate::vfs::Vfs;
+
+// the top bits of a Tiled gid flip the tile. bit 28 rotates hexagonal tiles, which
+// aren't supported, so it's masked off with the rest.
+const GID_FLIP_X: u32 = 0x8000_0000;
+const GID_FLIP_Y: u32 = 0x4000_0000;
+const GID_FLIP_DIAGONAL: u32 = 0x2000_0000;
+const GID_MASK: u32 = 0x0fff_ffff;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum PropertyValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Color(Vec4),
+    File(String),
+    Object(u32),
+    Class(Properties),
+}
+
+pub type Properties = HashMap<String, PropertyValue>;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum ObjectShape {
+    Rect,
+    Ellipse,
+    Point,
+    // points relative to the object's position.
+    Polygon(Vec<Vec2>),
+    Polyline(Vec<Vec2>),
+    Text(String),
+    // a tile placed freely, with its flips.
+    Tile(Cell),
+}
+
+// an object in world pixels. position is the top left of its bounds (even for tile
+// objects, which Tiled places by their bottom left), and it turns clockwise by `degrees`
+// about that corner.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TiledObject {
+    pub id: u32,
+    pub name: String,
+    pub class: String,
+    pub position: Vec2,
+    pub size: Vec2,
+    pub degrees: f32,
+    pub visible: bool,
+    pub shape: ObjectShape,
+    pub properties: Properties,
+}
+
+pub struct ObjectLayer {
+    pub name: String,
+    pub visible: bool,
+    pub objects: Vec<TiledObject>,
+    pub properties: Properties,
+}
+
+// where a tileset's tiles ended up: its sheet layers in TiledMap::sheets, and its
+// tiles from `first_tile` on in the tilemap.
+pub struct TiledTileset {
+    pub name: String,
+    pub first_gid: u32,
+    pub first_tile: TileId,
+    pub tile_size: UVec2,
+    pub sheet_layers: Range<usize>,
+    pub properties: Properties,
+}
+
+// a Tiled map (.tmx or .tmj) as a tilemap and object lists. tile layers become tilemap
+// layers in order, group layers are flattened, and image layers are skipped.
+//
+// each tileset image is one sheet in `sheets`, with one cluster whose subs are its tiles;
+// image collection tilesets give one sheet per tile. create the map's group from them with
+// GeoManager::new_unit_square_array, then set `tilemap.group` to it:
+//
+//     let mut map = context.geos.assets.tiled("maps/cave.tmx")?;
+//     map.tilemap.group = context.geos.new_unit_square_array(.., map.sheets.clone(), ..)?;
+//     context.tilemaps.push(map.tilemap);
+//
+// tiles are drawn at the map's tile size. a bool `solid` property on a tile makes it
+// TileFlags::SOLID, and tile animations are kept. only orthogonal maps are supported.
+pub struct TiledMap {
+    pub tilemap: Tilemap,
+    pub sheets: Vec<TextureSheetDefinition>,
+    pub tilesets: Vec<TiledTileset>,
+    pub object_layers: Vec<ObjectLayer>,
+    // per tilemap layer.
+    pub layer_properties: Vec<Properties>,
+    pub tile_properties: HashMap<TileId, Properties>,
+    pub tile_classes: HashMap<TileId, String>,
+    pub properties: Properties,
+    pub background: Option<Vec4>,
+    // what was skipped or ignored while loading.
+    pub warnings: Vec<String>,
+    gids: HashMap<u32, TileId>,
+}
+
+struct ImageSource {
+    path: String,
+    size: Option<UVec2>,
+}
+
+struct TileSource {
+    id: u32,
+    class: String,
+    image: Option<ImageSource>,
+    // local tile ids and seconds.
+    frames: Vec<(u32, f32)>,
+    properties: Properties,
+}
+
+struct TilesetSource {
+    name: String,
+    tile_size: UVec2,
+    columns: u32,
+    tile_count: u32,
+    margin: u32,
+    spacing: u32,
+    image: Option<ImageSource>,
+    tiles: Vec<TileSource>,
+    properties: Properties,
+}
+
+struct TileLayerSource {
+    name: String,
+    visible: bool,
+    opacity: f32,
+    properties: Properties,
+    // origin and size in tiles, and gids row by row.
+    chunks: Vec<(IVec2, UVec2, Vec<u32>)>,
+}
+
+// what group layers pass down to their children.
+#[derive(Copy, Clone)]
+struct Inherited {
+    visible: bool,
+    opacity: f32,
+    offset: Vec2,
+}
+
+impl Inherited {
+    const ROOT: Inherited = Inherited {
+        visible: true,
+        opacity: 1.0,
+        offset: Vec2::ZERO,
+    };
+}
+
+// the map while it's read. its tilemap only holds tile definitions until the size is known.
+struct Loader<'a> {
+    path: &'a str,
+    vfs: &'a Vfs,
+    size: UVec2,
+    tile_size: Vec2,
+    infinite: bool,
+    tile_layers: Vec<TileLayerSource>,
+    map: TiledMap,
+}
+
+impl TiledMap {
+    // a .tmx or .tmj map. external tilesets and images are read relative to it.
+    pub fn load(path: &str, vfs: &Vfs) -> Result<Self, Box<dyn Error>> {
+        let source = vfs.read_to_string(path)?;
+        match path.ends_with(".tmj") || path.ends_with(".json") {
+            true => Self::from_tmj(path, &source, vfs),
+            false => Self::from_tmx(path, &source, vfs),
+        }
+    }
+
+    pub fn from_tmx(path: &str, source: &str, vfs: &Vfs) -> Result<Self, Box<dyn Error>> {
+        let document = Document::parse(source).map_err(|e| format!("{}: {}", path, e))?;
+        let root = document.root_element();
+        if !root.has_tag_name("map") {
+            return Err(format!("{}: not a Tiled map", path).into());
+        }
+        let mut loader = Loader::new(
+            path,
+            vfs,
+            root.attribute("orientation"),
+            UVec2::new(xml_u32(root, "width"), xml_u32(root, "height")),
+            UVec2::new(xml_u32(root, "tilewidth"), xml_u32(root, "tileheight")),
+            root.attribute("infinite") == Some("1"),
+        )?;
+        loader.map.background = root.attribute("backgroundcolor").and_then(parse_color);
+        loader.map.properties = xml_properties(root);
+        for node in root.children().filter(|n| n.has_tag_name("tileset")) {
+            let first_gid = xml_u32(node, "firstgid");
+            let tileset = match node.attribute("source") {
+                Some(source) => loader.external_tileset(source)?,
+                None => xml_tileset(node, path)?,
+            };
+            loader.add_tileset(first_gid, tileset)?;
+        }
+        loader.xml_layers(root, Inherited::ROOT)?;
+        loader.finish()
+    }
+
+    pub fn from_tmj(path: &str, source: &str, vfs: &Vfs) -> Result<Self, Box<dyn Error>> {
+        let root: Value = serde_json::from_str(source).map_err(|e| format!("{}: {}", path, e))?;
+        if root["type"].as_str().is_some_and(|t| t != "map") {
+            return Err(format!("{}: not a Tiled map", path).into());
+        }
+        let mut loader = Loader::new(
+            path,
+            vfs,
+            root["orientation"].as_str(),
+            UVec2::new(json_u32(&root, "width"), json_u32(&root, "height")),
+            UVec2::new(json_u32(&root, "tilewidth"), json_u32(&root, "tileheight")),
+            root["infinite"].as_bool().unwrap_or(false),
+        )?;
+        loader.map.background = root["backgroundcolor"].as_str().and_then(parse_color);
+        loader.map.properties = json_properties(&root);
+        for tileset in json_array(&root, "tilesets") {
+            let first_gid = json_u32(tileset, "firstgid");
+            let tileset_source = match tileset["source"].as_str() {
+                Some(source) => loader.external_tileset(source)?,
+                None => json_tileset(tileset, path)?,
+            };
+            loader.add_tileset(first_gid, tileset_source)?;
+        }
+        loader.json_layers(json_array(&root, "layers"), Inherited::ROOT)?;
+        loader.finish()
+    }
+
+    // the cell a gid places: its tile, that tile's flags and the gid's flips. unknown
+    // gids are empty cells.
+    pub fn cell(&self, gid: u32) -> Cell {
+        let Some(id) = self.gids.get(&(gid & GID_MASK)) else {
+            return Cell::default();
+        };
+        let mut flags = self
+            .tilemap
+            .tile_def(*id)
+            .map_or(TileFlags::NONE, |def| def.flags);
+        for (bit, flag) in [
+            (GID_FLIP_X, TileFlags::FLIP_X),
+            (GID_FLIP_Y, TileFlags::FLIP_Y),
+            (GID_FLIP_DIAGONAL, TileFlags::FLIP_DIAGONAL),
+        ] {
+            if gid & bit != 0 {
+                flags.insert(flag);
+            }
+        }
+        Cell { id: *id, flags }
+    }
+
+    pub fn object_layer(&self, name: &str) -> Option<&ObjectLayer> {
+        self.object_layers.iter().find(|layer| layer.name == name)
+    }
+
+    // every object on every layer with this name, e.g. spawn points.
+    pub fn objects_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a TiledObject> {
+        self.object_layers
+            .iter()
+            .flat_map(|layer| layer.objects.iter())
+            .filter(move |object| object.name == name)
+    }
+}
+
+impl<'a> Loader<'a> {
+    fn new(
+        path: &'a str,
+        vfs: &'a Vfs,
+        orientation: Option<&str>,
+        size: UVec2,
+        tile_size: UVec2,
+        infinite: bool,
+    ) -> Result<Self, Box<dyn Error>> {
+        if let Some(orientation) = orientation.filter(|o| *o != "orthogonal") {
+            return Err(format!("{}: {} maps are unsupported", path, orientation).into());
+        }
+        if tile_size.cmpeq(UVec2::ZERO).any() {
+            return Err(format!("{}: no tile size", path).into());
+        }
+        Ok(Self {
+            path,
+            vfs,
+            size,
+            tile_size: tile_size.as_vec2(),
+            infinite,
+            tile_layers: vec![],
+            map: TiledMap {
+                tilemap: Tilemap::new(0, UVec2::ZERO, tile_size.as_vec2()),
+                sheets: vec![],
+                tilesets: vec![],
+                object_layers: vec![],
+                layer_properties: vec![],
+                tile_properties: HashMap::new(),
+                tile_classes: HashMap::new(),
+                properties: HashMap::new(),
+                background: None,
+                warnings: vec![],
+                gids: HashMap::new(),
+            },
+        })
+    }
+
+    fn external_tileset(&self, source: &str) -> Result<TilesetSource, Box<dyn Error>> {
+        let path = relative_to(self.path, source);
+        let text = self.vfs.read_to_string(&path)?;
+        if path.ends_with(".tsj") || path.ends_with(".json") {
+            let root: Value =
+                serde_json::from_str(&text).map_err(|e| format!("{}: {}", path, e))?;
+            json_tileset(&root, &path)
+        } else {
+            let document = Document::parse(&text).map_err(|e| format!("{}: {}", path, e))?;
+            xml_tileset(document.root_element(), &path)
+        }
+    }
+
+    fn add_tileset(&mut self, first_gid: u32, source: TilesetSource) -> Result<(), Box<dyn Error>> {
+        let first_layer = self.map.sheets.len();
+        let mut sprites = HashMap::new();
+        if let Some(image) = &source.image {
+            if source.tile_size.cmpeq(UVec2::ZERO).any() {
+                return Err(
+                    format!("{}: tileset {} has no tile size", self.path, source.name).into(),
+                );
+            }
+            let size = self.image_size(image)?;
+            let step = source.tile_size + source.spacing;
+            let columns = match source.columns {
+                0 => (size.x.saturating_sub(2 * source.margin) + source.spacing) / step.x,
+                columns => columns,
+            };
+            let tile_count = match source.tile_count {
+                0 => {
+                    columns * ((size.y.saturating_sub(2 * source.margin) + source.spacing) / step.y)
+                }
+                count => count,
+            };
+            let rows = tile_count.div_ceil(columns.max(1));
+            self.map.sheets.push(TextureSheetDefinition {
+                path: image.path.clone(),
+                clusters: vec![TextureSheetClusterDefinition {
+                    label: source.name.clone(),
+                    offset: UVec2::splat(source.margin),
+                    cluster_size: UVec2::new(columns, rows) * step,
+                    sub_size: source.tile_size,
+                    spacing: UVec2::splat(source.spacing),
+                }],
+                sampler: SamplerOptions::default(),
+            });
+            for id in 0..tile_count {
+                sprites.insert(id, SpriteRef::on_layer(first_layer, 0, id as usize));
+            }
+        }
+        for tile in source.tiles.iter() {
+            if let Some(image) = &tile.image {
+                let size = self.image_size(image)?;
+                sprites.insert(tile.id, SpriteRef::on_layer(self.map.sheets.len(), 0, 0));
+                self.map.sheets.push(TextureSheetDefinition {
+                    path: image.path.clone(),
+                    clusters: vec![TextureSheetClusterDefinition {
+                        label: format!("{}/{}", source.name, tile.id),
+                        offset: UVec2::ZERO,
+                        cluster_size: size,
+                        sub_size: size,
+                        spacing: UVec2::ZERO,
+                    }],
+                    sampler: SamplerOptions::default(),
+                });
+            }
+        }
+
+        let first_tile = self.map.tilemap.tiles.len() as TileId + 1;
+        let tiles: HashMap<u32, &TileSource> =
+            source.tiles.iter().map(|tile| (tile.id, tile)).collect();
+        let mut ids: Vec<u32> = sprites.keys().copied().collect();
+        ids.sort();
+        for id in ids {
+            let mut def = TileDef::new(sprites[&id]);
+            let tile_id = self.map.tilemap.tiles.len() as TileId + 1;
+            if let Some(tile) = tiles.get(&id) {
+                if !tile.frames.is_empty() {
+                    let frames = tile
+                        .frames
+                        .iter()
+                        .filter_map(|(frame, seconds)| Some((*sprites.get(frame)?, *seconds)))
+                        .collect();
+                    def = def.animated(frames);
+                }
+                if tile.properties.get("solid") == Some(&PropertyValue::Bool(true)) {
+                    def = def.solid();
+                }
+                if !tile.properties.is_empty() {
+                    self.map
+                        .tile_properties
+                        .insert(tile_id, tile.properties.clone());
+                }
+                if !tile.class.is_empty() {
+                    self.map.tile_classes.insert(tile_id, tile.class.clone());
+                }
+            }
+            self.map.tilemap.add_tile(def);
+            self.map.gids.insert(first_gid + id, tile_id);
+        }
+        self.map.tilesets.push(TiledTileset {
+            name: source.name,
+            first_gid,
+            first_tile,
+            tile_size: source.tile_size,
+            sheet_layers: first_layer..self.map.sheets.len(),
+            properties: source.properties,
+        });
+        Ok(())
+    }
+
+    fn image_size(&self, image: &ImageSource) -> Result<UVec2, Box<dyn Error>> {
+        if let Some(size) = image.size.filter(|size| size.cmpgt(UVec2::ZERO).all()) {
+            return Ok(size);
+        }
+        let bytes = self.vfs.read(&image.path)?;
+        let (width, height) = ImageReader::new(Cursor::new(bytes))
+            .with_guessed_format()?
+            .into_dimensions()
+            .map_err(|e| format!("{}: {}", image.path, e))?;
+        Ok(UVec2::new(width, height))
+    }
+
+    fn xml_layers(&mut self, parent: Node, inherited: Inherited) -> Result<(), Box<dyn Error>> {
+        for node in parent.children().filter(|n| n.is_element()) {
+            let name = node.attribute("name").unwrap_or_default().to_string();
+            let inherited = Inherited {
+                visible: inherited.visible && node.attribute("visible") != Some("0"),
+                opacity: inherited.opacity * xml_f32(node, "opacity", 1.0),
+                offset: inherited.offset
+                    + Vec2::new(xml_f32(node, "offsetx", 0.0), xml_f32(node, "offsety", 0.0)),
+            };
+            match node.tag_name().name() {
+                "layer" => {
+                    self.warn_offset(&name, inherited.offset);
+                    let Some(data) = node.children().find(|n| n.has_tag_name("data")) else {
+                        continue;
+                    };
+                    let encoding = data.attribute("encoding");
+                    let compression = data.attribute("compression");
+                    let mut chunks = vec![];
+                    for chunk in data.children().filter(|n| n.has_tag_name("chunk")) {
+                        chunks.push((
+                            IVec2::new(xml_i32(chunk, "x"), xml_i32(chunk, "y")),
+                            UVec2::new(xml_u32(chunk, "width"), xml_u32(chunk, "height")),
+                            self.xml_gids(chunk, encoding, compression)?,
+                        ));
+                    }
+                    if chunks.is_empty() {
+                        chunks.push((
+                            IVec2::ZERO,
+                            UVec2::new(xml_u32(node, "width"), xml_u32(node, "height")),
+                            self.xml_gids(data, encoding, compression)?,
+                        ));
+                    }
+                    self.tile_layers.push(TileLayerSource {
+                        name,
+                        visible: inherited.visible,
+                        opacity: inherited.opacity,
+                        properties: xml_properties(node),
+                        chunks,
+                    });
+                }
+                "objectgroup" => {
+                    let objects = node
+                        .children()
+                        .filter(|n| n.has_tag_name("object"))
+                        .map(|object| self.xml_object(object, inherited.offset))
+                        .collect();
+                    self.map.object_layers.push(ObjectLayer {
+                        name,
+                        visible: inherited.visible,
+                        objects,
+                        properties: xml_properties(node),
+                    });
+                }
+                "group" => self.xml_layers(node, inherited)?,
+                "imagelayer" => {
+                    self.warn(format!("skipping image layer {}", name));
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn xml_gids(
+        &self,
+        data: Node,
+        encoding: Option<&str>,
+        compression: Option<&str>,
+    ) -> Result<Vec<u32>, Box<dyn Error>> {
+        let text = data
+            .children()
+            .filter(|n| n.is_text())
+            .filter_map(|n| n.text())
+            .collect::<String>();
+        match encoding {
+            Some(encoding) => decode_gids(self.path, encoding, compression, &text),
+            // no encoding: one <tile> element per cell.
+            None => Ok(data
+                .children()
+                .filter(|n| n.has_tag_name("tile"))
+                .map(|tile| xml_u32(tile, "gid"))
+                .collect()),
+        }
+    }
+
+    fn xml_object(&mut self, node: Node, offset: Vec2) -> TiledObject {
+        if node.attribute("template").is_some() {
+            self.warn(format!(
+                "object templates are unsupported, object {} is incomplete",
+                xml_u32(node, "id")
+            ));
+        }
+        let child = |tag: &str| node.children().find(|n| n.has_tag_name(tag));
+        let shape = if let Some(gid) = node.attribute("gid") {
+            ObjectShape::Tile(self.map.cell(gid.parse().unwrap_or(0)))
+        } else if child("ellipse").is_some() {
+            ObjectShape::Ellipse
+        } else if child("point").is_some() {
+            ObjectShape::Point
+        } else if let Some(polygon) = child("polygon") {
+            ObjectShape::Polygon(parse_points(
+                polygon.attribute("points").unwrap_or_default(),
+            ))
+        } else if let Some(polyline) = child("polyline") {
+            ObjectShape::Polyline(parse_points(
+                polyline.attribute("points").unwrap_or_default(),
+            ))
+        } else if let Some(text) = child("text") {
+            ObjectShape::Text(text.text().unwrap_or_default().to_string())
+        } else {
+            ObjectShape::Rect
+        };
+        self.object(
+            shape,
+            TiledObject {
+                id: xml_u32(node, "id"),
+                name: node.attribute("name").unwrap_or_default().to_string(),
+                class: node
+                    .attribute("class")
+                    .or(node.attribute("type"))
+                    .unwrap_or_default()
+                    .to_string(),
+                position: Vec2::new(xml_f32(node, "x", 0.0), xml_f32(node, "y", 0.0)) + offset,
+                size: Vec2::new(xml_f32(node, "width", 0.0), xml_f32(node, "height", 0.0)),
+                degrees: xml_f32(node, "rotation", 0.0),
+                visible: node.attribute("visible") != Some("0"),
+                shape: ObjectShape::Rect,
+                properties: xml_properties(node),
+            },
+        )
+    }
+
+    fn json_layers(
+        &mut self,
+        layers: &[Value],
+        inherited: Inherited,
+    ) -> Result<(), Box<dyn Error>> {
+        for layer in layers {
+            let name = layer["name"].as_str().unwrap_or_default().to_string();
+            let inherited = Inherited {
+                visible: inherited.visible && layer["visible"].as_bool().unwrap_or(true),
+                opacity: inherited.opacity * layer["opacity"].as_f64().unwrap_or(1.0) as f32,
+                offset: inherited.offset
+                    + Vec2::new(
+                        layer["offsetx"].as_f64().unwrap_or(0.0) as f32,
+                        layer["offsety"].as_f64().unwrap_or(0.0) as f32,
+                    ),
+            };
+            match layer["type"].as_str().unwrap_or_default() {
+                "tilelayer" => {
+                    self.warn_offset(&name, inherited.offset);
+                    let encoding = layer["encoding"].as_str();
+                    let compression = layer["compression"].as_str();
+                    let mut chunks = vec![];
+                    for chunk in json_array(layer, "chunks") {
+                        chunks.push((
+                            IVec2::new(json_i32(chunk, "x"), json_i32(chunk, "y")),
+                            UVec2::new(json_u32(chunk, "width"), json_u32(chunk, "height")),
+                            self.json_gids(&chunk["data"], encoding, compression)?,
+                        ));
+                    }
+                    if chunks.is_empty() {
+                        chunks.push((
+                            IVec2::ZERO,
+                            UVec2::new(json_u32(layer, "width"), json_u32(layer, "height")),
+                            self.json_gids(&layer["data"], encoding, compression)?,
+                        ));
+                    }
+                    self.tile_layers.push(TileLayerSource {
+                        name,
+                        visible: inherited.visible,
+                        opacity: inherited.opacity,
+                        properties: json_properties(layer),
+                        chunks,
+                    });
+                }
+                "objectgroup" => {
+                    let objects = json_array(layer, "objects")
+                        .iter()
+                        .map(|object| self.json_object(object, inherited.offset))
+                        .collect();
+                    self.map.object_layers.push(ObjectLayer {
+                        name,
+                        visible: inherited.visible,
+                        objects,
+                        properties: json_properties(layer),
+                    });
+                }
+                "group" => self.json_layers(json_array(layer, "layers"), inherited)?,
+                "imagelayer" => {
+                    self.warn(format!("skipping image layer {}", name));
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    fn json_gids(
+        &self,
+        data: &Value,
+        encoding: Option<&str>,
+        compression: Option<&str>,
+    ) -> Result<Vec<u32>, Box<dyn Error>> {
+        match data {
+            Value::String(text) => {
+                decode_gids(self.path, encoding.unwrap_or("base64"), compression, text)
+            }
+            Value::Array(gids) => Ok(gids
+                .iter()
+                .map(|gid| gid.as_u64().unwrap_or(0) as u32)
+                .collect()),
+            _ => Ok(vec![]),
+        }
+    }
+
+    fn json_object(&mut self, object: &Value, offset: Vec2) -> TiledObject {
+        if object["template"].is_string() {
+            self.warn(format!(
+                "object templates are unsupported, object {} is incomplete",
+                json_u32(object, "id")
+            ));
+        }
+        let points = |key: &str| {
+            json_array(object, key)
+                .iter()
+                .map(|point| {
+                    Vec2::new(
+                        point["x"].as_f64().unwrap_or(0.0) as f32,
+                        point["y"].as_f64().unwrap_or(0.0) as f32,
+                    )
+                })
+                .collect()
+        };
+        let shape = if let Some(gid) = object["gid"].as_u64() {
+            ObjectShape::Tile(self.map.cell(gid as u32))
+        } else if object["ellipse"].as_bool() == Some(true) {
+            ObjectShape::Ellipse
+        } else if object["point"].as_bool() == Some(true) {
+            ObjectShape::Point
+        } else if object["polygon"].is_array() {
+            ObjectShape::Polygon(points("polygon"))
+        } else if object["polyline"].is_array() {
+            ObjectShape::Polyline(points("polyline"))
+        } else if object["text"].is_object() {
+            ObjectShape::Text(
+                object["text"]["text"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+            )
+        } else {
+            ObjectShape::Rect
+        };
+        let number = |key: &str| object[key].as_f64().unwrap_or(0.0) as f32;
+        self.object(
+            shape,
+            TiledObject {
+                id: json_u32(object, "id"),
+                name: object["name"].as_str().unwrap_or_default().to_string(),
+                class: object["class"]
+                    .as_str()
+                    .or(object["type"].as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+                position: Vec2::new(number("x"), number("y")) + offset,
+                size: Vec2::new(number("width"), number("height")),
+                degrees: number("rotation"),
+                visible: object["visible"].as_bool().unwrap_or(true),
+                shape: ObjectShape::Rect,
+                properties: json_properties(object),
+            },
+        )
+    }
+
+    // fill in what's shared by both formats: tile objects get a size and move to their
+    // top left, and positions move into the tilemap's world space.
+    fn object(&self, shape: ObjectShape, mut object: TiledObject) -> TiledObject {
+        if let ObjectShape::Tile(_) = shape {
+            if object.size == Vec2::ZERO {
+                object.size = self.tile_size;
+            }
+            // Tiled turns tile objects about their bottom left.
+            let up = Vec2::from_angle(object.degrees.to_radians()).rotate(Vec2::NEG_Y);
+            object.position += up * object.size.y;
+        }
+        object.shape = shape;
+        object
+    }
+
+    fn warn(&mut self, warning: String) {
+        self.map
+            .warnings
+            .push(format!("{}: {}", self.path, warning));
+    }
+
+    fn warn_offset(&mut self, name: &str, offset: Vec2) {
+        if offset != Vec2::ZERO {
+            self.warn(format!("tile layer {} is offset, which is ignored", name));
+        }
+    }
+
+    fn finish(mut self) -> Result<TiledMap, Box<dyn Error>> {
+        // infinite maps are as big as their chunks reach.
+        let (min, max) = match self.infinite {
+            true => self
+                .tile_layers
+                .iter()
+                .flat_map(|layer| layer.chunks.iter())
+                .filter(|(_, size, _)| size.cmpgt(UVec2::ZERO).all())
+                .fold(None, |bounds: Option<(IVec2, IVec2)>, (origin, size, _)| {
+                    let end = *origin + size.as_ivec2();
+                    Some(match bounds {
+                        Some((min, max)) => (min.min(*origin), max.max(end)),
+                        None => (*origin, end),
+                    })
+                })
+                .unwrap_or((IVec2::ZERO, IVec2::ZERO)),
+            false => (IVec2::ZERO, self.size.as_ivec2()),
+        };
+
+        let mut tilemap = Tilemap::new(0, (max - min).as_uvec2(), self.tile_size);
+        tilemap.position = min.as_vec2() * self.tile_size;
+        tilemap.tiles = self.map.tilemap.tiles.clone();
+        for source in self.tile_layers.iter() {
+            let layer = tilemap.add_layer(&source.name);
+            tilemap.layers[layer].visible = source.visible;
+            // instance colors multiply premultiplied texels, so fade all four channels.
+            tilemap.layers[layer].color = Vec4::splat(source.opacity);
+            let mut unknown = 0;
+            for (origin, size, gids) in source.chunks.iter() {
+                if size.cmpeq(UVec2::ZERO).any() {
+                    if !gids.is_empty() {
+                        let warning = format!(
+                            "layer {} has tile data with no size at {}, which is skipped",
+                            source.name, origin
+                        );
+                        self.map
+                            .warnings
+                            .push(format!("{}: {}", self.path, warning));
+                    }
+                    continue;
+                }
+                for (index, gid) in gids.iter().enumerate() {
+                    if *gid == 0 {
+                        continue;
+                    }
+                    let cell = self.map.cell(*gid);
+                    if cell.id == 0 {
+                        unknown += 1;
+                        continue;
+                    }
+                    let index = index as u32;
+                    let xy = *origin - min + UVec2::new(index % size.x, index / size.x).as_ivec2();
+                    tilemap.set_cell(layer, xy.as_uvec2(), cell);
+                }
+            }
+            if unknown > 0 {
+                let warning = format!(
+                    "layer {} has {} tiles from no tileset",
+                    source.name, unknown
+                );
+                self.map
+                    .warnings
+                    .push(format!("{}: {}", self.path, warning));
+            }
+            self.map.layer_properties.push(source.properties.clone());
+        }
+        self.map.tilemap = tilemap;
+        Ok(self.map)
+    }
+}
+
+fn xml_tileset(node: Node, path: &str) -> Result<TilesetSource, Box<dyn Error>> {
+    if !node.has_tag_name("tileset") {
+        return Err(format!("{}: not a Tiled tileset", path).into());
+    }
+    let image = |parent: Node| {
+        parent
+            .children()
+            .find(|n| n.has_tag_name("image"))
+            .and_then(|image| {
+                Some(ImageSource {
+                    path: relative_to(path, image.attribute("source")?),
+                    size: Some(UVec2::new(
+                        xml_u32(image, "width"),
+                        xml_u32(image, "height"),
+                    )),
+                })
+            })
+    };
+    let tiles = node
+        .children()
+        .filter(|n| n.has_tag_name("tile"))
+        .map(|tile| TileSource {
+            id: xml_u32(tile, "id"),
+            class: tile
+                .attribute("class")
+                .or(tile.attribute("type"))
+                .unwrap_or_default()
+                .to_string(),
+            image: image(tile),
+            frames: tile
+                .children()
+                .filter(|n| n.has_tag_name("animation"))
+                .flat_map(|animation| animation.children())
+                .filter(|n| n.has_tag_name("frame"))
+                .map(|frame| {
+                    (
+                        xml_u32(frame, "tileid"),
+                        xml_u32(frame, "duration") as f32 / 1000.0,
+                    )
+                })
+                .collect(),
+            properties: xml_properties(tile),
+        })
+        .collect();
+    Ok(TilesetSource {
+        name: node.attribute("name").unwrap_or_default().to_string(),
+        tile_size: UVec2::new(xml_u32(node, "tilewidth"), xml_u32(node, "tileheight")),
+        columns: xml_u32(node, "columns"),
+        tile_count: xml_u32(node, "tilecount"),
+        margin: xml_u32(node, "margin"),
+        spacing: xml_u32(node, "spacing"),
+        image: image(node),
+        tiles,
+        properties: xml_properties(node),
+    })
+}
+
+fn json_tileset(tileset: &Value, path: &str) -> Result<TilesetSource, Box<dyn Error>> {
+    if tileset["type"].as_str().is_some_and(|t| t != "tileset") {
+        return Err(format!("{}: not a Tiled tileset", path).into());
+    }
+    let image = |value: &Value| {
+        Some(ImageSource {
+            path: relative_to(path, value["image"].as_str()?),
+            size: Some(UVec2::new(
+                json_u32(value, "imagewidth"),
+                json_u32(value, "imageheight"),
+            )),
+        })
+    };
+    let tiles = json_array(tileset, "tiles")
+        .iter()
+        .map(|tile| TileSource {
+            id: json_u32(tile, "id"),
+            class: tile["class"]
+                .as_str()
+                .or(tile["type"].as_str())
+                .unwrap_or_default()
+                .to_string(),
+            image: image(tile),
+            frames: json_array(tile, "animation")
+                .iter()
+                .map(|frame| {
+                    (
+                        json_u32(frame, "tileid"),
+                        json_u32(frame, "duration") as f32 / 1000.0,
+                    )
+                })
+                .collect(),
+            properties: json_properties(tile),
+        })
+        .collect();
+    Ok(TilesetSource {
+        name: tileset["name"].as_str().unwrap_or_default().to_string(),
+        tile_size: UVec2::new(
+            json_u32(tileset, "tilewidth"),
+            json_u32(tileset, "tileheight"),
+        ),
+        columns: json_u32(tileset, "columns"),
+        tile_count: json_u32(tileset, "tilecount"),
+        margin: json_u32(tileset, "margin"),
+        spacing: json_u32(tileset, "spacing"),
+        image: image(tileset),
+        tiles,
+        properties: json_properties(tileset),
+    })
+}
+
+// csv or base64 layer data, the latter optionally zlib or gzip compressed.
+fn decode_gids(
+    path: &str,
+    encoding: &str,
+    compression: Option<&str>,
+    text: &str,
+) -> Result<Vec<u32>, Box<dyn Error>> {
+    match encoding {
+        "csv" => Ok(text
+            .split(',')
+            .map(str::trim)
+            .filter(|gid| !gid.is_empty())
+            .map(|gid| gid.parse().unwrap_or(0))
+            .collect()),
+        "base64" => {
+            let bytes = STANDARD.decode(text.trim())?;
+            let mut inflated = vec![];
+            match compression.unwrap_or_default() {
+                "" => inflated = bytes,
+                "zlib" => {
+                    ZlibDecoder::new(bytes.as_slice()).read_to_end(&mut inflated)?;
+                }
+                "gzip" => {
+                    GzDecoder::new(bytes.as_slice()).read_to_end(&mut inflated)?;
+                }
+                other => {
+                    return Err(format!("{}: {} compression is unsupported", path, other).into())
+                }
+            }
+            Ok(inflated
+                .chunks_exact(4)
+                .map(|gid| u32::from_le_bytes([gid[0], gid[1], gid[2], gid[3]]))
+                .collect())
+        }
+        other => Err(format!("{}: {} encoding is unsupported", path, other).into()),
+    }
+}
+
+fn xml_properties(node: Node) -> Properties {
+    let mut properties = HashMap::new();
+    let Some(list) = node.children().find(|n| n.has_tag_name("properties")) else {
+        return properties;
+    };
+    for property in list.children().filter(|n| n.has_tag_name("property")) {
+        let name = property.attribute("name").unwrap_or_default().to_string();
+        // long strings are kept in the element's text instead of `value`.
+        let value = property
+            .attribute("value")
+            .or(property.text())
+            .unwrap_or_default();
+        let value = match property.attribute("type").unwrap_or("string") {
+            "bool" => PropertyValue::Bool(value == "true"),
+            "int" => PropertyValue::Int(value.parse().unwrap_or(0)),
+            "float" => PropertyValue::Float(value.parse().unwrap_or(0.0)),
+            "color" => PropertyValue::Color(parse_color(value).unwrap_or(Vec4::ZERO)),
+            "file" => PropertyValue::File(value.to_string()),
+            "object" => PropertyValue::Object(value.parse().unwrap_or(0)),
+            "class" => PropertyValue::Class(xml_properties(property)),
+            _ => PropertyValue::String(value.to_string()),
+        };
+        properties.insert(name, value);
+    }
+    properties
+}
+
+fn json_properties(value: &Value) -> Properties {
+    json_array(value, "properties")
+        .iter()
+        .map(|property| {
+            let name = property["name"].as_str().unwrap_or_default().to_string();
+            let value = &property["value"];
+            let value = match property["type"].as_str().unwrap_or("string") {
+                "color" => {
+                    PropertyValue::Color(value.as_str().and_then(parse_color).unwrap_or(Vec4::ZERO))
+                }
+                "file" => PropertyValue::File(value.as_str().unwrap_or_default().to_string()),
+                "object" => PropertyValue::Object(value.as_u64().unwrap_or(0) as u32),
+                _ => json_value(value),
+            };
+            (name, value)
+        })
+        .collect()
+}
+
+// a property value by its json type. class members carry no type of their own.
+fn json_value(value: &Value) -> PropertyValue {
+    match value {
+        Value::Bool(b) => PropertyValue::Bool(*b),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => PropertyValue::Int(i),
+            None => PropertyValue::Float(n.as_f64().unwrap_or(0.0)),
+        },
+        Value::Object(members) => PropertyValue::Class(
+            members
+                .iter()
+                .map(|(name, member)| (name.clone(), json_value(member)))
+                .collect(),
+        ),
+        _ => PropertyValue::String(value.as_str().unwrap_or_default().to_string()),
+    }
+}
+
+// "#rrggbb" or "#aarrggbb", to rgba.
+pub(crate) fn parse_color(text: &str) -> Option<Vec4> {
+    let hex = text.trim_start_matches('#');
+    let channel = |i: usize| {
+        u8::from_str_radix(hex.get(i..i + 2)?, 16)
+            .ok()
+            .map(|c| c as f32 / 255.0)
+    };
+    match hex.len() {
+        6 => Some(Vec4::new(channel(0)?, channel(2)?, channel(4)?, 1.0)),
+        8 => Some(Vec4::new(
+            channel(2)?,
+            channel(4)?,
+            channel(6)?,
+            channel(0)?,
+        )),
+        _ => None,
+    }
+}
+
+// "x,y x,y ..." polygon points.
+fn parse_points(text: &str) -> Vec<Vec2> {
+    text.split_whitespace()
+        .filter_map(|point| {
+            let (x, y) = point.split_once(',')?;
+            Some(Vec2::new(x.parse().ok()?, y.parse().ok()?))
+        })
+        .collect()
+}
+
+// `file` as referenced from `from`, with ".." resolved so it can be found in packs.
+pub(crate) fn relative_to(from: &str, file: &str) -> String {
+    let joined = Path::new(from).parent().unwrap_or(Path::new("")).join(file);
+    let joined = joined.to_string_lossy().replace('\\', "/");
+    let mut parts: Vec<&str> = vec![];
+    for part in joined.split('/') {
+        match part {
+            "." | "" => (),
+            ".." if parts.last().is_some_and(|last| *last != "..") => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    let path = parts.join("/");
+    match from.starts_with('/') {
+        true => format!("/{}", path),
+        false => path,
+    }
+}
+
+fn xml_u32(node: Node, key: &str) -> u32 {
+    node.attribute(key)
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0)
+}
+
+fn xml_i32(node: Node, key: &str) -> i32 {
+    node.attribute(key)
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0)
+}
+
+fn xml_f32(node: Node, key: &str, default: f32) -> f32 {
+    node.attribute(key)
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+pub(crate) fn json_u32(value: &Value, key: &str) -> u32 {
+    value[key].as_u64().unwrap_or(0) as u32
+}
+
+pub(crate) fn json_i32(value: &Value, key: &str) -> i32 {
+    value[key].as_i64().unwrap_or(0) as i32
+}
+
+pub(crate) fn json_array<'v>(value: &'v Value, key: &str) -> &'v [Value] {
+    value[key].as_array().map_or(&[], |array| array.as_slice())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{
+        write::{GzEncoder, ZlibEncoder},
+        Compression,
+    };
+
+    use super::*;
+
+    fn gid_bytes(gids: &[u32]) -> Vec<u8> {
+        gids.iter().flat_map(|gid| gid.to_le_bytes()).collect()
+    }
+
+    fn zlib_base64(gids: &[u32]) -> String {
+        let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+        encoder.write_all(&gid_bytes(gids)).unwrap();
+        STANDARD.encode(encoder.finish().unwrap())
+    }
+
+    #[test]
+    fn decodes_csv() {
+        let gids = decode_gids("map.tmx", "csv", None, "\n1,2,\n0,3\n").unwrap();
+        assert_eq!(gids, vec![1, 2, 0, 3]);
+    }
+
+    #[test]
+    fn decodes_uncompressed_base64() {
+        let text = STANDARD.encode(gid_bytes(&[5, GID_FLIP_X | 1]));
+        let gids = decode_gids("map.tmx", "base64", None, &text).unwrap();
+        assert_eq!(gids, vec![5, GID_FLIP_X | 1]);
+    }
+
+    #[test]
+    fn decodes_zlib_and_gzip_base64() {
+        let gids = [1, 0, GID_FLIP_Y | 2, 7];
+        let zlib = decode_gids("map.tmx", "base64", Some("zlib"), &zlib_base64(&gids));
+        assert_eq!(zlib.unwrap(), gids);
+
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(&gid_bytes(&gids)).unwrap();
+        let text = STANDARD.encode(encoder.finish().unwrap());
+        let gzip = decode_gids("map.tmx", "base64", Some("gzip"), &text);
+        assert_eq!(gzip.unwrap(), gids);
+    }
+
+    #[test]
+    fn rejects_unsupported_compression() {
+        assert!(decode_gids("map.tmx", "base64", Some("zstd"), "").is_err());
+    }
+
+    // a 2x2 map over a four tile tileset, its layer zlib compressed.
+    fn tmx(gids: &[u32]) -> String {
+        format!(
+            r#"<map orientation="orthogonal" width="2" height="2" tilewidth="8" tileheight="8">
+                <tileset firstgid="1" name="tiles" tilewidth="8" tileheight="8" tilecount="4" columns="2">
+                    <image source="tiles.png" width="16" height="16"/>
+                </tileset>
+                <layer name="ground" width="2" height="2">
+                    <data encoding="base64" compression="zlib">{}</data>
+                </layer>
+            </map>"#,
+            zlib_base64(gids)
+        )
+    }
+
+    #[test]
+    fn loads_a_compressed_layer_with_flips() {
+        let gids = [1, GID_FLIP_X | 2, GID_FLIP_Y | GID_FLIP_DIAGONAL | 3, 0];
+        let map = TiledMap::from_tmx("maps/map.tmx", &tmx(&gids), &Vfs::new()).unwrap();
+        let layer = map.tilemap.layer_index("ground").unwrap();
+        let cell = |x, y| map.tilemap.cell(layer, UVec2::new(x, y)).unwrap();
+
+        assert_eq!(cell(0, 0).id, map.cell(1).id);
+        assert_eq!(cell(0, 0).flags, TileFlags::NONE);
+
+        assert_eq!(cell(1, 0).id, map.cell(2).id);
+        assert!(cell(1, 0).flags.contains(TileFlags::FLIP_X));
+        assert!(!cell(1, 0).flags.contains(TileFlags::FLIP_Y));
+
+        assert_eq!(cell(0, 1).id, map.cell(3).id);
+        assert!(cell(0, 1)
+            .flags
+            .contains(TileFlags(TileFlags::FLIP_Y.0 | TileFlags::FLIP_DIAGONAL.0)));
+        assert!(!cell(0, 1).flags.contains(TileFlags::FLIP_X));
+
+        assert_eq!(cell(1, 1).id, 0);
+        assert_eq!(map.sheets[0].path, "maps/tiles.png");
+    }
+
+    #[test]
+    fn unknown_gids_are_empty_cells() {
+        let map = TiledMap::from_tmx("map.tmx", &tmx(&[0, 0, 0, 0]), &Vfs::new()).unwrap();
+        assert_eq!(map.cell(GID_FLIP_X | 50), Cell::default());
+        // the hexagonal rotation bit is masked off with the flips.
+        assert_eq!(map.cell(0x1000_0000 | 4).id, map.cell(4).id);
+        assert_ne!(map.cell(4).id, 0);
+    }
+
+    #[test]
+    fn rejects_a_tileset_with_no_tile_size() {
+        let source = tmx(&[0, 0, 0, 0]).replace(
+            r#"name="tiles" tilewidth="8" tileheight="8" tilecount="4" columns="2""#,
+            r#"name="tiles""#,
+        );
+        assert!(TiledMap::from_tmx("map.tmx", &source, &Vfs::new()).is_err());
+    }
+
+    #[test]
+    fn rejects_an_external_tileset_with_a_zero_tile_size() {
+        let mut vfs = Vfs::new();
+        vfs.mount_embedded(
+            "maps/tiles.tsx",
+            br#"<tileset name="tiles" tilewidth="0" tileheight="0">
+                <image source="tiles.png" width="16" height="16"/>
+            </tileset>"#,
+        );
+        let source = r#"<map orientation="orthogonal" width="1" height="1" tilewidth="8" tileheight="8">
+            <tileset firstgid="1" source="tiles.tsx"/>
+        </map>"#;
+        let result = TiledMap::from_tmx("maps/map.tmx", source, &vfs);
+        assert!(result.is_err_and(|e| e.to_string().contains("no tile size")));
+    }
+
+    #[test]
+    fn skips_layers_with_no_size() {
+        let source = tmx(&[1, 2, 3, 4]).replace(
+            r#"<layer name="ground" width="2" height="2">"#,
+            r#"<layer name="ground" width="0" height="2">"#,
+        );
+        let map = TiledMap::from_tmx("map.tmx", &source, &Vfs::new()).unwrap();
+        let layer = map.tilemap.layer_index("ground").unwrap();
+        assert_eq!(map.tilemap.tile(layer, UVec2::ZERO), 0);
+        assert_eq!(map.warnings.len(), 1);
+    }
+
+    #[test]
+    fn skips_infinite_chunks_with_no_size() {
+        let source = r#"<map orientation="orthogonal" width="2" height="2" tilewidth="8" tileheight="8" infinite="1">
+                <tileset firstgid="1" name="tiles" tilewidth="8" tileheight="8" tilecount="4" columns="2">
+                    <image source="tiles.png" width="16" height="16"/>
+                </tileset>
+                <layer name="ground" width="2" height="2">
+                    <data encoding="csv">
+                        <chunk x="0" y="0" width="2" height="1">1,2</chunk>
+                        <chunk x="4" y="4" height="1">3,4</chunk>
+                    </data>
+                </layer>
+            </map>"#;
+        let map = TiledMap::from_tmx("map.tmx", source, &Vfs::new()).unwrap();
+        assert_eq!(map.tilemap.size, UVec2::new(2, 1));
+        assert_eq!(map.warnings.len(), 1);
+    }
+}
@@ -0,0 +1,585 @@
+use std::collections::{HashMap, VecDeque};
+
+use std::mem::size_of;
+
+use glam::{IVec2, UVec2, Vec2, Vec4};
+use wgpu::{Buffer, BufferAddress, BufferDescriptor, BufferUsages, Device, Queue};
+
+use crate::autotile::AutotileRule;
+use crate::geo::GeoManager;
+use crate::types::{flip_mat4, ComponentTransform, DrawRun, InstanceData, SpriteRef};
+
+// tiles per chunk side. chunks are the unit of culling and rebuilding.
+pub const CHUNK_SIZE: u32 = 16;
+const CHUNK_AREA: usize = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+// an index into Tilemap::tiles, plus one. 0 is an empty cell.
+pub type TileId = u32;
+
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct TileFlags(pub u32);
+
+impl TileFlags {
+    pub const NONE: TileFlags = TileFlags(0);
+    pub const SOLID: TileFlags = TileFlags(1);
+    pub const ANIMATED: TileFlags = TileFlags(1 << 1);
+    // mirror the sprite. a diagonal flip swaps its x and y first, so together with the
+    // others it gives all eight rotations and reflections, as in Tiled.
+    pub const FLIP_X: TileFlags = TileFlags(1 << 2);
+    pub const FLIP_Y: TileFlags = TileFlags(1 << 3);
+    pub const FLIP_DIAGONAL: TileFlags = TileFlags(1 << 4);
+
+    pub fn contains(&self, flags: TileFlags) -> bool {
+        self.0 & flags.0 == flags.0
+    }
+
+    pub fn insert(&mut self, flags: TileFlags) {
+        self.0 |= flags.0;
+    }
+
+    pub fn remove(&mut self, flags: TileFlags) {
+        self.0 &= !flags.0;
+    }
+}
+
+// a kind of tile: the sprite it draws and the flags new cells of it get. animated tiles
+// cycle through `frames`, each shown for its duration in seconds.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TileDef {
+    pub sprite: SpriteRef,
+    pub flags: TileFlags,
+    pub frames: Vec<(SpriteRef, f32)>,
+}
+
+impl TileDef {
+    pub fn new(sprite: SpriteRef) -> Self {
+        Self {
+            sprite,
+            flags: TileFlags::NONE,
+            frames: vec![],
+        }
+    }
+
+    pub fn solid(mut self) -> Self {
+        self.flags.insert(TileFlags::SOLID);
+        self
+    }
+
+    pub fn animated(mut self, frames: Vec<(SpriteRef, f32)>) -> Self {
+        self.flags.insert(TileFlags::ANIMATED);
+        self.frames = frames;
+        self
+    }
+
+    fn sprite_at(&self, time: f32) -> SpriteRef {
+        let total: f32 = self.frames.iter().map(|(_, duration)| duration).sum();
+        if total <= 0.0 {
+            return self.sprite;
+        }
+        let mut time = time % total;
+        for (sprite, duration) in self.frames.iter() {
+            if time < *duration {
+                return *sprite;
+            }
+            time -= duration;
+        }
+        self.sprite
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct Cell {
+    pub id: TileId,
+    pub flags: TileFlags,
+}
+
+pub struct TileLayer {
+    pub name: String,
+    pub cells: Vec<Cell>,
+    pub visible: bool,
+    pub color: Vec4,
+    // the layer the tiles draw on among instances; see SortMode.
+    pub draw_layer: f32,
+}
+
+struct Chunk {
+    // the pool slot holding the chunk's instances while it's visible.
+    slot: Option<usize>,
+    // instance count per layer in the chunk's slot, rebuilt when dirty.
+    counts: Vec<u32>,
+    dirty: bool,
+    animated: bool,
+}
+
+// a grid of tiles in layers, drawn from one group's sheet through its unit-square pipeline.
+// the map is split into CHUNK_SIZE square chunks. a chunk the camera sees takes a slot from a
+// pool in the map's own instance buffer, and gives it back once it's culled, so the buffer
+// only grows to the most chunks seen at once. a chunk is uploaded again only when it changes
+// or comes back into view, and is drawn ordered with the group's instances by layer.
+//
+// cell (0, 0) is at `position`, and cells follow world +x and +y. add the map to
+// Context::tilemaps for it to be drawn.
+pub struct Tilemap {
+    pub group: usize,
+    pub size: UVec2,
+    pub tile_size: Vec2,
+    pub position: Vec2,
+    pub tiles: Vec<TileDef>,
+    pub layers: Vec<TileLayer>,
+    pub autotiles: Vec<AutotileRule>,
+    // the autotile rule per tile, so retiling doesn't search every rule.
+    terrains: HashMap<TileId, usize>,
+    chunks: Vec<Chunk>,
+    time: f32,
+    built_y_down: bool,
+    buffer: Option<Buffer>,
+    // chunk slots the buffer has room for, and the layer count it was sized for.
+    capacity: usize,
+    built_layers: usize,
+    // slots handed out so far, and those given back by culled chunks.
+    slot_count: usize,
+    free_slots: Vec<usize>,
+    // the visible chunks' instances, found by prepare.
+    runs: Vec<DrawRun>,
+}
+
+impl Tilemap {
+    // `size` in tiles, `tile_size` in world pixels.
+    pub fn new(group: usize, size: UVec2, tile_size: Vec2) -> Self {
+        let chunk_count = (size.as_vec2() / CHUNK_SIZE as f32).ceil().as_uvec2();
+        let chunks = (0..chunk_count.x * chunk_count.y)
+            .map(|_| Chunk {
+                slot: None,
+                counts: vec![],
+                dirty: true,
+                animated: false,
+            })
+            .collect();
+        Self {
+            group,
+            size,
+            tile_size,
+            position: Vec2::ZERO,
+            tiles: vec![],
+            layers: vec![],
+            autotiles: vec![],
+            terrains: HashMap::new(),
+            chunks,
+            time: 0.0,
+            built_y_down: true,
+            buffer: None,
+            capacity: 0,
+            built_layers: 0,
+            slot_count: 0,
+            free_slots: vec![],
+            runs: vec![],
+        }
+    }
+
+    pub fn add_tile(&mut self, def: TileDef) -> TileId {
+        self.tiles.push(def);
+        self.tiles.len() as TileId
+    }
+
+    pub fn tile_def(&self, id: TileId) -> Option<&TileDef> {
+        self.tiles.get((id as usize).checked_sub(1)?)
+    }
+
+    pub fn add_layer(&mut self, name: &str) -> usize {
+        self.layers.push(TileLayer {
+            name: name.to_string(),
+            cells: vec![Cell::default(); (self.size.x * self.size.y) as usize],
+            visible: true,
+            color: Vec4::ONE,
+            draw_layer: 0.0,
+        });
+        self.mark_all_dirty();
+        self.layers.len() - 1
+    }
+
+    pub fn layer_index(&self, name: &str) -> Option<usize> {
+        self.layers.iter().position(|layer| layer.name == name)
+    }
+
+    pub fn contains(&self, xy: UVec2) -> bool {
+        xy.x < self.size.x && xy.y < self.size.y
+    }
+
+    pub fn cell(&self, layer: usize, xy: UVec2) -> Option<Cell> {
+        if !self.contains(xy) {
+            return None;
+        }
+        Some(self.layers.get(layer)?.cells[self.cell_index(xy)])
+    }
+
+    pub fn tile(&self, layer: usize, xy: UVec2) -> TileId {
+        self.cell(layer, xy).map_or(0, |cell| cell.id)
+    }
+
+    // place a tile, giving the cell its definition's flags. 0 clears the cell. autotiled
+    // cells around it are re-picked, and so is the tile itself if it's autotiled.
+    pub fn set_tile(&mut self, layer: usize, xy: UVec2, id: TileId) {
+        let flags = self.tile_def(id).map_or(TileFlags::NONE, |def| def.flags);
+        self.set_cell(layer, xy, Cell { id, flags });
+        for y in -1..=1 {
+            for x in -1..=1 {
+                let near = xy.as_ivec2() + IVec2::new(x, y);
+                if near.cmpge(IVec2::ZERO).all() {
+                    self.retile(layer, near.as_uvec2());
+                }
+            }
+        }
+    }
+
+    pub fn add_autotile(&mut self, rule: AutotileRule) -> usize {
+        let terrain = self.autotiles.len();
+        for id in rule.ids() {
+            self.terrains.entry(id).or_insert(terrain);
+        }
+        self.autotiles.push(rule);
+        terrain
+    }
+
+    // re-pick every autotiled cell of a layer, e.g. after changing the rules or setting
+    // cells directly.
+    pub fn autotile(&mut self, layer: usize) {
+        self.terrains.clear();
+        for rule in std::mem::take(&mut self.autotiles) {
+            self.add_autotile(rule);
+        }
+        for y in 0..self.size.y {
+            for x in 0..self.size.x {
+                self.retile(layer, UVec2::new(x, y));
+            }
+        }
+    }
+
+    // the autotile rule a tile belongs to, if any.
+    pub fn terrain(&self, id: TileId) -> Option<usize> {
+        match id {
+            0 => None,
+            id => self.terrains.get(&id).copied(),
+        }
+    }
+
+    // the same tile, or two tiles of one autotiled terrain.
+    pub fn same_terrain(&self, a: TileId, b: TileId) -> bool {
+        a == b
+            || self
+                .terrain(a)
+                .is_some_and(|terrain| self.terrain(b) == Some(terrain))
+    }
+
+    pub fn set_cell(&mut self, layer: usize, xy: UVec2, cell: Cell) {
+        if !self.contains(xy) || layer >= self.layers.len() {
+            return;
+        }
+        let index = self.cell_index(xy);
+        self.layers[layer].cells[index] = cell;
+        let chunk = self.chunk_index(xy);
+        self.chunks[chunk].dirty = true;
+    }
+
+    pub fn flags(&self, layer: usize, xy: UVec2) -> TileFlags {
+        self.cell(layer, xy)
+            .map_or(TileFlags::NONE, |cell| cell.flags)
+    }
+
+    // flags belong to the cell, so they can be changed per tile, e.g. an opened door.
+    pub fn set_flags(&mut self, layer: usize, xy: UVec2, flags: TileFlags) {
+        if let Some(cell) = self.cell(layer, xy) {
+            self.set_cell(layer, xy, Cell { flags, ..cell });
+        }
+    }
+
+    // whether any layer has a solid cell at `xy`. cells outside the map aren't solid.
+    pub fn is_solid(&self, xy: UVec2) -> bool {
+        (0..self.layers.len()).any(|layer| self.flags(layer, xy).contains(TileFlags::SOLID))
+    }
+
+    pub fn fill(&mut self, layer: usize, id: TileId) {
+        for y in 0..self.size.y {
+            for x in 0..self.size.x {
+                self.set_tile(layer, UVec2::new(x, y), id);
+            }
+        }
+    }
+
+    // replace the 4-connected area of matching tiles (or terrain) around `start` with `id`,
+    // returning how many cells changed.
+    pub fn flood_fill(&mut self, layer: usize, start: UVec2, id: TileId) -> usize {
+        let target = match self.cell(layer, start) {
+            Some(cell) if !self.same_terrain(cell.id, id) => cell.id,
+            _ => return 0,
+        };
+        let mut count = 0;
+        let mut queue = VecDeque::from([start]);
+        while let Some(xy) = queue.pop_front() {
+            if !self.contains(xy) || !self.same_terrain(self.tile(layer, xy), target) {
+                continue;
+            }
+            self.set_tile(layer, xy, id);
+            count += 1;
+            queue.push_back(xy + UVec2::X);
+            queue.push_back(xy + UVec2::Y);
+            if xy.x > 0 {
+                queue.push_back(xy - UVec2::X);
+            }
+            if xy.y > 0 {
+                queue.push_back(xy - UVec2::Y);
+            }
+        }
+        count
+    }
+
+    // the cell under a world position, if it's on the map.
+    pub fn world_to_tile(&self, world: Vec2) -> Option<UVec2> {
+        let xy = ((world - self.position) / self.tile_size).floor();
+        if xy.cmplt(Vec2::ZERO).any() {
+            return None;
+        }
+        Some(xy.as_uvec2()).filter(|xy| self.contains(*xy))
+    }
+
+    // the corner of a cell nearest `position`.
+    pub fn tile_to_world(&self, xy: UVec2) -> Vec2 {
+        self.position + xy.as_vec2() * self.tile_size
+    }
+
+    // rebuild every chunk, e.g. after moving the map or changing its tile definitions.
+    pub fn mark_all_dirty(&mut self) {
+        for chunk in self.chunks.iter_mut() {
+            chunk.dirty = true;
+        }
+    }
+
+    // advance tile animations. called from Context::update.
+    pub fn update(&mut self, delta_time: f32) {
+        self.time += delta_time;
+        for chunk in self.chunks.iter_mut() {
+            chunk.dirty |= chunk.animated;
+        }
+    }
+
+    // upload the visible chunks that changed and find the runs to draw this frame. called
+    // from Context::render.
+    pub fn prepare(&mut self, geos: &GeoManager, device: &Device, queue: &Queue) {
+        let y_down = geos.camera.y_down;
+        if y_down != self.built_y_down {
+            self.built_y_down = y_down;
+            self.mark_all_dirty();
+        }
+        let view_min = geos.camera.position;
+        let view_max = view_min + geos.extent.as_vec2() / geos.camera.zoom;
+        let chunk_world = self.tile_size * CHUNK_SIZE as f32;
+        let chunk_count = self.chunk_count();
+        let first = ((view_min - self.position) / chunk_world)
+            .floor()
+            .max(Vec2::ZERO)
+            .as_uvec2();
+        let last = ((view_max - self.position) / chunk_world)
+            .ceil()
+            .max(Vec2::ZERO)
+            .as_uvec2()
+            .min(chunk_count);
+
+        let mut visible = vec![];
+        for y in first.y..last.y {
+            for x in first.x..last.x {
+                visible.push((y * chunk_count.x + x) as usize);
+            }
+        }
+
+        // give culled chunks' slots back before handing them to newly visible chunks.
+        let mut in_view = vec![false; self.chunks.len()];
+        for chunk in visible.iter() {
+            in_view[*chunk] = true;
+        }
+        for (index, chunk) in self.chunks.iter_mut().enumerate() {
+            if in_view[index] {
+                continue;
+            }
+            if let Some(slot) = chunk.slot.take() {
+                self.free_slots.push(slot);
+                chunk.dirty = true;
+            }
+        }
+        for chunk in visible.iter() {
+            if self.chunks[*chunk].slot.is_none() {
+                let slot = self.free_slots.pop().unwrap_or_else(|| {
+                    self.slot_count += 1;
+                    self.slot_count - 1
+                });
+                self.chunks[*chunk].slot = Some(slot);
+                self.chunks[*chunk].dirty = true;
+            }
+        }
+        self.reserve(device);
+
+        // draw in slot order, so chunks in neighbouring slots merge into one run.
+        visible.sort_by_key(|chunk| self.chunks[*chunk].slot);
+        for chunk in visible.iter() {
+            if self.chunks[*chunk].dirty {
+                self.build_chunk(*chunk, geos, queue, y_down);
+            }
+        }
+        self.runs.clear();
+        for layer in 0..self.layers.len() {
+            if !self.layers[layer].visible {
+                continue;
+            }
+            let draw_layer = self.layers[layer].draw_layer;
+            for chunk in visible.iter() {
+                let start = self.slot_start(*chunk, layer) as u32;
+                let end = start + self.chunks[*chunk].counts[layer];
+                match self.runs.last_mut() {
+                    _ if start == end => (),
+                    Some(run) if run.layer == draw_layer && run.instances.end == start => {
+                        run.instances.end = end;
+                    }
+                    _ => self.runs.push(DrawRun {
+                        layer: draw_layer,
+                        instances: start..end,
+                    }),
+                }
+            }
+        }
+    }
+
+    // the instance buffer holding the chunks, once prepared.
+    pub fn buffer(&self) -> Option<&Buffer> {
+        self.buffer.as_ref()
+    }
+
+    // runs of `buffer` to draw with the group's pipeline, from the last prepare.
+    pub fn draw_runs(&self) -> &[DrawRun] {
+        &self.runs
+    }
+
+    // grow the buffer to fit every slot handed out. the old contents aren't copied over, so
+    // visible chunks are built again.
+    fn reserve(&mut self, device: &Device) {
+        let layers = self.layers.len().max(1);
+        if self.buffer.is_some() && self.slot_count <= self.capacity && layers == self.built_layers
+        {
+            return;
+        }
+        let capacity = self
+            .slot_count
+            .max(self.capacity)
+            .max(1)
+            .next_power_of_two();
+        let size = (capacity * layers * CHUNK_AREA * size_of::<InstanceData>()) as BufferAddress;
+        self.buffer = Some(device.create_buffer(&BufferDescriptor {
+            label: Some("tilemap instance buffer"),
+            size,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        }));
+        self.capacity = capacity;
+        self.built_layers = layers;
+        self.mark_all_dirty();
+    }
+
+    fn build_chunk(&mut self, chunk_index: usize, geos: &GeoManager, queue: &Queue, y_down: bool) {
+        let sheet = &geos.instance_groups[self.group].sheet;
+        let chunk_xy = UVec2::new(
+            chunk_index as u32 % self.chunk_count().x,
+            chunk_index as u32 / self.chunk_count().x,
+        ) * CHUNK_SIZE;
+        let chunk_end = (chunk_xy + CHUNK_SIZE).min(self.size);
+        let mut counts = vec![];
+        let mut animated = false;
+        for (layer_index, layer) in self.layers.iter().enumerate() {
+            let mut data = vec![];
+            for y in chunk_xy.y..chunk_end.y {
+                for x in chunk_xy.x..chunk_end.x {
+                    let xy = UVec2::new(x, y);
+                    let cell = layer.cells[self.cell_index(xy)];
+                    let Some(def) = self.tile_def(cell.id) else {
+                        continue;
+                    };
+                    animated |= !def.frames.is_empty();
+                    let sprite = def.sprite_at(self.time);
+                    // instances are placed by their top left on screen.
+                    let mut corner = self.tile_to_world(xy);
+                    if !y_down {
+                        corner.y += self.tile_size.y;
+                    }
+                    data.push(InstanceData {
+                        transform: ComponentTransform::world_rect(corner, self.tile_size)
+                            .to_instance_mat4(y_down),
+                        tex_transform: sheet
+                            .layer_cluster_sub_transform(sprite.layer, sprite.cluster, sprite.sub)
+                            .to_mat4()
+                            * flip_mat4(
+                                cell.flags.contains(TileFlags::FLIP_X),
+                                cell.flags.contains(TileFlags::FLIP_Y),
+                                cell.flags.contains(TileFlags::FLIP_DIAGONAL),
+                            ),
+                        color: layer.color,
+                        tex_layer: sprite.layer as u32,
+                        padding: [0; 3],
+                    });
+                }
+            }
+            if let (Some(buffer), false) = (&self.buffer, data.is_empty()) {
+                queue.write_buffer(
+                    buffer,
+                    (self.slot_start(chunk_index, layer_index) * size_of::<InstanceData>())
+                        as BufferAddress,
+                    bytemuck::cast_slice(&data),
+                );
+            }
+            counts.push(data.len() as u32);
+        }
+        let chunk = &mut self.chunks[chunk_index];
+        chunk.counts = counts;
+        chunk.dirty = false;
+        chunk.animated = animated;
+    }
+
+    fn retile(&mut self, layer: usize, xy: UVec2) {
+        let id = self.tile(layer, xy);
+        let Some(terrain) = self.terrain(id) else {
+            return;
+        };
+        let rule = &self.autotiles[terrain];
+        let mask = rule.neighbours.mask(|offset| {
+            let near = xy.as_ivec2() + offset;
+            if near.cmplt(IVec2::ZERO).any() || !self.contains(near.as_uvec2()) {
+                return rule.edges_match;
+            }
+            self.terrain(self.tile(layer, near.as_uvec2())) == Some(terrain)
+        });
+        match rule.pick(mask, xy) {
+            Some(picked) if picked != id => {
+                let flags = self
+                    .tile_def(picked)
+                    .map_or(TileFlags::NONE, |def| def.flags);
+                self.set_cell(layer, xy, Cell { id: picked, flags });
+            }
+            _ => (),
+        }
+    }
+
+    // the first instance of a resident chunk's slot for a layer. a layer's slots are in a
+    // row, so chunks in neighbouring slots draw together.
+    fn slot_start(&self, chunk_index: usize, layer: usize) -> usize {
+        let slot = self.chunks[chunk_index].slot.unwrap_or(0);
+        (layer * self.capacity + slot) * CHUNK_AREA
+    }
+
+    fn chunk_count(&self) -> UVec2 {
+        (self.size.as_vec2() / CHUNK_SIZE as f32).ceil().as_uvec2()
+    }
+
+    fn cell_index(&self, xy: UVec2) -> usize {
+        (xy.y * self.size.x + xy.x) as usize
+    }
+
+    fn chunk_index(&self, xy: UVec2) -> usize {
+        let chunk = xy / CHUNK_SIZE;
+        (chunk.y * self.chunk_count().x + chunk.x) as usize
+    }
+}
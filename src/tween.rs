@@ -0,0 +1,751 @@
+use std::{error::Error, f32::consts::TAU};
+
+use glam::{Vec2, Vec4};
+use glyphon::Color;
+
+use crate::geo::GeoManager;
+use crate::hierarchy::NodeRef;
+use crate::text::TextCollection;
+use crate::window::Context;
+
+// runs once an animation has played through. like the loading hook, it's taken out of the
+// context while it runs, so it can start more tweens.
+pub type TweenCallback = Box<dyn FnMut(&mut Context) -> Result<(), Box<dyn Error>>>;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Easing {
+    Linear,
+    QuadIn,
+    QuadOut,
+    QuadInOut,
+    CubicIn,
+    CubicOut,
+    CubicInOut,
+    ElasticIn,
+    ElasticOut,
+    ElasticInOut,
+    BounceIn,
+    BounceOut,
+    BounceInOut,
+    BackIn,
+    BackOut,
+    BackInOut,
+}
+
+impl Easing {
+    // maps progress 0..1 to eased progress. elastic and back overshoot past 0 and 1.
+    pub fn apply(&self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        // back overshoot, and its in-out variant.
+        let (c1, c2) = (1.70158, 1.70158 * 1.525);
+        match self {
+            Easing::Linear => t,
+            Easing::QuadIn => t * t,
+            Easing::QuadOut => 1.0 - (1.0 - t).powi(2),
+            Easing::QuadInOut if t < 0.5 => 2.0 * t * t,
+            Easing::QuadInOut => 1.0 - (-2.0 * t + 2.0).powi(2) / 2.0,
+            Easing::CubicIn => t.powi(3),
+            Easing::CubicOut => 1.0 - (1.0 - t).powi(3),
+            Easing::CubicInOut if t < 0.5 => 4.0 * t.powi(3),
+            Easing::CubicInOut => 1.0 - (-2.0 * t + 2.0).powi(3) / 2.0,
+            Easing::ElasticIn | Easing::ElasticOut | Easing::ElasticInOut
+                if t == 0.0 || t == 1.0 =>
+            {
+                t
+            }
+            Easing::ElasticIn => {
+                -(2.0f32.powf(10.0 * t - 10.0)) * ((10.0 * t - 10.75) * TAU / 3.0).sin()
+            }
+            Easing::ElasticOut => {
+                2.0f32.powf(-10.0 * t) * ((10.0 * t - 0.75) * TAU / 3.0).sin() + 1.0
+            }
+            Easing::ElasticInOut => {
+                let wave = ((20.0 * t - 11.125) * TAU / 4.5).sin();
+                if t < 0.5 {
+                    -(2.0f32.powf(20.0 * t - 10.0) * wave) / 2.0
+                } else {
+                    2.0f32.powf(-20.0 * t + 10.0) * wave / 2.0 + 1.0
+                }
+            }
+            Easing::BounceIn => 1.0 - bounce_out(1.0 - t),
+            Easing::BounceOut => bounce_out(t),
+            Easing::BounceInOut if t < 0.5 => (1.0 - bounce_out(1.0 - 2.0 * t)) / 2.0,
+            Easing::BounceInOut => (1.0 + bounce_out(2.0 * t - 1.0)) / 2.0,
+            Easing::BackIn => (c1 + 1.0) * t.powi(3) - c1 * t * t,
+            Easing::BackOut => 1.0 + (c1 + 1.0) * (t - 1.0).powi(3) + c1 * (t - 1.0).powi(2),
+            Easing::BackInOut if t < 0.5 => (2.0 * t).powi(2) * ((c2 + 1.0) * 2.0 * t - c2) / 2.0,
+            Easing::BackInOut => {
+                ((2.0 * t - 2.0).powi(2) * ((c2 + 1.0) * (t * 2.0 - 2.0) + c2) + 2.0) / 2.0
+            }
+        }
+    }
+}
+
+fn bounce_out(t: f32) -> f32 {
+    let (n, d) = (7.5625, 2.75);
+    if t < 1.0 / d {
+        n * t * t
+    } else if t < 2.0 / d {
+        let t = t - 1.5 / d;
+        n * t * t + 0.75
+    } else if t < 2.5 / d {
+        let t = t - 2.25 / d;
+        n * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / d;
+        n * t * t + 0.984375
+    }
+}
+
+// what a tween animates. values are carried in a Vec4:
+//   Position, top left in world pixels (x, y), or logical pixels for text labels,
+//   Size, in world pixels (x, y), or a text label's scale (x),
+//   Rotation, in degrees (x), about the instance's pivot,
+//   Color, rgba 0..1.
+// text labels don't rotate, so Rotation does nothing to them.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TweenProperty {
+    Position,
+    Size,
+    Rotation,
+    Color,
+}
+
+pub struct Tween {
+    pub target: NodeRef,
+    pub property: TweenProperty,
+    // where to start from. when None, the value the property has once the tween starts.
+    pub from: Option<Vec4>,
+    pub to: Vec4,
+    pub duration: f32,
+    pub easing: Easing,
+    start: Option<Vec4>,
+}
+
+impl Tween {
+    pub fn new(target: NodeRef, property: TweenProperty, to: Vec4, duration: f32) -> Self {
+        Self {
+            target,
+            property,
+            from: None,
+            to,
+            duration,
+            easing: Easing::Linear,
+            start: None,
+        }
+    }
+
+    pub fn position(target: NodeRef, to: Vec2, duration: f32) -> Self {
+        Self::new(
+            target,
+            TweenProperty::Position,
+            to.extend(0.0).extend(0.0),
+            duration,
+        )
+    }
+
+    pub fn size(target: NodeRef, to: Vec2, duration: f32) -> Self {
+        Self::new(
+            target,
+            TweenProperty::Size,
+            to.extend(0.0).extend(0.0),
+            duration,
+        )
+    }
+
+    pub fn rotation(target: NodeRef, degrees: f32, duration: f32) -> Self {
+        Self::new(
+            target,
+            TweenProperty::Rotation,
+            Vec4::new(degrees, 0.0, 0.0, 0.0),
+            duration,
+        )
+    }
+
+    pub fn color(target: NodeRef, to: Vec4, duration: f32) -> Self {
+        Self::new(target, TweenProperty::Color, to, duration)
+    }
+
+    pub fn from(mut self, from: Vec4) -> Self {
+        self.from = Some(from);
+        self
+    }
+
+    pub fn ease(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+}
+
+// tweens put together. sequences play their parts one after another and parallel groups
+// all at once; repeats with yoyo play every other cycle backwards.
+pub enum Animation {
+    Tween(Tween),
+    Delay(f32),
+    Sequence(Vec<Animation>),
+    Parallel(Vec<Animation>),
+    Repeat {
+        animation: Box<Animation>,
+        // None repeats forever.
+        times: Option<u32>,
+        yoyo: bool,
+    },
+}
+
+impl From<Tween> for Animation {
+    fn from(tween: Tween) -> Self {
+        Animation::Tween(tween)
+    }
+}
+
+impl Animation {
+    pub fn sequence(animations: Vec<Animation>) -> Self {
+        Animation::Sequence(animations)
+    }
+
+    pub fn parallel(animations: Vec<Animation>) -> Self {
+        Animation::Parallel(animations)
+    }
+
+    pub fn delay(seconds: f32) -> Self {
+        Animation::Delay(seconds)
+    }
+
+    pub fn repeat(self, times: u32) -> Self {
+        self.with_repeat(Some(times), None)
+    }
+
+    pub fn repeat_forever(self) -> Self {
+        self.with_repeat(None, None)
+    }
+
+    // there and back again. combine with repeat for more round trips, counting each way
+    // as one cycle.
+    pub fn yoyo(self) -> Self {
+        self.with_repeat(Some(2), Some(true))
+    }
+
+    fn with_repeat(self, new_times: Option<u32>, new_yoyo: Option<bool>) -> Self {
+        match self {
+            Animation::Repeat {
+                animation,
+                times,
+                yoyo,
+            } => Animation::Repeat {
+                animation,
+                times: match new_yoyo {
+                    Some(_) => times,
+                    None => new_times,
+                },
+                yoyo: new_yoyo.unwrap_or(yoyo),
+            },
+            animation => Animation::Repeat {
+                animation: Box::new(animation),
+                times: new_times,
+                yoyo: new_yoyo.unwrap_or(false),
+            },
+        }
+    }
+
+    // in seconds. infinite when anything inside repeats forever.
+    pub fn duration(&self) -> f32 {
+        match self {
+            Animation::Tween(tween) => tween.duration,
+            Animation::Delay(seconds) => *seconds,
+            Animation::Sequence(animations) => animations.iter().map(|a| a.duration()).sum(),
+            Animation::Parallel(animations) => {
+                animations.iter().map(|a| a.duration()).fold(0.0, f32::max)
+            }
+            Animation::Repeat {
+                animation, times, ..
+            } => match times {
+                Some(times) => animation.duration() * *times as f32,
+                None if animation.duration() > 0.0 => f32::INFINITY,
+                None => 0.0,
+            },
+        }
+    }
+
+    // set every property to where it is `time` seconds in.
+    fn apply(&mut self, time: f32, targets: &mut dyn TweenTargets) {
+        match self {
+            Animation::Tween(tween) => {
+                let start = match tween.start {
+                    Some(start) => start,
+                    None => {
+                        let Some(value) = targets.read(tween.target, tween.property) else {
+                            return;
+                        };
+                        *tween.start.insert(tween.from.unwrap_or(value))
+                    }
+                };
+                let progress = match tween.duration > 0.0 {
+                    true => time / tween.duration,
+                    false => 1.0,
+                };
+                let value = start.lerp(tween.to, tween.easing.apply(progress));
+                targets.write(tween.target, tween.property, value);
+            }
+            Animation::Delay(_) => (),
+            Animation::Sequence(animations) => {
+                let mut starts = vec![];
+                let mut start = 0.0;
+                for animation in animations.iter() {
+                    starts.push(start);
+                    start += animation.duration();
+                }
+                // parts not reached yet go back to their beginning, latest first, so when
+                // played backwards the earlier parts end up on top.
+                for (animation, start) in animations.iter_mut().zip(starts.iter()).rev() {
+                    if time < *start {
+                        animation.rewind(targets);
+                    }
+                }
+                for (animation, start) in animations.iter_mut().zip(starts.iter()) {
+                    if time < *start {
+                        break;
+                    }
+                    let duration = animation.duration();
+                    animation.apply((time - start).min(duration), targets);
+                }
+            }
+            Animation::Parallel(animations) => {
+                for animation in animations.iter_mut() {
+                    let duration = animation.duration();
+                    animation.apply(time.min(duration), targets);
+                }
+            }
+            Animation::Repeat {
+                animation,
+                times,
+                yoyo,
+            } => {
+                let duration = animation.duration();
+                if duration <= 0.0 {
+                    animation.apply(0.0, targets);
+                    return;
+                }
+                let mut cycle = (time / duration).floor();
+                let mut local = time - cycle * duration;
+                if let Some(times) = times {
+                    if cycle >= *times as f32 {
+                        cycle = (*times as f32 - 1.0).max(0.0);
+                        local = duration;
+                    }
+                }
+                if *yoyo && cycle as u32 % 2 == 1 {
+                    local = duration - local;
+                }
+                animation.apply(local, targets);
+            }
+        }
+    }
+
+    // put started tweens back at their first value.
+    fn rewind(&mut self, targets: &mut dyn TweenTargets) {
+        match self {
+            Animation::Tween(tween) => {
+                if let Some(start) = tween.start {
+                    targets.write(tween.target, tween.property, start);
+                }
+            }
+            Animation::Delay(_) => (),
+            Animation::Sequence(animations) | Animation::Parallel(animations) => {
+                for animation in animations.iter_mut().rev() {
+                    animation.rewind(targets);
+                }
+            }
+            Animation::Repeat { animation, .. } => animation.rewind(targets),
+        }
+    }
+
+    fn targets(&self, node: NodeRef) -> bool {
+        match self {
+            Animation::Tween(tween) => tween.target == node,
+            Animation::Delay(_) => false,
+            Animation::Sequence(animations) | Animation::Parallel(animations) => {
+                animations.iter().any(|a| a.targets(node))
+            }
+            Animation::Repeat { animation, .. } => animation.targets(node),
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct TweenId(pub usize);
+
+struct Playing {
+    id: TweenId,
+    animation: Animation,
+    time: f32,
+    on_complete: Option<TweenCallback>,
+}
+
+// the animations playing on a context, advanced in Context::update before the hierarchy
+// and instance uploads.
+pub struct Tweens {
+    playing: Vec<Playing>,
+    next_id: usize,
+}
+
+impl Tweens {
+    pub fn new() -> Self {
+        Self {
+            playing: vec![],
+            next_id: 0,
+        }
+    }
+
+    pub fn play(&mut self, animation: impl Into<Animation>) -> TweenId {
+        self.start(animation.into(), None)
+    }
+
+    pub fn play_then(
+        &mut self,
+        animation: impl Into<Animation>,
+        on_complete: TweenCallback,
+    ) -> TweenId {
+        self.start(animation.into(), Some(on_complete))
+    }
+
+    fn start(&mut self, animation: Animation, on_complete: Option<TweenCallback>) -> TweenId {
+        let id = TweenId(self.next_id);
+        self.next_id += 1;
+        self.playing.push(Playing {
+            id,
+            animation,
+            time: 0.0,
+            on_complete,
+        });
+        id
+    }
+
+    pub fn is_playing(&self, id: TweenId) -> bool {
+        self.playing.iter().any(|playing| playing.id == id)
+    }
+
+    // stop where it is, without calling back.
+    pub fn stop(&mut self, id: TweenId) {
+        self.playing.retain(|playing| playing.id != id);
+    }
+
+    // stop everything animating `node`, e.g. before starting a new tween on it.
+    pub fn stop_target(&mut self, node: NodeRef) {
+        self.playing
+            .retain(|playing| !playing.animation.targets(node));
+    }
+
+    pub fn clear(&mut self) {
+        self.playing.clear();
+    }
+
+    // advance everything by `delta_time` seconds, returning the callbacks of the animations
+    // that finished.
+    pub fn update(
+        &mut self,
+        delta_time: f32,
+        geos: &mut GeoManager,
+        texts: &mut TextCollection,
+    ) -> Vec<TweenCallback> {
+        self.advance(delta_time, &mut Targets { geos, texts })
+    }
+
+    fn advance(&mut self, delta_time: f32, targets: &mut dyn TweenTargets) -> Vec<TweenCallback> {
+        let mut finished = vec![];
+        self.playing.retain_mut(|playing| {
+            playing.time += delta_time;
+            let duration = playing.animation.duration();
+            playing.animation.apply(playing.time.min(duration), targets);
+            if playing.time < duration {
+                return true;
+            }
+            finished.extend(playing.on_complete.take());
+            false
+        });
+        finished
+    }
+}
+
+impl Default for Tweens {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// where tweened values are read from and written to.
+trait TweenTargets {
+    fn read(&self, node: NodeRef, property: TweenProperty) -> Option<Vec4>;
+    fn write(&mut self, node: NodeRef, property: TweenProperty, value: Vec4);
+}
+
+struct Targets<'a> {
+    geos: &'a mut GeoManager,
+    texts: &'a mut TextCollection,
+}
+
+impl TweenTargets for Targets<'_> {
+    fn read(&self, node: NodeRef, property: TweenProperty) -> Option<Vec4> {
+        match node {
+            NodeRef::Instance { group, index } => {
+                let instance = self
+                    .geos
+                    .instance_groups
+                    .get(group)?
+                    .instance_buffer_manager
+                    .data
+                    .get(index)?;
+                Some(match property {
+                    TweenProperty::Position => instance.position().extend(0.0).extend(0.0),
+                    TweenProperty::Size => instance.size().extend(0.0).extend(0.0),
+                    TweenProperty::Rotation => Vec4::new(instance.transform.degrees, 0.0, 0.0, 0.0),
+                    TweenProperty::Color => instance.color,
+                })
+            }
+            NodeRef::Text(index) => {
+                let label = self.texts.texts.get(index)?;
+                Some(match property {
+                    TweenProperty::Position => {
+                        Vec4::new(label.left as f32, label.top as f32, 0.0, 0.0)
+                    }
+                    TweenProperty::Size => Vec4::new(label.scale as f32, 0.0, 0.0, 0.0),
+                    TweenProperty::Rotation => Vec4::ZERO,
+                    TweenProperty::Color => {
+                        let color = label.default_color;
+                        Vec4::new(
+                            color.r() as f32,
+                            color.g() as f32,
+                            color.b() as f32,
+                            color.a() as f32,
+                        ) / 255.0
+                    }
+                })
+            }
+        }
+    }
+
+    // only changed values are written, so settled tweens don't keep re-uploading.
+    fn write(&mut self, node: NodeRef, property: TweenProperty, value: Vec4) {
+        if self.read(node, property) == Some(value) {
+            return;
+        }
+        match node {
+            NodeRef::Instance { group, index } => {
+                let Some(instance) = self
+                    .geos
+                    .instance_groups
+                    .get_mut(group)
+                    .and_then(|group| group.instance_buffer_manager.data.get_mut(index))
+                else {
+                    return;
+                };
+                let xy = value.truncate().truncate();
+                match property {
+                    TweenProperty::Position => instance.set_position_px(xy),
+                    TweenProperty::Size => instance.set_size_px(xy.max(Vec2::ZERO)),
+                    TweenProperty::Rotation => instance.set_rotation(value.x),
+                    TweenProperty::Color => {
+                        instance.color = value;
+                        instance.needs_update = true;
+                    }
+                }
+            }
+            NodeRef::Text(index) => {
+                let Some(label) = self.texts.texts.get_mut(index) else {
+                    return;
+                };
+                match property {
+                    TweenProperty::Position => {
+                        label.left = value.x as f64;
+                        label.top = value.y as f64;
+                    }
+                    TweenProperty::Size => label.scale = value.x.max(0.0) as f64,
+                    TweenProperty::Rotation => (),
+                    TweenProperty::Color => {
+                        let [r, g, b, a] = (value.clamp(Vec4::ZERO, Vec4::ONE) * 255.0)
+                            .round()
+                            .to_array()
+                            .map(|c| c as u8);
+                        label.default_color = Color::rgba(r, g, b, a);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EASINGS: [Easing; 16] = [
+        Easing::Linear,
+        Easing::QuadIn,
+        Easing::QuadOut,
+        Easing::QuadInOut,
+        Easing::CubicIn,
+        Easing::CubicOut,
+        Easing::CubicInOut,
+        Easing::ElasticIn,
+        Easing::ElasticOut,
+        Easing::ElasticInOut,
+        Easing::BounceIn,
+        Easing::BounceOut,
+        Easing::BounceInOut,
+        Easing::BackIn,
+        Easing::BackOut,
+        Easing::BackInOut,
+    ];
+
+    const A: NodeRef = NodeRef::Text(0);
+    const B: NodeRef = NodeRef::Text(1);
+
+    // position values of nodes, standing in for the geos and labels. everything starts at 0.
+    #[derive(Default)]
+    struct Positions(Vec<(NodeRef, Vec4)>);
+
+    impl Positions {
+        fn x(&self, node: NodeRef) -> f32 {
+            self.read(node, TweenProperty::Position).unwrap().x
+        }
+    }
+
+    impl TweenTargets for Positions {
+        fn read(&self, node: NodeRef, _: TweenProperty) -> Option<Vec4> {
+            let value = self.0.iter().find(|(n, _)| *n == node);
+            Some(value.map_or(Vec4::ZERO, |(_, value)| *value))
+        }
+
+        fn write(&mut self, node: NodeRef, _: TweenProperty, value: Vec4) {
+            self.0.retain(|(n, _)| *n != node);
+            self.0.push((node, value));
+        }
+    }
+
+    fn to_x(node: NodeRef, x: f32, duration: f32) -> Animation {
+        Tween::position(node, Vec2::new(x, 0.0), duration).into()
+    }
+
+    fn assert_near(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-4, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn easings_start_at_zero_and_end_at_one() {
+        for easing in EASINGS {
+            assert_near(easing.apply(0.0), 0.0);
+            assert_near(easing.apply(1.0), 1.0);
+            // progress outside 0..1 is clamped.
+            assert_near(easing.apply(-1.0), 0.0);
+            assert_near(easing.apply(2.0), 1.0);
+        }
+    }
+
+    #[test]
+    fn sequences_play_parts_in_turn() {
+        let animation = Animation::sequence(vec![
+            to_x(A, 10.0, 1.0),
+            Animation::delay(0.5),
+            to_x(B, 20.0, 2.0),
+        ]);
+        assert_near(animation.duration(), 3.5);
+        let (mut tweens, mut values) = (Tweens::new(), Positions::default());
+        let id = tweens.play(animation);
+
+        tweens.advance(0.5, &mut values);
+        assert_near(values.x(A), 5.0);
+        assert_near(values.x(B), 0.0);
+        tweens.advance(1.0, &mut values);
+        assert_near(values.x(A), 10.0);
+        assert_near(values.x(B), 0.0);
+        tweens.advance(1.0, &mut values);
+        assert_near(values.x(B), 10.0);
+        tweens.advance(1.0, &mut values);
+        assert_near(values.x(B), 20.0);
+        assert!(!tweens.is_playing(id));
+    }
+
+    #[test]
+    fn parallel_parts_hold_at_their_ends() {
+        let animation = Animation::parallel(vec![to_x(A, 10.0, 1.0), to_x(B, 20.0, 2.0)]);
+        assert_near(animation.duration(), 2.0);
+        let (mut tweens, mut values) = (Tweens::new(), Positions::default());
+        tweens.play(animation);
+
+        tweens.advance(0.5, &mut values);
+        assert_near(values.x(A), 5.0);
+        assert_near(values.x(B), 5.0);
+        tweens.advance(1.0, &mut values);
+        assert_near(values.x(A), 10.0);
+        assert_near(values.x(B), 15.0);
+    }
+
+    #[test]
+    fn repeats_restart_each_cycle() {
+        let animation = to_x(A, 10.0, 1.0).repeat(3);
+        assert_near(animation.duration(), 3.0);
+        let (mut tweens, mut values) = (Tweens::new(), Positions::default());
+        let id = tweens.play(animation);
+
+        tweens.advance(1.5, &mut values);
+        assert_near(values.x(A), 5.0);
+        tweens.advance(0.75, &mut values);
+        assert_near(values.x(A), 2.5);
+        tweens.advance(1.0, &mut values);
+        assert_near(values.x(A), 10.0);
+        assert!(!tweens.is_playing(id));
+
+        let forever = to_x(A, 10.0, 1.0).repeat_forever();
+        assert_eq!(forever.duration(), f32::INFINITY);
+        let id = tweens.play(forever);
+        let mut values = Positions::default();
+        tweens.advance(100.25, &mut values);
+        assert_near(values.x(A), 2.5);
+        assert!(tweens.is_playing(id));
+    }
+
+    #[test]
+    fn yoyos_play_every_other_cycle_backwards() {
+        let animation = to_x(A, 10.0, 1.0).yoyo();
+        assert_near(animation.duration(), 2.0);
+        let (mut tweens, mut values) = (Tweens::new(), Positions::default());
+        tweens.play(animation);
+
+        tweens.advance(0.75, &mut values);
+        assert_near(values.x(A), 7.5);
+        tweens.advance(0.5, &mut values);
+        assert_near(values.x(A), 7.5);
+        tweens.advance(1.0, &mut values);
+        assert_near(values.x(A), 0.0);
+
+        // repeat after yoyo counts each way as a cycle, and keeps the yoyo.
+        let animation = to_x(A, 10.0, 1.0).yoyo().repeat(3);
+        assert_near(animation.duration(), 3.0);
+        tweens.play(animation);
+        tweens.advance(2.5, &mut values);
+        assert_near(values.x(A), 5.0);
+        tweens.advance(1.0, &mut values);
+        assert_near(values.x(A), 10.0);
+    }
+
+    #[test]
+    fn callbacks_are_returned_once_when_finished() {
+        let (mut tweens, mut values) = (Tweens::new(), Positions::default());
+        let id = tweens.play_then(to_x(A, 10.0, 1.0), Box::new(|_| Ok(())));
+        assert!(tweens.advance(0.5, &mut values).is_empty());
+        assert_eq!(tweens.advance(0.5, &mut values).len(), 1);
+        assert!(!tweens.is_playing(id));
+        assert!(tweens.advance(0.5, &mut values).is_empty());
+
+        // zero length animations finish on their first update.
+        tweens.play_then(to_x(A, 20.0, 0.0), Box::new(|_| Ok(())));
+        assert_eq!(tweens.advance(0.0, &mut values).len(), 1);
+        assert_near(values.x(A), 20.0);
+
+        // stopped animations don't call back.
+        let id = tweens.play_then(to_x(A, 10.0, 1.0), Box::new(|_| Ok(())));
+        tweens.advance(0.5, &mut values);
+        tweens.stop(id);
+        assert!(tweens.advance(1.0, &mut values).is_empty());
+        assert_near(values.x(A), 15.0);
+    }
+}
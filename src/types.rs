@@ -1,18 +1,22 @@
 use glam::{IVec2, Quat, UVec2};
 use std::{
     mem::size_of,
+    ops::Range,
     sync::{Arc, Mutex},
 };
 
+use crate::assets::{Handle, Shader};
 use bytemuck::{ByteEq, ByteHash, Pod, Zeroable};
 use glam::{Mat4, Vec2, Vec3, Vec4};
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
-    Sampler, Texture, TextureView,
+    AddressMode, BlendComponent, BlendFactor, BlendOperation, BlendState, FilterMode, Sampler,
+    Texture, TextureView,
 };
 use wgpu::{
-    Buffer, BufferAddress, BufferUsages, Device, PipelineLayout, Queue, RenderPipeline,
-    ShaderModule, TextureFormat, VertexAttribute, VertexBufferLayout, VertexFormat, VertexStepMode,
+    Buffer, BufferAddress, BufferDescriptor, BufferUsages, Device, IndexFormat, PipelineLayout,
+    PrimitiveTopology, Queue, RenderPipeline, TextureFormat, VertexAttribute, VertexBufferLayout,
+    VertexFormat, VertexStepMode,
 };
 
 #[repr(C)]
@@ -42,6 +46,101 @@ pub const UNIT_SQUARE_VERTICES: [Vertex; 4] = [
 ];
 pub const UNIT_SQUARE_INDICES: [u16; 6] = [0, 2, 1, 2, 0, 3];
 
+// which sprite of a group's sheet to draw.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct SpriteRef {
+    pub layer: usize,
+    pub cluster: usize,
+    pub sub: usize,
+}
+
+impl SpriteRef {
+    pub fn new(cluster: usize, sub: usize) -> Self {
+        Self {
+            layer: 0,
+            cluster,
+            sub,
+        }
+    }
+
+    // a sprite on one layer of a texture array group.
+    pub fn on_layer(layer: usize, cluster: usize, sub: usize) -> Self {
+        Self {
+            layer,
+            cluster,
+            sub,
+        }
+    }
+}
+
+// index data for a mesh group; u32 only when a mesh needs more than 65536 vertices.
+pub enum MeshIndices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl MeshIndices {
+    // the narrowest format that can index `vertex_count` vertices.
+    pub fn fitting(indices: Vec<u32>, vertex_count: usize) -> Self {
+        match vertex_count <= u16::MAX as usize + 1 {
+            true => MeshIndices::U16(indices.into_iter().map(|i| i as u16).collect()),
+            false => MeshIndices::U32(indices),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            MeshIndices::U16(indices) => indices.len(),
+            MeshIndices::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn format(&self) -> IndexFormat {
+        match self {
+            MeshIndices::U16(_) => IndexFormat::Uint16,
+            MeshIndices::U32(_) => IndexFormat::Uint32,
+        }
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        match self {
+            MeshIndices::U16(indices) => bytemuck::cast_slice(indices),
+            MeshIndices::U32(indices) => bytemuck::cast_slice(indices),
+        }
+    }
+}
+
+// how a mesh group's vertex buffer is read. the per-instance buffer is always appended after
+// `vertex`, so custom vertex attributes must use shader locations below 5.
+#[derive(Clone)]
+pub struct MeshLayout {
+    pub vertex: VertexBufferLayout<'static>,
+    pub topology: PrimitiveTopology,
+}
+
+impl MeshLayout {
+    pub fn line_strip() -> Self {
+        Self {
+            topology: PrimitiveTopology::LineStrip,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for MeshLayout {
+    // triangles of `Vertex`, like the unit square.
+    fn default() -> Self {
+        Self {
+            vertex: UNIT_SQUARE_BUFFER_LAYOUT[0].clone(),
+            topology: PrimitiveTopology::TriangleList,
+        }
+    }
+}
+
 pub const UNIT_SQUARE_BUFFER_LAYOUT: [VertexBufferLayout<'_>; 2] = [
     VertexBufferLayout {
         array_stride: (size_of::<Vec3>() + size_of::<Vec2>()) as BufferAddress,
@@ -113,16 +212,62 @@ pub const UNIT_SQUARE_BUFFER_LAYOUT: [VertexBufferLayout<'_>; 2] = [
                 shader_location: 13,
                 format: VertexFormat::Float32x4,
             },
+            // u32 texture array layer
+            VertexAttribute {
+                offset: size_of::<[f32; 36]>() as BufferAddress,
+                shader_location: 14,
+                format: VertexFormat::Uint32,
+            },
         ],
     },
 ];
 
+// how a group's fragments combine with what is already drawn.
+// every mode but Alpha expects the shader to output premultiplied color, as the bundled shaders do.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum BlendMode {
+    Alpha,
+    Premultiplied,
+    Additive,
+    Multiply,
+    Screen,
+    Opaque,
+}
+
+impl BlendMode {
+    pub fn blend_state(&self) -> Option<BlendState> {
+        let component = |src_factor, dst_factor| BlendComponent {
+            src_factor,
+            dst_factor,
+            operation: BlendOperation::Add,
+        };
+        match self {
+            BlendMode::Alpha => Some(BlendState::ALPHA_BLENDING),
+            BlendMode::Premultiplied => Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+            BlendMode::Additive => Some(BlendState {
+                color: component(BlendFactor::One, BlendFactor::One),
+                alpha: component(BlendFactor::One, BlendFactor::One),
+            }),
+            BlendMode::Multiply => Some(BlendState {
+                color: component(BlendFactor::Dst, BlendFactor::OneMinusSrcAlpha),
+                alpha: component(BlendFactor::Zero, BlendFactor::One),
+            }),
+            BlendMode::Screen => Some(BlendState {
+                color: component(BlendFactor::One, BlendFactor::OneMinusSrc),
+                alpha: component(BlendFactor::One, BlendFactor::OneMinusSrcAlpha),
+            }),
+            BlendMode::Opaque => None,
+        }
+    }
+}
+
 pub struct RenderPipelineRecord {
     pub render_pipeline: RenderPipeline,
     pub pipeline_layout: PipelineLayout,
-    pub shader_module: ShaderModule,
-    pub shader_path: String,
+    pub shader: Handle<Shader>,
+    pub mesh_layout: MeshLayout,
     pub format: TextureFormat,
+    pub blend_mode: BlendMode,
 }
 
 pub struct GeoUniformVec2 {
@@ -140,13 +285,106 @@ pub struct Instance {
     pub transform: ComponentTransform,
     pub tex_transform: ComponentTransform,
     pub color: Vec4,
+    pub layer: f32,
+    pub tex_layer: u32,
+    pub flip_x: bool,
+    pub flip_y: bool,
 }
 
+// instances live in world space; see Camera.
 impl Instance {
     pub fn translate(&mut self, by: Vec3) {
         self.transform.location += by;
         self.needs_update = true;
     }
+
+    // move the rect's top left corner to `xy`, in world pixels.
+    pub fn set_position_px(&mut self, xy: Vec2) {
+        self.transform.location = xy.extend(self.transform.location.z);
+        self.needs_update = true;
+    }
+
+    pub fn translate_px(&mut self, by: Vec2) {
+        self.translate(by.extend(0.0));
+    }
+
+    pub fn set_size_px(&mut self, wh: Vec2) {
+        self.transform.scale = wh.extend(self.transform.scale.z);
+        self.needs_update = true;
+    }
+
+    // top left corner in world pixels.
+    pub fn position(&self) -> Vec2 {
+        self.transform.location.truncate()
+    }
+
+    pub fn size(&self) -> Vec2 {
+        self.transform.scale.truncate()
+    }
+
+    // see SortMode. location.z adds to the layer.
+    pub fn set_layer(&mut self, layer: f32) {
+        self.layer = layer;
+        self.needs_update = true;
+    }
+
+    // see ComponentTransform::pivot and degrees.
+    pub fn set_pivot(&mut self, pivot: Vec2) {
+        self.transform.pivot = pivot;
+        self.needs_update = true;
+    }
+
+    pub fn set_rotation(&mut self, degrees: f32) {
+        self.transform.degrees = degrees;
+        self.needs_update = true;
+    }
+
+    pub fn rotate(&mut self, degrees: f32) {
+        self.transform.degrees += degrees;
+        self.needs_update = true;
+    }
+
+    // mirror the sprite within its rect, leaving the rect itself in place.
+    pub fn set_flip(&mut self, flip_x: bool, flip_y: bool) {
+        self.flip_x = flip_x;
+        self.flip_y = flip_y;
+        self.needs_update = true;
+    }
+
+    pub fn draw_layer(&self) -> f32 {
+        self.layer + self.transform.location.z
+    }
+
+    // bottom edge of the instance on screen, growing downward, for y-sorting.
+    fn sort_y(&self, y_down: bool) -> f32 {
+        match y_down {
+            true => self.transform.location.y + self.transform.scale.y,
+            false => -(self.transform.location.y - self.transform.scale.y),
+        }
+    }
+
+    fn to_data(&self, y_down: bool) -> InstanceData {
+        InstanceData {
+            transform: self.transform.to_instance_mat4(y_down),
+            tex_transform: self.tex_transform.to_mat4()
+                * flip_mat4(self.flip_x, self.flip_y, false),
+            color: self.color,
+            tex_layer: self.tex_layer,
+            padding: [0; 3],
+        }
+    }
+}
+
+// mirrors unit texture coordinates, before the sheet's tex_transform picks the sprite.
+// a diagonal flip swaps x and y, and happens before the other two.
+pub(crate) fn flip_mat4(flip_x: bool, flip_y: bool, flip_diagonal: bool) -> Mat4 {
+    let (x, y) = (flip_x as u8 as f32, flip_y as u8 as f32);
+    let mirror = Mat4::from_translation(Vec3::new(x, y, 0.0))
+        * Mat4::from_scale(Vec3::new(1.0 - 2.0 * x, 1.0 - 2.0 * y, 1.0));
+    match flip_diagonal {
+        true => Mat4::from_cols(Vec4::Y, Vec4::X, Vec4::Z, Vec4::W) * mirror,
+        false => mirror,
+    }
 }
 
 #[derive(Copy, Clone, Pod, Zeroable, ByteEq, ByteHash)]
@@ -155,6 +393,8 @@ pub struct InstanceData {
     pub transform: Mat4,
     pub tex_transform: Mat4,
     pub color: Vec4,
+    pub tex_layer: u32,
+    pub padding: [u32; 3],
 }
 
 impl Default for InstanceData {
@@ -163,26 +403,66 @@ impl Default for InstanceData {
             transform: Mat4::IDENTITY,
             tex_transform: Mat4::IDENTITY,
             color: Vec4::new(1.0, 1.0, 1.0, 1.0),
+            tex_layer: 0,
+            padding: [0; 3],
         }
     }
 }
 
+// draw order of a group's instances. sorted modes draw lower layers first;
+// LayerThenY then draws instances whose bottom edge is higher on screen first, for top-down games.
+// layers also order groups: every group's instances on one layer draw before any on a higher
+// layer, and groups on the same layer draw in the order they were made.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum SortMode {
+    Insertion,
+    Layer,
+    LayerThenY,
+}
+
+// a stretch of a group's instance buffer whose instances share a layer.
+#[derive(Clone, PartialEq, Debug)]
+pub struct DrawRun {
+    pub layer: f32,
+    pub instances: Range<u32>,
+}
+
 pub struct InstanceBufferManager {
     pub data: Vec<Instance>,
+    // the instance each buffer slot holds, so a re-sort only rewrites slots that changed.
+    slots: Vec<usize>,
+    // this frame's immediate instances and their layers, drawn after `data` and cleared
+    // after render.
+    pub transient: Vec<(f32, InstanceData)>,
     pub buffer: Buffer,
+    pub sort_mode: SortMode,
+    pub order_dirty: bool,
+    // which way world y points, following the camera. see ComponentTransform::to_instance_mat4.
+    pub y_down: bool,
+    // to grow the buffer when instances are added past its end.
+    pub device: Arc<Mutex<Device>>,
 }
 
 impl InstanceBufferManager {
     pub fn new(max_instances: usize, device: Arc<Mutex<Device>>) -> Self {
-        let device = device.lock().unwrap();
         let init_buffer_data = vec![InstanceData::default(); max_instances];
-        InstanceBufferManager {
-            data: vec![],
-            buffer: device.create_buffer_init(&BufferInitDescriptor {
+        let buffer = device
+            .lock()
+            .unwrap()
+            .create_buffer_init(&BufferInitDescriptor {
                 label: Some("instance buffer"),
                 contents: bytemuck::cast_slice(&init_buffer_data),
                 usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
-            }),
+            });
+        InstanceBufferManager {
+            data: vec![],
+            slots: vec![],
+            transient: vec![],
+            buffer,
+            sort_mode: SortMode::Insertion,
+            order_dirty: false,
+            y_down: true,
+            device,
         }
     }
 
@@ -192,59 +472,206 @@ impl InstanceBufferManager {
         transform: ComponentTransform,
         tex_transform: ComponentTransform,
         color: Vec4,
+        tex_layer: u32,
     ) {
         let queue = queue.lock().unwrap();
+        let device = self.device.clone();
+        self.reserve(&device.lock().unwrap(), &queue, self.data.len() + 1);
         let new_data = InstanceData {
-            transform: transform.to_mat4(),
+            transform: transform.to_instance_mat4(self.y_down),
             tex_transform: tex_transform.to_mat4(),
             color,
+            tex_layer,
+            padding: [0; 3],
         };
         queue.write_buffer(
             &self.buffer,
             (self.data.len() * size_of::<InstanceData>()) as u64,
             bytemuck::cast_slice(&[new_data]),
         );
+        self.slots.push(self.data.len());
         self.data.push(Instance {
             transform,
             tex_transform,
             color,
             needs_update: false,
+            layer: 0.0,
+            tex_layer,
+            flip_x: false,
+            flip_y: false,
         });
+        if self.sort_mode != SortMode::Insertion {
+            self.order_dirty = true;
+        }
     }
 
-    pub fn recalc_screen_instances(&mut self, queue: Arc<Mutex<Queue>>, screen: UVec2) {
-        for (instance_index, instance) in self.data.iter_mut().enumerate() {
-            if instance.needs_update && instance.transform.pixel_rect.is_some() {
-                instance.needs_update = false;
-                let queue = queue.lock().unwrap();
-                let pr = instance.transform.pixel_rect.unwrap();
-                let new_data = InstanceData {
-                    transform: ComponentTransform::unit_square_transform_from_pixel_rect(
-                        PixelRect {
-                            xy: IVec2::new(
-                                ((instance.transform.location.x * 0.5 + 0.5) * screen.x as f32)
-                                    as i32,
-                                ((instance.transform.location.y * 0.5 + 0.5) * screen.y as f32)
-                                    as i32,
-                            ),
-                            wh: pr.wh,
-                            extent: screen,
-                        },
-                    )
-                    .to_mat4(),
-                    tex_transform: instance.tex_transform.to_mat4(),
-                    color: instance.color,
-                };
-                queue.write_buffer(
-                    &self.buffer,
-                    (instance_index * size_of::<InstanceData>()) as BufferAddress,
-                    bytemuck::cast_slice(&[new_data]),
-                );
+    pub fn set_sort_mode(&mut self, sort_mode: SortMode) {
+        self.sort_mode = sort_mode;
+        self.order_dirty = true;
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buffer.size() as usize / size_of::<InstanceData>()
+    }
+
+    pub fn draw_count(&self) -> usize {
+        self.data.len() + self.transient.len()
+    }
+
+    // the retained instances in buffer order, then the transients, split where the layer
+    // changes.
+    pub fn draw_runs(&self) -> Vec<DrawRun> {
+        let mut runs: Vec<DrawRun> = vec![];
+        let retained = self
+            .slots
+            .iter()
+            .map(|index| self.data[*index].draw_layer());
+        let transient = self.transient.iter().map(|(layer, _)| *layer);
+        for (slot, layer) in retained.chain(transient).enumerate() {
+            match runs.last_mut() {
+                Some(run) if run.layer == layer => run.instances.end += 1,
+                _ => runs.push(DrawRun {
+                    layer,
+                    instances: slot as u32..slot as u32 + 1,
+                }),
             }
         }
+        runs
+    }
+
+    // upload the transient instances after the retained ones, grouped by layer so each layer
+    // draws as one run. if they don't fit, the buffer grows and the retained instances are
+    // rewritten into it.
+    pub fn write_transient(&mut self, device: &Device, queue: &Queue) {
+        if self.transient.is_empty() {
+            return;
+        }
+        self.transient.sort_by(|a, b| a.0.total_cmp(&b.0));
+        self.reserve(device, queue, self.draw_count());
+        let data: Vec<InstanceData> = self.transient.iter().map(|(_, data)| *data).collect();
+        queue.write_buffer(
+            &self.buffer,
+            (self.data.len() * size_of::<InstanceData>()) as BufferAddress,
+            bytemuck::cast_slice(&data),
+        );
+    }
+
+    // make room for `count` instances. a new buffer gets the retained instances rewritten into it.
+    fn reserve(&mut self, device: &Device, queue: &Queue, count: usize) {
+        if count <= self.capacity() {
+            return;
+        }
+        self.buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("instance buffer"),
+            size: (count.next_power_of_two() * size_of::<InstanceData>()) as BufferAddress,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let retained: Vec<InstanceData> = self
+            .slots
+            .iter()
+            .map(|index| self.data[*index].to_data(self.y_down))
+            .collect();
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&retained));
+    }
+
+    // instance indices in the order the sort mode lays them out in the buffer.
+    pub fn draw_order(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.data.len()).collect();
+        match self.sort_mode {
+            SortMode::Insertion => (),
+            SortMode::Layer => {
+                order.sort_by(|a, b| {
+                    self.data[*a]
+                        .draw_layer()
+                        .total_cmp(&self.data[*b].draw_layer())
+                });
+            }
+            SortMode::LayerThenY => order.sort_by(|a, b| {
+                let (a, b) = (&self.data[*a], &self.data[*b]);
+                a.draw_layer()
+                    .total_cmp(&b.draw_layer())
+                    .then(a.sort_y(self.y_down).total_cmp(&b.sort_y(self.y_down)))
+            }),
+        }
+        order
+    }
+
+    pub fn recalc_screen_instances(&mut self, queue: Arc<Mutex<Queue>>) {
+        if self.sort_mode == SortMode::Insertion {
+            // coming back from a sorted mode, the buffer still holds the sorted order, so every
+            // slot goes back to its own instance.
+            if self.order_dirty {
+                self.slots = (0..self.data.len()).collect();
+                let data: Vec<InstanceData> = self
+                    .data
+                    .iter()
+                    .map(|instance| instance.to_data(self.y_down))
+                    .collect();
+                queue
+                    .lock()
+                    .unwrap()
+                    .write_buffer(&self.buffer, 0, bytemuck::cast_slice(&data));
+                for instance in self.data.iter_mut() {
+                    instance.needs_update = false;
+                }
+                self.order_dirty = false;
+                return;
+            }
+            let y_down = self.y_down;
+            for (instance_index, instance) in self.data.iter_mut().enumerate() {
+                if instance.needs_update {
+                    instance.needs_update = false;
+                    let queue = queue.lock().unwrap();
+                    queue.write_buffer(
+                        &self.buffer,
+                        (instance_index * size_of::<InstanceData>()) as BufferAddress,
+                        bytemuck::cast_slice(&[instance.to_data(y_down)]),
+                    );
+                }
+            }
+            return;
+        }
+
+        // sorted groups re-sort whenever anything changes, then rewrite the runs of slots
+        // that now hold another instance or one that changed.
+        if !self.order_dirty && !self.data.iter().any(|instance| instance.needs_update) {
+            return;
+        }
+        let order = self.draw_order();
+        let queue = queue.lock().unwrap();
+        let mut slot = 0;
+        while slot < order.len() {
+            let changed = |slot: usize| {
+                self.slots[slot] != order[slot] || self.data[order[slot]].needs_update
+            };
+            if !changed(slot) {
+                slot += 1;
+                continue;
+            }
+            let start = slot;
+            while slot < order.len() && changed(slot) {
+                slot += 1;
+            }
+            let data: Vec<InstanceData> = order[start..slot]
+                .iter()
+                .map(|index| self.data[*index].to_data(self.y_down))
+                .collect();
+            queue.write_buffer(
+                &self.buffer,
+                (start * size_of::<InstanceData>()) as BufferAddress,
+                bytemuck::cast_slice(&data),
+            );
+        }
+        for instance in self.data.iter_mut() {
+            instance.needs_update = false;
+        }
+        self.slots = order;
+        self.order_dirty = false;
     }
 }
 
+#[derive(Clone)]
 pub struct TextureSheetClusterDefinition {
     pub label: String,
     pub offset: UVec2,
@@ -253,57 +680,121 @@ pub struct TextureSheetClusterDefinition {
     pub spacing: UVec2,
 }
 
+// how a sheet is sampled. anisotropy above 1 needs Linear filtering.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SamplerOptions {
+    pub filter: FilterMode,
+    pub address_mode: AddressMode,
+    pub anisotropy: u16,
+    pub mipmaps: bool,
+}
+
+impl Default for SamplerOptions {
+    fn default() -> Self {
+        Self {
+            filter: FilterMode::Nearest,
+            address_mode: AddressMode::ClampToEdge,
+            anisotropy: 1,
+            mipmaps: false,
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct TextureSheetDefinition {
     pub path: String,
     pub clusters: Vec<TextureSheetClusterDefinition>,
+    pub sampler: SamplerOptions,
 }
 
 impl TextureSheetDefinition {
+    // untextured geometry: samples a single white pixel, so instances show their color.
     pub fn none() -> Self {
         Self {
             path: "".to_string(),
             clusters: vec![],
+            sampler: SamplerOptions::default(),
         }
     }
 }
 
-pub struct TextureSheet {
-    pub sheet_info: TextureSheetDefinition,
+// a texture on the gpu, shared by every sheet loaded from the same files with the same
+// mipmaps option. `sources` are the definitions it was first loaded with.
+pub struct SheetTexture {
+    pub sources: Vec<TextureSheetDefinition>,
+    pub array: bool,
     pub dimensions: UVec2,
     pub texture: Texture,
-    pub sampler: Sampler,
     pub view: TextureView,
 }
 
+// what a group draws from: a shared texture, with the group's own clusters per layer and
+// its own sampler.
+pub struct TextureSheet {
+    pub layers: Vec<TextureSheetDefinition>,
+    pub texture: Handle<SheetTexture>,
+    pub sampler: Sampler,
+}
+
+impl TextureSheetClusterDefinition {
+    pub fn columns(&self) -> u32 {
+        let mut rc = 0;
+        for _ in (0..self.cluster_size.x).step_by((self.sub_size.x + self.spacing.x) as usize) {
+            rc += 1;
+        }
+        rc
+    }
+
+    // top left pixel of a sub-image within the sheet.
+    pub fn sub_offset(&self, sub_index: usize) -> UVec2 {
+        let rc /*row count*/ = self.columns();
+
+        let row_index = sub_index as u32 / rc;
+        let col_index = sub_index as u32 % rc;
+
+        UVec2::new(
+            self.offset.x + col_index * (self.sub_size.x + self.spacing.x),
+            self.offset.y + row_index * (self.sub_size.y + self.spacing.y),
+        )
+    }
+}
+
 impl TextureSheet {
+    // index of the first cluster with this label, e.g. a packed atlas frame name.
+    pub fn cluster_index(&self, label: &str) -> Option<usize> {
+        self.layers[0]
+            .clusters
+            .iter()
+            .position(|c| c.label == label)
+    }
+
     pub fn cluster_sub_transform(
         &self,
         cluster_index: usize,
         sub_index: usize,
     ) -> ComponentTransform {
-        let c /*cluster*/ = &self.sheet_info.clusters[cluster_index];
-        let rc /*row count*/ = {
-            let mut rc = 0;
-            for _ in (0..c.cluster_size.x).step_by((c.sub_size.x + c.spacing.x) as usize) {
-                rc += 1;
-            }
-            rc
-        };
-
-        let row_index = sub_index as u32 / rc;
-        let col_index = sub_index as u32 % rc;
+        self.layer_cluster_sub_transform(0, cluster_index, sub_index)
+    }
 
-        let x_offset = c.offset.x + col_index * (c.sub_size.x + c.spacing.x);
-        let y_offset = c.offset.y + row_index * (c.sub_size.y + c.spacing.y);
+    pub fn layer_cluster_sub_transform(
+        &self,
+        layer: usize,
+        cluster_index: usize,
+        sub_index: usize,
+    ) -> ComponentTransform {
+        let c /*cluster*/ = &self.layers[layer].clusters[cluster_index];
+        let offset = c.sub_offset(sub_index);
 
         ComponentTransform::tex_transform_from_pixel_rect(PixelRect {
-            xy: IVec2::new(x_offset as i32, y_offset as i32),
+            xy: IVec2::new(offset.x as i32, offset.y as i32),
             wh: c.sub_size,
-            extent: self.dimensions,
+            extent: self.texture.dimensions,
         })
     }
 }
 
+// a rect with a top left origin, within a screen or sheet of size `extent`: logical pixels on
+// a screen, texels on a sheet.
 #[derive(Copy, Clone)]
 pub struct PixelRect {
     pub xy: IVec2,
@@ -311,11 +802,16 @@ pub struct PixelRect {
     pub extent: UVec2,
 }
 
+#[derive(Copy, Clone)]
 pub struct ComponentTransform {
     pub pixel_rect: Option<PixelRect>,
     pub location: Vec3,
     pub rotation: Quat,
     pub scale: Vec3,
+    // the point to rotate around, 0..1 from the rect's top left, and a rotation about it in
+    // degrees, applied on top of `rotation`. clockwise on screen when world y points down.
+    pub pivot: Vec2,
+    pub degrees: f32,
 }
 
 impl Default for ComponentTransform {
@@ -325,6 +821,8 @@ impl Default for ComponentTransform {
             location: Vec3::ZERO,
             rotation: Quat::IDENTITY,
             scale: Vec3::ONE,
+            pivot: Vec2::ZERO,
+            degrees: 0.0,
         }
     }
 }
@@ -334,6 +832,44 @@ impl ComponentTransform {
         Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.location)
     }
 
+    pub fn with_pivot(mut self, pivot: Vec2) -> Self {
+        self.pivot = pivot;
+        self
+    }
+
+    pub fn with_rotation(mut self, degrees: f32) -> Self {
+        self.degrees = degrees;
+        self
+    }
+
+    // from `location` to the pivot in world space. rects grow down the screen from their top
+    // left, which is +y when world y points down and -y when it points up.
+    pub fn pivot_offset(&self, y_down: bool) -> Vec2 {
+        let down = if y_down { 1.0 } else { -1.0 };
+        self.pivot * self.scale.truncate() * Vec2::new(1.0, down)
+    }
+
+    // the transform uploaded for an instance, turning about the pivot. unit geometry spans
+    // y 0..-1, so it's mirrored when world y points down to keep sprites upright. location.z
+    // only orders instances (see Instance::draw_layer), so it's left out.
+    pub fn to_instance_mat4(&self, y_down: bool) -> Mat4 {
+        let down = if y_down { 1.0 } else { -1.0 };
+        let pivot = self.pivot_offset(y_down).extend(0.0);
+        Mat4::from_translation(self.location.truncate().extend(0.0) + pivot)
+            * Mat4::from_quat(self.rotation * Quat::from_rotation_z(self.degrees.to_radians()))
+            * Mat4::from_translation(-pivot)
+            * Mat4::from_scale(self.scale * Vec3::new(1.0, -down, 1.0))
+    }
+
+    // a world space rect: top left `xy` and size `wh`, in world pixels.
+    pub fn world_rect(xy: Vec2, wh: Vec2) -> ComponentTransform {
+        ComponentTransform {
+            location: xy.extend(0.0),
+            scale: wh.extend(1.0),
+            ..Default::default()
+        }
+    }
+
     pub fn tex_transform_from_pixel_rect(pixel_rect: PixelRect) -> ComponentTransform {
         let xy = Vec2::new(pixel_rect.xy.x as f32, pixel_rect.xy.y as f32);
         let wh = Vec2::new(pixel_rect.wh.x as f32, pixel_rect.wh.y as f32);
@@ -348,30 +884,17 @@ impl ComponentTransform {
             location,
             rotation,
             scale,
+            ..Default::default()
         }
     }
 
     pub fn unit_square_transform_from_pixel_rect(pixel_rect: PixelRect) -> ComponentTransform {
-        // given window pixels x, y (top left) of w, h (width, height) produce a transform
-        // that positions the UNIT_SQUARE geometry as desired in render space...
-
-        let xy = Vec2::new(pixel_rect.xy.x as f32, pixel_rect.xy.y as f32);
-        let wh = Vec2::new(pixel_rect.wh.x as f32, pixel_rect.wh.y as f32);
-        let extent = Vec2::new(pixel_rect.extent.x as f32, pixel_rect.extent.y as f32);
-
-        let location = Vec3::new(
-            (xy.x / extent.x) * 2.0 - 1.0,
-            1.0 - (xy.y / extent.y) * 2.0,
-            0.0,
-        );
-        let rotation = Quat::IDENTITY;
-        let scale = Vec3::new((wh.x / extent.x) * 2.0, (wh.y / extent.y) * 2.0, 1.0);
-
+        // given pixels x, y (top left) of w, h (width, height) produce a transform that
+        // positions the UNIT_SQUARE geometry there in world space. the camera takes it to the
+        // screen from there, so `extent` doesn't matter here.
         ComponentTransform {
             pixel_rect: Some(pixel_rect),
-            location,
-            rotation,
-            scale,
+            ..Self::world_rect(pixel_rect.xy.as_vec2(), pixel_rect.wh.as_vec2())
         }
     }
 }
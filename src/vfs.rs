@@ -0,0 +1,447 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::{read, read_dir, File},
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+
+// pack layout, all integers little endian:
+//   magic "VPAK", version u32, entry count u32,
+//   per entry: path length u32, path utf-8, offset u64, compressed length u64, size u64,
+//   then every entry's deflate data, with offsets counted from the end of the index.
+const PACK_MAGIC: &[u8; 4] = b"VPAK";
+const PACK_VERSION: u32 = 1;
+// an index entry without its path.
+const PACK_ENTRY_LEN: u64 = 4 + 8 * 3;
+// deflate can't shrink data by more than this, so larger sizes mean a corrupt entry.
+const DEFLATE_MAX_RATIO: u64 = 1032;
+
+struct PackEntry {
+    offset: u64,
+    compressed_len: u64,
+    size: u64,
+}
+
+// a read-only pack file. entries are read and inflated on demand.
+pub struct Pack {
+    pub path: PathBuf,
+    entries: HashMap<String, PackEntry>,
+    data_start: u64,
+}
+
+impl Pack {
+    // lengths in the index are checked against the file before anything is allocated for them.
+    pub fn open(path: &str) -> Result<Self, Box<dyn Error>> {
+        let mut file = File::open(path)?;
+        let file_len = file.metadata()?.len();
+        let corrupt = || format!("{}: pack index is corrupt", path);
+        let mut magic = [0; 4];
+        file.read_exact(&mut magic)?;
+        if &magic != PACK_MAGIC {
+            return Err(format!("{}: not a virae pack", path).into());
+        }
+        let version = read_u32(&mut file)?;
+        if version != PACK_VERSION {
+            return Err(format!("{}: unsupported pack version {}", path, version).into());
+        }
+        let count = read_u32(&mut file)?;
+        if count as u64 * PACK_ENTRY_LEN > file_len - file.stream_position()? {
+            return Err(corrupt().into());
+        }
+        let mut entries = HashMap::new();
+        for _ in 0..count {
+            let name_len = read_u32(&mut file)? as u64;
+            if name_len > file_len - file.stream_position()? {
+                return Err(corrupt().into());
+            }
+            let mut name = vec![0; name_len as usize];
+            file.read_exact(&mut name)?;
+            let entry = PackEntry {
+                offset: read_u64(&mut file)?,
+                compressed_len: read_u64(&mut file)?,
+                size: read_u64(&mut file)?,
+            };
+            if entries.insert(String::from_utf8(name)?, entry).is_some() {
+                return Err(corrupt().into());
+            }
+        }
+        let data_start = file.stream_position()?;
+        let data_len = file_len - data_start;
+        for entry in entries.values() {
+            let in_file = entry
+                .offset
+                .checked_add(entry.compressed_len)
+                .is_some_and(|end| end <= data_len);
+            if !in_file || entry.size > entry.compressed_len.saturating_mul(DEFLATE_MAX_RATIO) {
+                return Err(corrupt().into());
+            }
+        }
+        // every entry has its own range of the data.
+        let mut ranges: Vec<(u64, u64)> = entries
+            .values()
+            .map(|entry| (entry.offset, entry.offset + entry.compressed_len))
+            .collect();
+        ranges.sort_unstable();
+        if ranges.windows(2).any(|pair| pair[1].0 < pair[0].1) {
+            return Err(corrupt().into());
+        }
+        Ok(Self {
+            path: PathBuf::from(path),
+            entries,
+            data_start,
+        })
+    }
+
+    pub fn contains(&self, path: &str) -> bool {
+        self.entries.contains_key(path)
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &String> {
+        self.entries.keys()
+    }
+
+    pub fn read(&self, path: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        let entry =
+            self.entries
+                .get(path)
+                .ok_or(format!("{}: no entry {}", self.path.display(), path))?;
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(self.data_start + entry.offset))?;
+        let mut bytes = Vec::with_capacity(entry.size as usize);
+        DeflateDecoder::new(file.take(entry.compressed_len)).read_to_end(&mut bytes)?;
+        if bytes.len() as u64 != entry.size {
+            return Err(format!("{}: entry {} is truncated", self.path.display(), path).into());
+        }
+        Ok(bytes)
+    }
+}
+
+// collects files and writes them out as a pack.
+pub struct PackBuilder {
+    entries: Vec<(String, Vec<u8>)>,
+}
+
+impl PackBuilder {
+    pub fn new() -> Self {
+        Self { entries: vec![] }
+    }
+
+    pub fn add(&mut self, path: &str, bytes: Vec<u8>) {
+        let path = normalize(path);
+        self.entries.retain(|(p, _)| *p != path);
+        self.entries.push((path, bytes));
+    }
+
+    // add every file under `dir`, named by its path as given, e.g. "examples/testing/a.png".
+    pub fn add_dir(&mut self, dir: &str) -> Result<usize, Box<dyn Error>> {
+        let mut count = 0;
+        let mut dirs = vec![PathBuf::from(dir)];
+        while let Some(dir) = dirs.pop() {
+            for entry in read_dir(&dir)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else {
+                    self.add(&path.to_string_lossy(), read(&path)?);
+                    count += 1;
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    pub fn write(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let mut index = vec![];
+        let mut data = vec![];
+        index.extend_from_slice(PACK_MAGIC);
+        index.extend_from_slice(&PACK_VERSION.to_le_bytes());
+        index.extend_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        for (name, bytes) in self.entries.iter() {
+            let mut encoder = DeflateEncoder::new(vec![], Compression::best());
+            encoder.write_all(bytes)?;
+            let compressed = encoder.finish()?;
+            index.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            index.extend_from_slice(name.as_bytes());
+            index.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            index.extend_from_slice(&(compressed.len() as u64).to_le_bytes());
+            index.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+            data.extend_from_slice(&compressed);
+        }
+        let mut file = File::create(path)?;
+        file.write_all(&index)?;
+        file.write_all(&data)?;
+        Ok(())
+    }
+}
+
+impl Default for PackBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub enum VfsSource {
+    Pack(Pack),
+    Directory(PathBuf),
+    Embedded(HashMap<String, &'static [u8]>),
+}
+
+// where asset paths resolve from. sources are searched most recently mounted first,
+// then the path is tried as-is on disk, relative to the working directory.
+pub struct Vfs {
+    pub sources: Vec<VfsSource>,
+}
+
+impl Vfs {
+    pub fn new() -> Self {
+        Self { sources: vec![] }
+    }
+
+    pub fn mount_pack(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
+        self.sources.push(VfsSource::Pack(Pack::open(path)?));
+        Ok(())
+    }
+
+    pub fn mount_dir(&mut self, dir: &str) {
+        self.sources.push(VfsSource::Directory(PathBuf::from(dir)));
+    }
+
+    // bytes compiled into the binary, e.g. with include_bytes!.
+    pub fn mount_embedded(&mut self, path: &str, bytes: &'static [u8]) {
+        let path = normalize(path);
+        match self.sources.last_mut() {
+            Some(VfsSource::Embedded(files)) => {
+                files.insert(path, bytes);
+            }
+            _ => self
+                .sources
+                .push(VfsSource::Embedded(HashMap::from([(path, bytes)]))),
+        }
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        let path = normalize(path);
+        self.sources.iter().rev().any(|source| match source {
+            VfsSource::Pack(pack) => pack.contains(&path),
+            VfsSource::Directory(dir) => dir.join(&path).is_file(),
+            VfsSource::Embedded(files) => files.contains_key(&path),
+        }) || Path::new(&path).is_file()
+    }
+
+    pub fn read(&self, path: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        let path = normalize(path);
+        for source in self.sources.iter().rev() {
+            match source {
+                VfsSource::Pack(pack) if pack.contains(&path) => return pack.read(&path),
+                VfsSource::Directory(dir) if dir.join(&path).is_file() => {
+                    return Ok(read(dir.join(&path))?)
+                }
+                VfsSource::Embedded(files) if files.contains_key(&path) => {
+                    return Ok(files[&path].to_vec())
+                }
+                _ => (),
+            }
+        }
+        match read(&path) {
+            Ok(bytes) => Ok(bytes),
+            Err(e) => Err(format!("{}: {}", path, e).into()),
+        }
+    }
+
+    pub fn read_to_string(&self, path: &str) -> Result<String, Box<dyn Error>> {
+        Ok(String::from_utf8(self.read(path)?)?)
+    }
+
+    // the file on disk a path resolves to, if any, for hot reloading.
+    pub fn disk_path(&self, path: &str) -> Option<PathBuf> {
+        let path = normalize(path);
+        for source in self.sources.iter().rev() {
+            match source {
+                VfsSource::Pack(pack) if pack.contains(&path) => return None,
+                VfsSource::Directory(dir) if dir.join(&path).is_file() => {
+                    return Some(dir.join(&path))
+                }
+                VfsSource::Embedded(files) if files.contains_key(&path) => return None,
+                _ => (),
+            }
+        }
+        Some(PathBuf::from(&path)).filter(|p| p.is_file())
+    }
+}
+
+impl Default for Vfs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// forward slashes, no leading "./", so one asset has one name wherever it's stored.
+fn normalize(path: &str) -> String {
+    let path = path.replace('\\', "/");
+    let mut path = path.as_str();
+    while let Some(rest) = path.strip_prefix("./") {
+        path = rest;
+    }
+    path.to_string()
+}
+
+fn read_u32(file: &mut File) -> Result<u32, Box<dyn Error>> {
+    let mut bytes = [0; 4];
+    file.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(file: &mut File) -> Result<u64, Box<dyn Error>> {
+    let mut bytes = [0; 8];
+    file.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a pack file of its own for each test, under the system temp directory.
+    fn temp_pack(name: &str, bytes: &[u8]) -> String {
+        let path = std::env::temp_dir().join(format!("virae-{}-{}.vpak", std::process::id(), name));
+        std::fs::write(&path, bytes).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    fn built_pack(name: &str) -> Vec<u8> {
+        let mut builder = PackBuilder::new();
+        builder.add("./text\\hello.txt", b"hello, pack".to_vec());
+        builder.add("bytes.bin", (0..=255).cycle().take(4000).collect());
+        builder.add("empty", vec![]);
+        let path = temp_pack(name, &[]);
+        builder.write(&path).unwrap();
+        std::fs::read(&path).unwrap()
+    }
+
+    fn deflate(bytes: &[u8]) -> Vec<u8> {
+        let mut encoder = DeflateEncoder::new(vec![], Compression::best());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn index_entry(index: &mut Vec<u8>, name: &str, offset: u64, compressed_len: u64, size: u64) {
+        index.extend_from_slice(&(name.len() as u32).to_le_bytes());
+        index.extend_from_slice(name.as_bytes());
+        for n in [offset, compressed_len, size] {
+            index.extend_from_slice(&n.to_le_bytes());
+        }
+    }
+
+    fn pack_header(count: u32) -> Vec<u8> {
+        let mut bytes = PACK_MAGIC.to_vec();
+        bytes.extend_from_slice(&PACK_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&count.to_le_bytes());
+        bytes
+    }
+
+    fn is_corrupt(result: Result<Pack, Box<dyn Error>>) -> bool {
+        result.is_err_and(|e| e.to_string().ends_with("pack index is corrupt"))
+    }
+
+    #[test]
+    fn built_packs_read_back_identically() {
+        let path = temp_pack("round-trip", &built_pack("round-trip-build"));
+        let pack = Pack::open(&path).unwrap();
+        let mut paths: Vec<&String> = pack.paths().collect();
+        paths.sort();
+        assert_eq!(paths, ["bytes.bin", "empty", "text/hello.txt"]);
+        assert_eq!(pack.read("text/hello.txt").unwrap(), b"hello, pack");
+        assert_eq!(
+            pack.read("bytes.bin").unwrap(),
+            (0..=255).cycle().take(4000).collect::<Vec<u8>>()
+        );
+        assert!(pack.read("empty").unwrap().is_empty());
+        assert!(pack.read("missing").is_err());
+
+        let mut vfs = Vfs::new();
+        vfs.mount_pack(&path).unwrap();
+        assert!(vfs.exists("./text/hello.txt"));
+        assert_eq!(
+            vfs.read_to_string("text\\hello.txt").unwrap(),
+            "hello, pack"
+        );
+        assert_eq!(vfs.disk_path("text/hello.txt"), None);
+    }
+
+    #[test]
+    fn truncated_packs_are_errors() {
+        let bytes = built_pack("truncated-build");
+        for len in 0..bytes.len() {
+            let path = temp_pack("truncated", &bytes[..len]);
+            assert!(Pack::open(&path).is_err(), "cut at {} opened", len);
+        }
+    }
+
+    #[test]
+    fn bad_magic_and_versions_are_errors() {
+        let mut bytes = built_pack("magic-build");
+        bytes[0] = b'X';
+        let result = Pack::open(&temp_pack("magic", &bytes));
+        assert!(result.is_err_and(|e| e.to_string().ends_with("not a virae pack")));
+
+        let mut bytes = built_pack("version-build");
+        bytes[4] = 2;
+        let result = Pack::open(&temp_pack("version", &bytes));
+        assert!(result.is_err_and(|e| e.to_string().ends_with("unsupported pack version 2")));
+    }
+
+    #[test]
+    fn overlapping_entries_are_errors() {
+        let data = deflate(b"shared");
+        let len = data.len() as u64;
+        let mut bytes = pack_header(2);
+        index_entry(&mut bytes, "a", 0, len, 6);
+        index_entry(&mut bytes, "b", len - 1, 1, 1);
+        bytes.extend_from_slice(&data);
+        assert!(is_corrupt(Pack::open(&temp_pack("overlap", &bytes))));
+
+        let mut bytes = pack_header(2);
+        index_entry(&mut bytes, "a", 0, len, 6);
+        index_entry(&mut bytes, "a", 0, len, 6);
+        bytes.extend_from_slice(&data);
+        assert!(is_corrupt(Pack::open(&temp_pack("duplicate", &bytes))));
+    }
+
+    #[test]
+    fn impossible_lengths_are_errors() {
+        let data = deflate(b"shared");
+        let len = data.len() as u64;
+        let cases = [
+            (u64::MAX, len, 6),
+            (0, u64::MAX, 6),
+            (0, len + 1, 6),
+            (0, len, u64::MAX),
+        ];
+        for (i, (offset, compressed_len, size)) in cases.into_iter().enumerate() {
+            let mut bytes = pack_header(1);
+            index_entry(&mut bytes, "a", offset, compressed_len, size);
+            bytes.extend_from_slice(&data);
+            let path = temp_pack(&format!("lengths-{}", i), &bytes);
+            assert!(is_corrupt(Pack::open(&path)), "case {} opened", i);
+        }
+
+        let mut bytes = pack_header(u32::MAX);
+        index_entry(&mut bytes, "a", 0, len, 6);
+        assert!(is_corrupt(Pack::open(&temp_pack("count", &bytes))));
+    }
+
+    #[test]
+    fn sizes_that_disagree_with_the_data_are_errors() {
+        let data = deflate(b"shared");
+        let mut bytes = pack_header(1);
+        index_entry(&mut bytes, "a", 0, data.len() as u64, 7);
+        bytes.extend_from_slice(&data);
+        let pack = Pack::open(&temp_pack("size", &bytes)).unwrap();
+        assert!(pack
+            .read("a")
+            .is_err_and(|e| e.to_string().ends_with("entry a is truncated")));
+    }
+}
@@ -1,12 +1,12 @@
-use glam::UVec2;
+use glam::{UVec2, Vec2};
 use wgpu::{
-    CommandEncoderDescriptor, CompositeAlphaMode, Device, DeviceDescriptor, Features, IndexFormat,
+    Buffer, CommandEncoderDescriptor, CompositeAlphaMode, Device, DeviceDescriptor, Features,
     Instance, InstanceDescriptor, Limits, LoadOp, Operations, PresentMode, Queue,
     RenderPassColorAttachment, RenderPassDescriptor, RequestAdapterOptions, Surface,
     SurfaceConfiguration, TextureFormat, TextureUsages, TextureViewDescriptor,
 };
 use winit::{
-    dpi::{LogicalSize, PhysicalSize},
+    dpi::{LogicalPosition, LogicalSize, PhysicalPosition, PhysicalSize},
     event_loop::{ControlFlow, EventLoop},
     window::WindowBuilder,
 };
@@ -15,18 +15,28 @@ use std::{
     error::Error,
     fs::metadata,
     sync::{Arc, Mutex},
-    time::SystemTime,
+    time::{Instant, SystemTime},
 };
 
-use crate::geo::GeoManager;
+use crate::assets::LoadProgress;
+use crate::geo::{GeoManager, SpriteBatch};
+use crate::hierarchy::{Hierarchy, LocalTransform, NodeRef};
+use crate::scaling::{ScalingMode, VirtualResolution};
+use crate::shapes::{ShapeBatch, ShapeRenderer};
 use crate::text::TextCollection;
+use crate::tilemap::Tilemap;
+use crate::tween::Tweens;
+use crate::types::DrawRun;
 
 enum FileWatcherAction {
     ReloadShader,
+    ReloadAsset,
 }
 
 struct FileWatcherEntry {
     path: String,
+    // where the file is on disk, which differs from `path` for files in a mounted directory.
+    disk_path: String,
     last_modified: SystemTime,
     action: FileWatcherAction,
 }
@@ -41,15 +51,37 @@ impl FileWatcher {
     }
 
     pub fn add_path(&mut self, path: &str) {
-        let metadata = metadata(path).unwrap();
+        self.add(path, path, FileWatcherAction::ReloadShader);
+    }
+
+    // reload whatever the asset cache loaded from `path` when `disk_path` changes.
+    pub fn add_asset_path(&mut self, path: &str, disk_path: &str) {
+        self.add(path, disk_path, FileWatcherAction::ReloadAsset);
+    }
+
+    pub fn is_watched(&self, path: &str) -> bool {
+        self.entries.iter().any(|fwe| fwe.path == path)
+    }
+
+    // files that aren't on disk, e.g. ones only in a pack or embedded, can't change and
+    // aren't watched.
+    fn add(&mut self, path: &str, disk_path: &str, action: FileWatcherAction) {
+        let Ok(last_modified) = metadata(disk_path).and_then(|m| m.modified()) else {
+            return;
+        };
         self.entries.push(FileWatcherEntry {
             path: path.to_string(),
-            last_modified: metadata.modified().unwrap(),
-            action: FileWatcherAction::ReloadShader,
+            disk_path: disk_path.to_string(),
+            last_modified,
+            action,
         })
     }
 }
 
+// called from Context::update while background loads are outstanding, and once more when they
+// finish, e.g. to show loading progress and then build the scene that needed them.
+pub type LoadingHook = Box<dyn FnMut(&mut Context, LoadProgress) -> Result<(), Box<dyn Error>>>;
+
 pub struct Context<'a> {
     pub device: Arc<Mutex<Device>>,
     pub queue: Arc<Mutex<Queue>>,
@@ -59,7 +91,17 @@ pub struct Context<'a> {
     pub scale_factor: f64,
     pub texts: TextCollection,
     pub geos: GeoManager,
+    pub shapes: ShapeRenderer,
+    pub hierarchy: Hierarchy,
+    pub tweens: Tweens,
+    // drawn with their groups, culled to the camera each frame.
+    pub tilemaps: Vec<Tilemap>,
     pub file_watcher: FileWatcher,
+    pub virtual_resolution: Option<VirtualResolution>,
+    pub delta_time: f32,
+    pub loading_hook: Option<LoadingHook>,
+    last_update: Instant,
+    loads_finished: usize,
 }
 
 impl Context<'_> {
@@ -119,9 +161,20 @@ impl Context<'_> {
         };
         surface.configure(&device, &config);
 
+        let shapes = ShapeRenderer::new(&device, swapchain_format);
         let device_arc = Arc::<Mutex<Device>>::new(Mutex::new(device));
         let queue_arc = Arc::<Mutex<Queue>>::new(Mutex::new(queue));
-        let texts = TextCollection::new(device_arc.clone(), queue_arc.clone(), swapchain_format);
+        let texts = TextCollection::new(
+            device_arc.clone(),
+            queue_arc.clone(),
+            swapchain_format,
+            scale_factor,
+        );
+        let mut geos = GeoManager::new(device_arc.clone(), queue_arc.clone(), swapchain_format);
+        geos.extent = UVec2::new(
+            (size.width as f64 / scale_factor).round() as u32,
+            (size.height as f64 / scale_factor).round() as u32,
+        );
 
         Ok((
             event_loop,
@@ -134,74 +187,294 @@ impl Context<'_> {
                 swapchain_format,
                 scale_factor,
                 texts,
-                geos: GeoManager::new(device_arc.clone(), queue_arc.clone(), swapchain_format),
+                geos,
+                shapes,
+                hierarchy: Hierarchy::new(),
+                tweens: Tweens::new(),
+                tilemaps: vec![],
                 file_watcher: FileWatcher::new(),
+                virtual_resolution: None,
+                delta_time: 0.0,
+                loading_hook: None,
+                last_update: Instant::now(),
+                loads_finished: 0,
             },
         ))
     }
 
     pub fn check_watched_files(&mut self) -> Result<(), Box<dyn Error>> {
         for fwe in self.file_watcher.entries.iter_mut() {
-            let metadata = metadata(&*fwe.path)?;
-            if metadata.modified().unwrap() > fwe.last_modified {
+            // a file an editor is replacing may be briefly missing; it's checked again next time.
+            let Ok(modified) = metadata(&*fwe.disk_path).and_then(|m| m.modified()) else {
+                continue;
+            };
+            if modified > fwe.last_modified {
+                fwe.last_modified = modified;
                 match fwe.action {
                     FileWatcherAction::ReloadShader => {
                         self.geos.reload_shader(self.device.clone(), &fwe.path)?;
                     }
+                    FileWatcherAction::ReloadAsset => {
+                        self.geos.reload_asset(&fwe.path);
+                    }
                 }
             }
         }
         Ok(())
     }
 
+    // watch every file the asset cache has loaded so far, hot reloading them on change.
+    // files read from packs or embedded bytes can't change, and are skipped.
+    pub fn watch_assets(&mut self) {
+        let vfs = self.geos.assets.vfs.clone();
+        let vfs = vfs.lock().unwrap();
+        for path in self.geos.assets.source_paths() {
+            if self.file_watcher.is_watched(&path) {
+                continue;
+            }
+            if let Some(disk_path) = vfs.disk_path(&path) {
+                self.file_watcher
+                    .add_asset_path(&path, &disk_path.to_string_lossy());
+            }
+        }
+    }
+
+    // the space PixelRects and text labels are placed in: the window size in logical pixels,
+    // or the virtual resolution when one is set.
+    pub fn logical_size(&self) -> UVec2 {
+        if let Some(virtual_resolution) = &self.virtual_resolution {
+            return virtual_resolution.size;
+        }
+        let config = self.config.lock().unwrap();
+        UVec2::new(
+            (config.width as f64 / self.scale_factor).round() as u32,
+            (config.height as f64 / self.scale_factor).round() as u32,
+        )
+    }
+
+    // map a window position (e.g. from CursorMoved) into logical_size space.
+    pub fn to_logical(&self, position: PhysicalPosition<f64>) -> Vec2 {
+        if let Some(virtual_resolution) = &self.virtual_resolution {
+            let config = self.config.lock().unwrap();
+            return virtual_resolution.window_to_virtual(
+                UVec2::new(config.width, config.height),
+                Vec2::new(position.x as f32, position.y as f32),
+            );
+        }
+        let position: LogicalPosition<f64> = position.to_logical(self.scale_factor);
+        Vec2::new(position.x as f32, position.y as f32)
+    }
+
+    // a logical_size position, e.g. from to_logical, in world space, and back. see Camera.
+    pub fn screen_to_world(&self, screen: Vec2) -> Vec2 {
+        self.geos.camera.screen_to_world(screen, self.geos.extent)
+    }
+
+    pub fn world_to_screen(&self, world: Vec2) -> Vec2 {
+        self.geos.camera.world_to_screen(world, self.geos.extent)
+    }
+
+    // call on WindowEvent::ScaleFactorChanged. winit follows it with a Resized event.
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor;
+        self.sync_render_size();
+    }
+
+    // render the scene at a fixed size, scaled onto the window by `mode`.
+    pub fn set_virtual_resolution(&mut self, size: UVec2, mode: ScalingMode) {
+        self.virtual_resolution = Some(VirtualResolution::new(
+            self.device.clone(),
+            self.swapchain_format,
+            size,
+            mode,
+        ));
+        self.sync_render_size();
+    }
+
+    pub fn clear_virtual_resolution(&mut self) {
+        self.virtual_resolution = None;
+        self.sync_render_size();
+    }
+
+    // size of the target the scene is drawn to, in physical pixels.
+    fn render_size(&self) -> UVec2 {
+        match &self.virtual_resolution {
+            Some(virtual_resolution) => virtual_resolution.size,
+            None => {
+                let config = self.config.lock().unwrap();
+                UVec2::new(config.width, config.height)
+            }
+        }
+    }
+
+    fn sync_render_size(&mut self) {
+        // the virtual target is already in layout pixels, so text is drawn unscaled into it.
+        self.texts.scale_factor = match self.virtual_resolution {
+            Some(_) => 1.0,
+            None => self.scale_factor,
+        };
+        // instances stay put in world space; only the view follows the new size.
+        self.geos.extent = self.logical_size();
+        let render_size = self.render_size();
+        self.geos
+            .update_view(self.queue.clone(), render_size.x, render_size.y);
+    }
+
     pub fn update(&mut self) -> Result<(), Box<dyn Error>> {
         self.check_watched_files()?;
-        let config = self.config.lock().unwrap();
+        let now = Instant::now();
+        self.delta_time = now.duration_since(self.last_update).as_secs_f32();
+        self.last_update = now;
+        self.update_loads()?;
+        self.texts.update(self.delta_time);
+        for tilemap in self.tilemaps.iter_mut() {
+            tilemap.update(self.delta_time);
+        }
+        let finished = self
+            .tweens
+            .update(self.delta_time, &mut self.geos, &mut self.texts);
+        for mut on_complete in finished {
+            on_complete(self)?;
+        }
+        self.hierarchy.propagate(&mut self.geos, &mut self.texts);
+        self.geos.sync_view(self.queue.clone());
         for group in self.geos.instance_groups.iter_mut() {
-            group.recalc_screen_instances(
-                self.queue.clone(),
-                UVec2::new(config.width, config.height),
-            )
+            group.recalc_screen_instances(self.queue.clone())
+        }
+        Ok(())
+    }
+
+    // make `child` follow `parent`, keeping its current placement.
+    pub fn attach(&mut self, child: NodeRef, parent: NodeRef) -> Result<(), Box<dyn Error>> {
+        self.hierarchy
+            .attach(child, parent, None, &self.geos, &self.texts)
+    }
+
+    // make `child` follow `parent` at `local` from the parent's pivot.
+    pub fn attach_at(
+        &mut self,
+        child: NodeRef,
+        parent: NodeRef,
+        local: LocalTransform,
+    ) -> Result<(), Box<dyn Error>> {
+        self.hierarchy
+            .attach(child, parent, Some(local), &self.geos, &self.texts)
+    }
+
+    pub fn detach(&mut self, child: NodeRef) {
+        self.hierarchy.detach(child);
+    }
+
+    pub fn reparent(&mut self, child: NodeRef, new_parent: NodeRef) -> Result<(), Box<dyn Error>> {
+        self.hierarchy
+            .reparent(child, new_parent, &self.geos, &self.texts)
+    }
+
+    // queue shapes for this frame, in logical_size pixels. they're cleared after render.
+    pub fn draw(&mut self) -> &mut ShapeBatch {
+        &mut self.shapes.batch
+    }
+
+    // queue shapes for this frame in world pixels, placed by the camera like instances,
+    // e.g. hitboxes. they're cleared after render.
+    pub fn draw_world(&mut self) -> &mut ShapeBatch {
+        &mut self.shapes.world_batch
+    }
+
+    // queue sprites from a group's sheet for this frame only, drawn after its retained
+    // instances on the same layer and cleared after render.
+    pub fn sprites(&mut self, group_index: usize) -> SpriteBatch<'_> {
+        SpriteBatch {
+            group: &mut self.geos.instance_groups[group_index],
+            layer: 0.0,
+        }
+    }
+
+    pub fn is_loading(&self) -> bool {
+        !self.geos.assets.progress().is_done()
+    }
+
+    fn update_loads(&mut self) -> Result<(), Box<dyn Error>> {
+        self.geos.assets.poll_loads();
+        let progress = self.geos.assets.progress();
+        // nothing is loading, e.g. after Assets::clear_loads.
+        if progress.total == 0 {
+            self.loads_finished = 0;
+            return Ok(());
+        }
+        // loads can finish within one poll, so compare against the last completed total.
+        if !progress.is_done() || progress.total != self.loads_finished {
+            // the hook is taken out while it runs, so it can use the context freely.
+            if let Some(mut hook) = self.loading_hook.take() {
+                let result = hook(self, progress);
+                if self.loading_hook.is_none() {
+                    self.loading_hook = Some(hook);
+                }
+                result?;
+            }
+            if progress.is_done() {
+                self.loads_finished = progress.total;
+            }
         }
         Ok(())
     }
 
     pub fn resize(&mut self, size: PhysicalSize<u32>) {
-        let device = self.device.lock().unwrap();
-        let mut config = self.config.lock().unwrap();
-        config.width = size.width;
-        config.height = size.height;
-        let surface = self.surface.lock().unwrap();
-        surface.configure(&device, &config);
-        self.geos
-            .update_view(self.queue.clone(), config.width, config.height);
-        for group in self.geos.instance_groups.iter_mut() {
-            group.mark_all_for_update();
+        {
+            let device = self.device.lock().unwrap();
+            let mut config = self.config.lock().unwrap();
+            config.width = size.width;
+            config.height = size.height;
+            let surface = self.surface.lock().unwrap();
+            surface.configure(&device, &config);
         }
+        self.sync_render_size();
     }
 
     pub fn render(&mut self) -> Result<(), Box<dyn Error>> {
+        let render_size = self.render_size();
+        let logical_size = self.logical_size();
         let config = self.config.lock().unwrap();
 
         self.texts.prepare(
             self.device.clone(),
             self.queue.clone(),
-            config.width,
-            config.height,
+            render_size.x,
+            render_size.y,
         )?;
 
         let device = self.device.lock().unwrap();
         let queue = self.queue.lock().unwrap();
         let surface = self.surface.lock().unwrap();
 
+        self.shapes.prepare(
+            &device,
+            &queue,
+            logical_size.as_vec2(),
+            self.geos.view_matrix(),
+        );
+        for tilemap in self.tilemaps.iter_mut() {
+            tilemap.prepare(&self.geos, &device, &queue);
+        }
+        for group in self.geos.instance_groups.iter_mut() {
+            group
+                .instance_buffer_manager
+                .write_transient(&device, &queue);
+        }
+
         let frame = surface.get_current_texture()?;
-        let view = frame.texture.create_view(&TextureViewDescriptor::default());
+        let frame_view = frame.texture.create_view(&TextureViewDescriptor::default());
         let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: None });
         {
+            // with a virtual resolution the scene goes offscreen first, then gets scaled up.
+            let view = match &self.virtual_resolution {
+                Some(virtual_resolution) => &virtual_resolution.view,
+                None => &frame_view,
+            };
             let mut pass = encoder.begin_render_pass(&RenderPassDescriptor {
                 label: None,
                 color_attachments: &[Some(RenderPassColorAttachment {
-                    view: &view,
+                    view,
                     resolve_target: None,
                     ops: Operations {
                         load: LoadOp::Clear(wgpu::Color {
@@ -218,37 +491,62 @@ impl Context<'_> {
                 occlusion_query_set: None,
             });
 
-            // include geos in pass
-            pass.set_pipeline(
-                &self.geos.instance_groups[0]
-                    .render_pipeline_record
-                    .render_pipeline,
-            );
-            pass.set_bind_group(0, &self.geos.instance_groups[0].bind_group, &[]);
-            pass.set_index_buffer(
-                self.geos.instance_groups[0].index_buffer.slice(..),
-                IndexFormat::Uint16,
-            );
-            pass.set_vertex_buffer(0, self.geos.instance_groups[0].vertex_buffer.slice(..));
-            pass.set_vertex_buffer(
-                1,
-                self.geos.instance_groups[0]
-                    .instance_buffer_manager
-                    .buffer
-                    .slice(..),
-            );
-            pass.draw_indexed(0..6_u32, 0, 0..self.geos.num_instances(0));
+            // include geos and tilemaps in pass, run by run in layer order, each with its
+            // group's pipeline and blend mode
+            let mut runs: Vec<(usize, DrawRun, &Buffer)> = vec![];
+            for tilemap in self.tilemaps.iter() {
+                if let Some(buffer) = tilemap.buffer() {
+                    runs.extend(
+                        tilemap
+                            .draw_runs()
+                            .iter()
+                            .map(|run| (tilemap.group, run.clone(), buffer)),
+                    );
+                }
+            }
+            runs.extend(self.geos.draw_runs().into_iter().map(|(group_index, run)| {
+                let group = &self.geos.instance_groups[group_index];
+                (group_index, run, &group.instance_buffer_manager.buffer)
+            }));
+            // lowest layer first, then by group. the sort is stable, so on one layer a group's
+            // tiles draw under its instances, and its runs keep their order.
+            runs.sort_by(|a, b| a.1.layer.total_cmp(&b.1.layer).then(a.0.cmp(&b.0)));
+            let mut bound = None;
+            for (group_index, run, buffer) in runs {
+                let group = &self.geos.instance_groups[group_index];
+                if bound != Some(group_index) {
+                    bound = Some(group_index);
+                    pass.set_pipeline(&group.render_pipeline_record.render_pipeline);
+                    pass.set_bind_group(0, &group.bind_group, &[]);
+                    pass.set_index_buffer(group.index_buffer.slice(..), group.index_format);
+                    pass.set_vertex_buffer(0, group.vertex_buffer.slice(..));
+                }
+                pass.set_vertex_buffer(1, buffer.slice(..));
+                pass.draw_indexed(0..group.index_count, 0, run.instances);
+            }
+
+            // immediate shapes over geometry, under text
+            self.shapes.render(&mut pass);
 
             // include text labels in pass
-            self.texts
-                .text_renderer
-                .render(&self.texts.atlas, &mut pass)?;
+            self.texts.render(&mut pass)?;
+        }
+        if let Some(virtual_resolution) = &self.virtual_resolution {
+            virtual_resolution.blit(
+                &queue,
+                &mut encoder,
+                &frame_view,
+                UVec2::new(config.width, config.height),
+            );
         }
 
         queue.submit(Some(encoder.finish()));
         frame.present();
         self.texts.trim_atlas();
-
+        self.shapes.clear();
+        for group in self.geos.instance_groups.iter_mut() {
+            group.instance_buffer_manager.transient.clear();
+        }
         Ok(())
     }
 }